@@ -0,0 +1,295 @@
+//! Headless entry point. Parses a trace without starting the Tauri app and prints a summary of
+//! each pool to stdout, so analyses can be scripted on machines without a display.
+//!
+//! Usage: damselfly_headless <log_path> <binary_path> [--left-padding N] [--right-padding N] [--cache-size N] [--format F] [--layout PATH] [--debug-dir DIR]... [--remap-path FROM=TO]... [--module PATH@START-END]... [--symbol-cache DIR] [--no-symbol-cache] [--lenient] [--validate] [--export PATH] [--export-binary PATH]
+//!
+//! Progress messages are written to stderr. The summary is written to stdout as tab-separated
+//! values with a header row. --format is systrace, mtrace, massif, dhat, heaptrack, kmem,
+//! damselfly or binary, and is detected from the log if not given. --layout loads a JSON or TOML
+//! file describing where the fields of SysTrace lines are, for logs whose line headers differ
+//! from the default. --debug-dir adds a directory to search for the binary's separate debuginfo
+//! file (found by build ID or .gnu_debuglink), and --remap-path replaces the source path prefix
+//! FROM with TO in symbolised callstacks. --module adds another binary loaded by the program at
+//! the hex addresses START to END, whose debuginfo symbolises addresses in that range. These can
+//! be given more than once. Symbolised frames are cached in the user's cache directory, or DIR with
+//! --symbol-cache, so later traces of the same build skip the DWARF lookups. --no-symbol-cache
+//! turns this off. With --lenient, malformed records are skipped and listed on stderr.
+//! With --validate, anomalies (double frees etc.) are written to stdout instead of the summary,
+//! and the exit code is non-zero if any were found.
+//! With --export, the parsed trace is written to PATH in Damselfly's own format instead, so that it
+//! can be reopened without the original log or binary. --export-binary does the same in a compact
+//! binary format that loads much faster.
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+
+use damselfly3::damselfly::consts::DEFAULT_CACHE_INTERVAL;
+use damselfly3::damselfly::memory::binary_module::BinaryModule;
+use damselfly3::damselfly::memory::binary_format::BinaryTraceWriter;
+use damselfly3::damselfly::memory::log_format::{FormatParser, LogFormat};
+use damselfly3::damselfly::memory::memory_parsers::MemoryParser;
+use damselfly3::damselfly::memory::native_format::NativeTraceWriter;
+use damselfly3::damselfly::memory::symbol_cache::SymbolCache;
+use damselfly3::damselfly::memory::symbol_options::SymbolOptions;
+use damselfly3::damselfly::memory::systrace_layout::SysTraceLayout;
+use damselfly3::damselfly::viewer::damselfly_viewer::DamselflyViewer;
+
+const USAGE: &str = "Usage: damselfly_headless <log_path> <binary_path> [--left-padding N] [--right-padding N] [--cache-size N] [--format F] [--layout PATH] [--debug-dir DIR]... [--remap-path FROM=TO]... [--module PATH@START-END]... [--symbol-cache DIR] [--no-symbol-cache] [--lenient] [--validate] [--export PATH] [--export-binary PATH]";
+
+struct HeadlessArgs {
+    log_path: String,
+    binary_path: String,
+    left_padding: usize,
+    right_padding: usize,
+    cache_size: u64,
+    format: Option<LogFormat>,
+    layout_path: Option<String>,
+    symbol_options: SymbolOptions,
+    lenient: bool,
+    validate: bool,
+    export_path: Option<String>,
+    export_binary_path: Option<String>,
+}
+
+impl HeadlessArgs {
+    /// Parses command-line arguments, excluding the program name.
+    ///
+    /// # Arguments
+    ///
+    /// * `args`: Arguments passed to the binary.
+    ///
+    /// returns: Result<HeadlessArgs, String>
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut left_padding = 0;
+        let mut right_padding = 0;
+        let mut cache_size = DEFAULT_CACHE_INTERVAL;
+        let mut format = None;
+        let mut layout_path = None;
+        let mut symbol_options = SymbolOptions::new();
+        let mut symbol_cache_dir = SymbolCache::default_dir();
+        let mut lenient = false;
+        let mut validate = false;
+        let mut export_path = None;
+        let mut export_binary_path = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--left-padding" => left_padding = Self::parse_value(&arg, args.next())?,
+                "--right-padding" => right_padding = Self::parse_value(&arg, args.next())?,
+                "--cache-size" => cache_size = Self::parse_value(&arg, args.next())?,
+                "--format" => format = Some(Self::parse_value(&arg, args.next())?),
+                "--layout" => layout_path = Some(Self::parse_value(&arg, args.next())?),
+                "--debug-dir" => symbol_options = symbol_options.with_debug_dir(Self::parse_value(&arg, args.next())?),
+                "--remap-path" => {
+                    let remap: String = Self::parse_value(&arg, args.next())?;
+                    let (from, to) = SymbolOptions::parse_path_remap(&remap)
+                        .map_err(|reason| format!("Invalid value for {arg}: {reason}\n{USAGE}"))?;
+                    symbol_options = symbol_options.with_path_remap(from, to);
+                }
+                "--module" => {
+                    let module: String = Self::parse_value(&arg, args.next())?;
+                    let module = BinaryModule::parse(&module)
+                        .map_err(|reason| format!("Invalid value for {arg}: {reason}\n{USAGE}"))?;
+                    symbol_options = symbol_options.with_module(module);
+                }
+                "--symbol-cache" => symbol_cache_dir = Some(Self::parse_value(&arg, args.next())?),
+                "--no-symbol-cache" => symbol_cache_dir = None,
+                "--lenient" => lenient = true,
+                "--validate" => validate = true,
+                "--export" => export_path = Some(Self::parse_value(&arg, args.next())?),
+                "--export-binary" => export_binary_path = Some(Self::parse_value(&arg, args.next())?),
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {flag}\n{USAGE}")),
+                _ => positional.push(arg),
+            }
+        }
+
+        if let Some(symbol_cache_dir) = symbol_cache_dir {
+            symbol_options = symbol_options.with_cache_dir(symbol_cache_dir);
+        }
+        if positional.len() != 2 {
+            return Err(USAGE.to_string());
+        }
+        let binary_path = positional.pop().unwrap();
+        let log_path = positional.pop().unwrap();
+
+        Ok(Self {
+            log_path,
+            binary_path,
+            left_padding,
+            right_padding,
+            // A cache size of 0 would divide by zero when querying the map cache
+            cache_size: cache_size.max(1),
+            format,
+            layout_path,
+            symbol_options,
+            lenient,
+            validate,
+            export_path,
+            export_binary_path,
+        })
+    }
+
+    fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+        let value = value.ok_or_else(|| format!("Missing value for {flag}\n{USAGE}"))?;
+        value.parse::<T>().map_err(|_| format!("Invalid value for {flag}: {value}\n{USAGE}"))
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match HeadlessArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let format = match args.format {
+        Some(format) => format,
+        None => match LogFormat::detect(&args.log_path) {
+            Ok(format) => format,
+            Err(error) => {
+                eprintln!("Failed to load trace: {error}");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    eprintln!("Parsing {format} log");
+    let mut parser = FormatParser::new(format, args.lenient).with_symbol_options(args.symbol_options.clone());
+    if let Some(layout_path) = &args.layout_path {
+        match SysTraceLayout::load(layout_path) {
+            Ok(layout) => parser = parser.with_systrace_layout(layout),
+            Err(error) => {
+                eprintln!("Failed to load trace: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if args.export_path.is_some() || args.export_binary_path.is_some() {
+        return export_trace(&args, parser);
+    }
+    let viewer = match DamselflyViewer::new(
+        &args.log_path,
+        &args.binary_path,
+        args.cache_size,
+        args.left_padding,
+        args.right_padding,
+        parser,
+    ) {
+        Ok(viewer) => viewer,
+        Err(error) => {
+            eprintln!("Failed to load trace: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for diagnostic in &viewer.diagnostics {
+        eprintln!("Skipped line {}: {}: {}", diagnostic.get_line(), diagnostic.get_reason(), diagnostic.get_text());
+    }
+
+    if args.validate {
+        return print_anomalies(&viewer);
+    }
+
+    println!("pool\tstart\tsize\toperations\tpeak_usage\tpeak_distinct_blocks\tworst_free_segment_fragmentation\tlargest_free_block");
+    for damselfly in &viewer.damselflies {
+        let (lowest_address, highest_address) = damselfly.get_pool_bounds();
+        println!(
+            "{}\t0x{:x}\t{}\t{}\t{}\t{}\t{}\t{}",
+            damselfly.get_name(),
+            lowest_address,
+            highest_address - lowest_address,
+            damselfly.get_operation_count(),
+            damselfly.get_max_usage(),
+            damselfly.get_max_distinct_blocks(),
+            damselfly.get_max_free_segment_fragmentation(),
+            damselfly.get_max_largest_free_block(),
+        );
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Parses a trace and writes it in Damselfly's own formats, to whichever export paths were given.
+///
+/// # Arguments
+///
+/// * `args`: Command-line arguments.
+/// * `parser`: Parser for the trace's format.
+///
+/// returns: Success if the trace was written
+fn export_trace(args: &HeadlessArgs, parser: FormatParser) -> ExitCode {
+    let format = parser.get_format();
+    let parse_results = match parser.parse_log(&args.log_path, &args.binary_path) {
+        Ok(parse_results) => parse_results,
+        Err(error) => {
+            eprintln!("Failed to load trace: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for diagnostic in &parse_results.diagnostics {
+        eprintln!("Skipped line {}: {}: {}", diagnostic.get_line(), diagnostic.get_reason(), diagnostic.get_text());
+    }
+
+    let metadata = BTreeMap::from([
+        (String::from("source_format"), format.to_string()),
+        (String::from("source_log"), args.log_path.clone()),
+        (String::from("binary"), args.binary_path.clone()),
+    ]);
+    let mut exports = Vec::new();
+    if let Some(export_path) = &args.export_path {
+        exports.push((export_path, NativeTraceWriter::write_to_file(&parse_results, &metadata, export_path)));
+    }
+    if let Some(export_binary_path) = &args.export_binary_path {
+        exports.push((export_binary_path, BinaryTraceWriter::write_to_file(&parse_results, export_binary_path)));
+    }
+    for (export_path, result) in exports {
+        match result {
+            Ok(()) => eprintln!("Exported {} memory operations to {export_path}", parse_results.memory_updates.len()),
+            Err(error) => {
+                eprintln!("Failed to export trace: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints every anomaly in the trace as tab-separated values with a header row. Callstacks are
+/// printed on one line, with frames separated by " | ".
+///
+/// # Arguments
+///
+/// * `viewer`: The loaded trace.
+///
+/// returns: Success if the trace has no anomalies
+fn print_anomalies(viewer: &DamselflyViewer) -> ExitCode {
+    let one_line = |callstack: &str| callstack.trim_end().replace('\n', " | ");
+    println!("anomaly\ttimestamp\treal_timestamp\taddress\tsize\tcallstack\trelated_timestamp\trelated_callstack");
+    for anomaly in &viewer.anomalies {
+        let update = anomaly.get_update();
+        let (related_timestamp, related_callstack) = match anomaly.get_related_update() {
+            Some(related_update) => (related_update.get_timestamp().to_string(), one_line(&related_update.get_callstack())),
+            None => (String::new(), String::new()),
+        };
+        println!(
+            "{}\t{}\t{}\t0x{:x}\t{}\t{}\t{}\t{}",
+            anomaly.get_kind(),
+            update.get_timestamp(),
+            update.get_real_timestamp(),
+            update.get_absolute_address(),
+            update.get_absolute_size(),
+            one_line(&update.get_callstack()),
+            related_timestamp,
+            related_callstack,
+        );
+    }
+    eprintln!("Found {} anomalies", viewer.anomalies.len());
+
+    if viewer.anomalies.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
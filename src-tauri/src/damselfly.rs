@@ -2,4 +2,5 @@ pub mod memory;
 pub mod update_interval;
 pub mod viewer;
 pub mod consts;
+pub mod damselfly_error;
 
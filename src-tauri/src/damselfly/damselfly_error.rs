@@ -0,0 +1,101 @@
+//! Errors that can occur while loading a trace.
+//!
+//! Errors that stem from a specific line in the log carry the line number (starting from 1), so
+//! the offending record can be found in the log.
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum DamselflyError {
+    /// A file could not be opened or read.
+    Io { path: String, source: std::io::Error },
+    /// An address could not be parsed as hex.
+    BadAddress { line: usize, text: String },
+    /// A size could not be parsed.
+    BadSize { line: usize, text: String },
+    /// A realtime timestamp could not be parsed, has an unknown unit, or is out of range. The line
+    /// is 0 if the timestamp was not read from a log line.
+    BadTimestamp { line: usize, text: String },
+    /// A stack trace record appeared before any allocation or free it could belong to.
+    OrphanStackTrace { line: usize },
+    /// A record is missing fields or has an unknown operation type.
+    MalformedRecord { line: usize, reason: String },
+    /// The binary could not be parsed as an object file.
+    BadBinary { path: String, reason: String },
+    /// The binary contains neither DWARF debuginfo nor a symbol table, so addresses cannot be
+    /// symbolised.
+    MissingDwarf { path: String, reason: String },
+    /// A binary trace is truncated, from an unsupported version, or otherwise unreadable.
+    CorruptTrace { path: String, reason: String },
+    /// A SysTrace line layout file is not valid.
+    BadLayout { path: String, reason: String },
+    /// The log does not contain any memory operations.
+    EmptyTrace,
+    /// Memory operations were sorted into pools that do not match the pool list.
+    InconsistentPools { reason: String },
+    /// None of the memory operations in a pool were made by the threads being filtered for.
+    NoThreadOperations { pool: String },
+}
+
+impl DamselflyError {
+    /// Gets the line in the log that caused this error, if it came from a specific line.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            DamselflyError::BadAddress { line, .. }
+            | DamselflyError::BadSize { line, .. }
+            | DamselflyError::BadTimestamp { line, .. }
+            | DamselflyError::OrphanStackTrace { line }
+            | DamselflyError::MalformedRecord { line, .. } => Some(*line).filter(|line| *line > 0),
+            _ => None,
+        }
+    }
+
+    /// Describes the error without the line number prefix.
+    pub fn reason(&self) -> String {
+        match self {
+            DamselflyError::Io { path, source } =>
+                format!("Failed to read {path}: {source}"),
+            DamselflyError::BadAddress { text, .. } =>
+                format!("invalid hex address \"{text}\""),
+            DamselflyError::BadSize { text, .. } =>
+                format!("invalid size \"{text}\""),
+            DamselflyError::BadTimestamp { text, .. } =>
+                format!("invalid timestamp \"{text}\""),
+            DamselflyError::OrphanStackTrace { .. } =>
+                "stack trace does not follow an allocation or free".to_string(),
+            DamselflyError::MalformedRecord { reason, .. } =>
+                reason.clone(),
+            DamselflyError::BadBinary { path, reason } =>
+                format!("Failed to parse binary {path}: {reason}"),
+            DamselflyError::MissingDwarf { path, reason } =>
+                format!("No usable DWARF debuginfo or symbol table in {path}: {reason}"),
+            DamselflyError::CorruptTrace { path, reason } =>
+                format!("Failed to load binary trace {path}: {reason}"),
+            DamselflyError::BadLayout { path, reason } =>
+                format!("Invalid line layout {path}: {reason}"),
+            DamselflyError::EmptyTrace =>
+                "Log does not contain any memory operations".to_string(),
+            DamselflyError::InconsistentPools { reason } =>
+                format!("Inconsistent pools: {reason}"),
+            DamselflyError::NoThreadOperations { pool } =>
+                format!("No memory operations in {pool} were made by the selected threads"),
+        }
+    }
+}
+
+impl Display for DamselflyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.line() {
+            Some(line) => write!(f, "Line {line}: {}", self.reason()),
+            None => write!(f, "{}", self.reason()),
+        }
+    }
+}
+
+impl std::error::Error for DamselflyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DamselflyError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
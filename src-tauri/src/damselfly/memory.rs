@@ -16,3 +16,28 @@ pub mod memory_usage_sample;
 pub mod memory_usage_stats;
 pub mod memory_pool;
 pub mod memory_pool_list;
+pub mod parse_diagnostic;
+pub mod log_lines;
+pub mod trace_anomaly;
+pub mod trace_validator;
+pub mod leak_report_factory;
+pub mod allocation_site_factory;
+pub mod allocation_statistics;
+pub mod allocation_statistics_factory;
+pub mod mtrace_parser;
+pub mod log_format;
+pub mod massif_parser;
+pub mod dhat_parser;
+pub mod heaptrack_parser;
+pub mod kmem_parser;
+pub mod native_format;
+pub mod binary_format;
+pub mod log_reader;
+pub mod systrace_layout;
+pub mod cycle_counter_clock;
+pub mod pool_lifetime;
+pub mod symbol_frame;
+pub mod symbol_table;
+pub mod symbol_options;
+pub mod binary_module;
+pub mod symbol_cache;
@@ -0,0 +1,304 @@
+//! Aggregates memory operations by the callstack that made them, to show which code paths
+//! dominate heap traffic.
+//!
+//! Callstacks can be truncated to their first few frames, so that allocations made through the
+//! same wrapper from different callers can be grouped together (or told apart).
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
+
+/// Statistics for the allocations made from a single callstack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllocationSite {
+    callstack: Arc<String>,
+    allocation_count: usize,
+    total_bytes: usize,
+    live_bytes: usize,
+    peak_live_bytes: usize,
+    freed_count: usize,
+    total_lifetime: usize,
+    total_lifetime_microseconds: f64,
+}
+
+impl AllocationSite {
+    fn new(callstack: Arc<String>) -> AllocationSite {
+        AllocationSite {
+            callstack,
+            allocation_count: 0,
+            total_bytes: 0,
+            live_bytes: 0,
+            peak_live_bytes: 0,
+            freed_count: 0,
+            total_lifetime: 0,
+            total_lifetime_microseconds: 0.0,
+        }
+    }
+
+    pub fn get_callstack(&self) -> Arc<String> {
+        self.callstack.clone()
+    }
+
+    /// Gets the number of allocations (and reallocations) made from this callstack.
+    pub fn get_allocation_count(&self) -> usize {
+        self.allocation_count
+    }
+
+    /// Gets the number of bytes allocated from this callstack over the whole trace.
+    pub fn get_total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Gets the most bytes held by allocations from this callstack at any one time.
+    pub fn get_peak_live_bytes(&self) -> usize {
+        self.peak_live_bytes
+    }
+
+    /// Gets the mean number of operations between an allocation from this callstack and its free.
+    /// Allocations that are never freed are not counted.
+    ///
+    /// returns: mean lifetime, or None if no allocation from this callstack was freed
+    pub fn get_mean_lifetime(&self) -> Option<f64> {
+        if self.freed_count == 0 {
+            return None;
+        }
+        Some(self.total_lifetime as f64 / self.freed_count as f64)
+    }
+
+    /// Gets the mean time in microseconds between an allocation from this callstack and its free.
+    /// Allocations that are never freed are not counted.
+    ///
+    /// returns: mean lifetime, or None if no allocation from this callstack was freed
+    pub fn get_mean_lifetime_microseconds(&self) -> Option<f64> {
+        if self.freed_count == 0 {
+            return None;
+        }
+        Some(self.total_lifetime_microseconds / self.freed_count as f64)
+    }
+}
+
+impl Serialize for AllocationSite {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut state = serializer.serialize_struct("AllocationSite", 6)?;
+        state.serialize_field("callstack", &*self.callstack)?;
+        state.serialize_field("allocation_count", &self.allocation_count)?;
+        state.serialize_field("total_bytes", &self.total_bytes)?;
+        state.serialize_field("peak_live_bytes", &self.peak_live_bytes)?;
+        state.serialize_field("mean_lifetime", &self.get_mean_lifetime())?;
+        state.serialize_field("mean_lifetime_microseconds", &self.get_mean_lifetime_microseconds())?;
+        state.end()
+    }
+}
+
+/// An allocation that has not been freed yet.
+struct LiveAllocation {
+    site: usize,
+    size: usize,
+    timestamp: usize,
+    real_timestamp: f64,
+}
+
+pub struct AllocationSiteFactory {
+    memory_updates: Vec<MemoryUpdateType>,
+    right_padding: usize,
+}
+
+impl AllocationSiteFactory {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_updates`: Updates to aggregate, in any order.
+    /// * `right_padding`: Padding that has been applied to the right of each update. It is removed
+    ///   from the sizes.
+    ///
+    /// returns: AllocationSiteFactory
+    pub fn new(mut memory_updates: Vec<MemoryUpdateType>, right_padding: usize) -> AllocationSiteFactory {
+        memory_updates.sort_by_key(|update| update.get_timestamp());
+        AllocationSiteFactory {
+            memory_updates,
+            right_padding,
+        }
+    }
+
+    /// Aggregates the updates by callstack.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames`: Number of frames (from the top of the callstack) to group by, or None to group
+    ///   by the full callstack.
+    ///
+    /// returns: Vec<AllocationSite>, most bytes allocated first, or BadTimestamp if an update's
+    /// realtime timestamp cannot be converted
+    pub fn calculate_allocation_sites(&self, frames: Option<usize>) -> Result<Vec<AllocationSite>, DamselflyError> {
+        let mut sites: Vec<AllocationSite> = Vec::new();
+        let mut site_indices: HashMap<Arc<String>, usize> = HashMap::new();
+        let mut live_allocations: HashMap<usize, LiveAllocation> = HashMap::new();
+
+        for update in &self.memory_updates {
+            let (freed_address, allocated_address) = match update {
+                MemoryUpdateType::Allocation(allocation) => (None, Some(allocation.get_absolute_address())),
+                MemoryUpdateType::Free(free) => (Some(free.get_absolute_address()), None),
+                MemoryUpdateType::Reallocation(reallocation) => (
+                    Some(reallocation.get_old_address()),
+                    Some(reallocation.get_absolute_address())
+                        .filter(|_| reallocation.get_absolute_size() > self.right_padding),
+                ),
+            };
+            let real_timestamp = update.get_real_timestamp_microseconds()?;
+
+            if let Some(live_allocation) = freed_address.and_then(|address| live_allocations.remove(&address)) {
+                let site = &mut sites[live_allocation.site];
+                site.live_bytes -= live_allocation.size;
+                site.freed_count += 1;
+                site.total_lifetime += update.get_timestamp() - live_allocation.timestamp;
+                site.total_lifetime_microseconds += (real_timestamp - live_allocation.real_timestamp).max(0.0);
+            }
+
+            if let Some(address) = allocated_address {
+                let callstack = Self::truncate_callstack(update.get_callstack(), frames);
+                let site_index = *site_indices.entry(callstack.clone()).or_insert_with(|| {
+                    sites.push(AllocationSite::new(callstack));
+                    sites.len() - 1
+                });
+                let size = update.get_absolute_size().saturating_sub(self.right_padding);
+                let site = &mut sites[site_index];
+                site.allocation_count += 1;
+                site.total_bytes += size;
+                site.live_bytes += size;
+                site.peak_live_bytes = site.peak_live_bytes.max(site.live_bytes);
+                let replaced = live_allocations.insert(address, LiveAllocation {
+                    site: site_index,
+                    size,
+                    timestamp: update.get_timestamp(),
+                    real_timestamp,
+                });
+                // An allocation over a live one (an anomaly in the trace) replaces it
+                if let Some(replaced) = replaced {
+                    sites[replaced.site].live_bytes -= replaced.size;
+                }
+            }
+        }
+
+        sites.sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes)
+            .then(b.allocation_count.cmp(&a.allocation_count))
+            .then(a.callstack.cmp(&b.callstack)));
+        Ok(sites)
+    }
+
+    /// Keeps the first few frames of a callstack.
+    ///
+    /// # Arguments
+    ///
+    /// * `callstack`: Callstack with one frame per line.
+    /// * `frames`: Number of frames to keep, or None to keep all of them.
+    ///
+    /// returns: Arc<String>
+    fn truncate_callstack(callstack: Arc<String>, frames: Option<usize>) -> Arc<String> {
+        match frames {
+            Some(frames) if callstack.lines().count() > frames => {
+                Arc::new(callstack.split_inclusive('\n').take(frames).collect())
+            }
+            _ => callstack,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::allocation_site_factory::AllocationSiteFactory;
+    use crate::damselfly::memory::cycle_counter_clock::CycleCounterReading;
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+
+    fn initialise_test_updates() -> Vec<MemoryUpdateType> {
+        let first_callstack = Arc::new(String::from("malloc_wrapper\nfirst\n"));
+        let second_callstack = Arc::new(String::from("malloc_wrapper\nsecond\n"));
+        vec![
+            Allocation::new(0, 8, first_callstack.clone(), 0, String::from("0001.000 s")).wrap_in_enum(),
+            Allocation::new(16, 8, first_callstack.clone(), 1, String::from("0001.250 s")).wrap_in_enum(),
+            Allocation::new(32, 64, second_callstack.clone(), 2, String::from("0001.500 s")).wrap_in_enum(),
+            Free::new(0, 8, first_callstack.clone(), 3, String::from("0001.750 s")).wrap_in_enum(),
+            Allocation::new(0, 8, first_callstack.clone(), 4, String::from("0002.000 s")).wrap_in_enum(),
+            Free::new(32, 64, second_callstack.clone(), 5, String::from("0002.500 s")).wrap_in_enum(),
+        ]
+    }
+
+    #[test]
+    fn calculate_allocation_sites_test() {
+        let sites = AllocationSiteFactory::new(initialise_test_updates(), 0).calculate_allocation_sites(None).unwrap();
+        assert_eq!(sites.len(), 2);
+        assert_eq!(*sites[0].get_callstack(), "malloc_wrapper\nsecond\n");
+        assert_eq!(sites[0].get_allocation_count(), 1);
+        assert_eq!(sites[0].get_total_bytes(), 64);
+        assert_eq!(sites[0].get_peak_live_bytes(), 64);
+        assert_eq!(sites[0].get_mean_lifetime(), Some(3.0));
+        assert_eq!(sites[0].get_mean_lifetime_microseconds(), Some(1000000.0));
+
+        assert_eq!(*sites[1].get_callstack(), "malloc_wrapper\nfirst\n");
+        assert_eq!(sites[1].get_allocation_count(), 3);
+        assert_eq!(sites[1].get_total_bytes(), 24);
+        assert_eq!(sites[1].get_peak_live_bytes(), 16);
+        assert_eq!(sites[1].get_mean_lifetime(), Some(3.0));
+    }
+
+    #[test]
+    fn calculate_allocation_sites_top_frames_test() {
+        let sites = AllocationSiteFactory::new(initialise_test_updates(), 0).calculate_allocation_sites(Some(1)).unwrap();
+        assert_eq!(sites.len(), 1);
+        assert_eq!(*sites[0].get_callstack(), "malloc_wrapper\n");
+        assert_eq!(sites[0].get_allocation_count(), 4);
+        assert_eq!(sites[0].get_total_bytes(), 88);
+        assert_eq!(sites[0].get_peak_live_bytes(), 80);
+        assert_eq!(sites[0].get_mean_lifetime(), Some(3.0));
+    }
+
+    #[test]
+    fn calculate_allocation_sites_never_freed_test() {
+        let updates = vec![
+            Allocation::new(0, 8, Arc::new(String::from("leak\n")), 0, String::from("0001.000 s")).wrap_in_enum(),
+        ];
+        let sites = AllocationSiteFactory::new(updates, 0).calculate_allocation_sites(None).unwrap();
+        assert_eq!(sites[0].get_mean_lifetime(), None);
+        assert_eq!(sites[0].get_peak_live_bytes(), 8);
+    }
+
+    #[test]
+    fn calculate_allocation_sites_realloc_test() {
+        let mut updates = initialise_test_updates();
+        updates.push(Reallocation::new(128, 32, 16, 8, Arc::new(String::from("resize\n")), 6, String::from("0002.750 s")).wrap_in_enum());
+        let sites = AllocationSiteFactory::new(updates, 0).calculate_allocation_sites(None).unwrap();
+        let first = sites.iter().find(|site| *site.get_callstack() == "malloc_wrapper\nfirst\n").unwrap();
+        assert_eq!(first.get_mean_lifetime(), Some(4.0));
+        let resize = sites.iter().find(|site| *site.get_callstack() == "resize\n").unwrap();
+        assert_eq!(resize.get_total_bytes(), 32);
+        assert_eq!(resize.get_peak_live_bytes(), 32);
+        assert_eq!(resize.get_mean_lifetime(), None);
+    }
+
+    #[test]
+    fn calculate_allocation_sites_bad_timestamp_test() {
+        let mut updates = initialise_test_updates();
+        updates.push(Free::new(16, 8, Arc::new(String::from("free\n")), 6, String::from("-1 us")).wrap_in_enum());
+        let result = AllocationSiteFactory::new(updates, 0).calculate_allocation_sites(None);
+        assert!(matches!(result, Err(DamselflyError::BadTimestamp { .. })));
+    }
+
+    #[test]
+    fn calculate_allocation_sites_cycle_counter_test() {
+        let callstack = Arc::new(String::from("malloc_wrapper
+"));
+        let allocated_at = CycleCounterReading::new(0x100, 1_000_000_250);
+        let freed_at = CycleCounterReading::new(0x200, 1_000_001_000);
+        let mut allocation = Allocation::new(0, 8, callstack.clone(), 0, allocated_at.to_real_timestamp()).wrap_in_enum();
+        allocation.set_cycle_counter(Some(allocated_at));
+        let mut free = Free::new(0, 8, callstack, 1, freed_at.to_real_timestamp()).wrap_in_enum();
+        free.set_cycle_counter(Some(freed_at));
+        let sites = AllocationSiteFactory::new(vec![allocation, free], 0).calculate_allocation_sites(None).unwrap();
+        // Lifetimes keep the nanoseconds of the cycle counter readings
+        assert_eq!(sites[0].get_mean_lifetime_microseconds(), Some(0.75));
+    }
+}
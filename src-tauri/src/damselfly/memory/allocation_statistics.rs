@@ -0,0 +1,184 @@
+//! Distributions of allocation sizes and lifetimes, computed by AllocationStatisticsFactory.
+use std::collections::BTreeMap;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+
+/// A single bucket of a Histogram, counting the values in [lower, upper).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct HistogramBucket {
+    pub lower: u64,
+    pub upper: u64,
+    pub count: usize,
+}
+
+/// A histogram with power-of-two buckets: [0, 1), [1, 2), [2, 4), [4, 8) and so on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: BTreeMap<u32, usize>,
+    total_count: usize,
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    /// Counts a value in its bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `value`: Value to count.
+    ///
+    /// returns: ()
+    pub fn add(&mut self, value: u64) {
+        *self.counts.entry(Self::get_bucket_index(value)).or_insert(0) += 1;
+        self.total_count += 1;
+    }
+
+    /// Gets the index of the bucket that a value belongs in. Bucket 0 holds only 0, and bucket n
+    /// holds [2^(n - 1), 2^n).
+    fn get_bucket_index(value: u64) -> u32 {
+        u64::BITS - value.leading_zeros()
+    }
+
+    /// Gets the bounds of a bucket.
+    ///
+    /// returns: (lower, upper), where upper is exclusive
+    fn get_bucket_bounds(index: u32) -> (u64, u64) {
+        match index {
+            0 => (0, 1),
+            64 => (1 << 63, u64::MAX),
+            index => (1 << (index - 1), 1 << index),
+        }
+    }
+
+    /// Gets the buckets from the lowest to the highest non-empty bucket, including any empty buckets
+    /// in between so that the histogram can be plotted directly.
+    ///
+    /// returns: Vec<HistogramBucket>, in ascending order
+    pub fn get_buckets(&self) -> Vec<HistogramBucket> {
+        let (Some(&first), Some(&last)) = (self.counts.keys().next(), self.counts.keys().next_back()) else {
+            return Vec::new();
+        };
+        (first..=last)
+            .map(|index| {
+                let (lower, upper) = Self::get_bucket_bounds(index);
+                HistogramBucket { lower, upper, count: self.counts.get(&index).copied().unwrap_or(0) }
+            })
+            .collect()
+    }
+
+    /// Gets the number of values counted.
+    pub fn get_total_count(&self) -> usize {
+        self.total_count
+    }
+}
+
+impl Serialize for Histogram {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.get_buckets().serialize(serializer)
+    }
+}
+
+/// Histograms of the allocations made in a range of a pool's trace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AllocationStatistics {
+    size_histogram: Histogram,
+    lifetime_histogram: Histogram,
+    lifetime_microseconds_histogram: Histogram,
+    live_count: usize,
+}
+
+impl AllocationStatistics {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `size_histogram`: Sizes of the allocations, in bytes.
+    /// * `lifetime_histogram`: Lifetimes of the freed allocations, in operations.
+    /// * `lifetime_microseconds_histogram`: Lifetimes of the freed allocations, in microseconds.
+    /// * `live_count`: Number of allocations that are never freed.
+    ///
+    /// returns: AllocationStatistics
+    pub fn new(size_histogram: Histogram, lifetime_histogram: Histogram,
+               lifetime_microseconds_histogram: Histogram, live_count: usize) -> AllocationStatistics {
+        AllocationStatistics {
+            size_histogram,
+            lifetime_histogram,
+            lifetime_microseconds_histogram,
+            live_count,
+        }
+    }
+
+    pub fn get_size_histogram(&self) -> &Histogram {
+        &self.size_histogram
+    }
+
+    pub fn get_lifetime_histogram(&self) -> &Histogram {
+        &self.lifetime_histogram
+    }
+
+    pub fn get_lifetime_microseconds_histogram(&self) -> &Histogram {
+        &self.lifetime_microseconds_histogram
+    }
+
+    /// Gets the number of allocations counted.
+    pub fn get_allocation_count(&self) -> usize {
+        self.size_histogram.get_total_count()
+    }
+
+    /// Gets the number of allocations that are never freed, which have no lifetime.
+    pub fn get_live_count(&self) -> usize {
+        self.live_count
+    }
+}
+
+impl Serialize for AllocationStatistics {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut state = serializer.serialize_struct("AllocationStatistics", 5)?;
+        state.serialize_field("size_histogram", &self.size_histogram)?;
+        state.serialize_field("lifetime_histogram", &self.lifetime_histogram)?;
+        state.serialize_field("lifetime_microseconds_histogram", &self.lifetime_microseconds_histogram)?;
+        state.serialize_field("allocation_count", &self.get_allocation_count())?;
+        state.serialize_field("live_count", &self.live_count)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::allocation_statistics::{Histogram, HistogramBucket};
+
+    #[test]
+    fn histogram_buckets_test() {
+        let mut histogram = Histogram::new();
+        for value in [2, 3, 16, 31] {
+            histogram.add(value);
+        }
+        assert_eq!(histogram.get_total_count(), 4);
+        assert_eq!(histogram.get_buckets(), vec![
+            HistogramBucket { lower: 2, upper: 4, count: 2 },
+            HistogramBucket { lower: 4, upper: 8, count: 0 },
+            HistogramBucket { lower: 8, upper: 16, count: 0 },
+            HistogramBucket { lower: 16, upper: 32, count: 2 },
+        ]);
+    }
+
+    #[test]
+    fn histogram_edge_buckets_test() {
+        let mut histogram = Histogram::new();
+        histogram.add(0);
+        histogram.add(1);
+        histogram.add(u64::MAX);
+        let buckets = histogram.get_buckets();
+        assert_eq!(buckets.len(), 65);
+        assert_eq!(buckets[0], HistogramBucket { lower: 0, upper: 1, count: 1 });
+        assert_eq!(buckets[1], HistogramBucket { lower: 1, upper: 2, count: 1 });
+        assert_eq!(buckets[64], HistogramBucket { lower: 1 << 63, upper: u64::MAX, count: 1 });
+    }
+
+    #[test]
+    fn histogram_empty_test() {
+        assert!(Histogram::new().get_buckets().is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! Pairs each allocation in a pool with the free (or reallocation) that releases it, and computes
+//! histograms of allocation sizes and lifetimes.
+use std::collections::HashMap;
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::allocation_statistics::{AllocationStatistics, Histogram};
+use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
+
+/// An allocation, and the free that releases it if there is one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllocationLifetime {
+    pub size: usize,
+    pub allocated_at: usize,
+    pub allocated_at_microseconds: f64,
+    /// (operation timestamp, realtime timestamp in microseconds) of the free
+    pub freed_at: Option<(usize, f64)>,
+}
+
+pub struct AllocationStatisticsFactory {
+    memory_updates: Vec<MemoryUpdateType>,
+    right_padding: usize,
+}
+
+impl AllocationStatisticsFactory {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_updates`: Updates in a single pool, in any order.
+    /// * `right_padding`: Padding that has been applied to the right of each update. It is removed
+    ///   from the sizes.
+    ///
+    /// returns: AllocationStatisticsFactory
+    pub fn new(mut memory_updates: Vec<MemoryUpdateType>, right_padding: usize) -> AllocationStatisticsFactory {
+        memory_updates.sort_by_key(|update| update.get_timestamp());
+        AllocationStatisticsFactory {
+            memory_updates,
+            right_padding,
+        }
+    }
+
+    /// Pairs each allocation with the free that releases it. A reallocation releases the old
+    /// allocation and makes a new one.
+    ///
+    /// returns: Vec<AllocationLifetime>, in the order the allocations were made, or BadTimestamp
+    /// if an update's realtime timestamp cannot be converted
+    pub fn pair_allocations(&self) -> Result<Vec<AllocationLifetime>, DamselflyError> {
+        let mut lifetimes: Vec<AllocationLifetime> = Vec::new();
+        // Address -> index into lifetimes
+        let mut live_allocations: HashMap<usize, usize> = HashMap::new();
+
+        for update in &self.memory_updates {
+            let (freed_address, allocated_address) = match update {
+                MemoryUpdateType::Allocation(allocation) => (None, Some(allocation.get_absolute_address())),
+                MemoryUpdateType::Free(free) => (Some(free.get_absolute_address()), None),
+                MemoryUpdateType::Reallocation(reallocation) => (
+                    Some(reallocation.get_old_address()),
+                    Some(reallocation.get_absolute_address())
+                        .filter(|_| reallocation.get_absolute_size() > self.right_padding),
+                ),
+            };
+            let real_timestamp = update.get_real_timestamp_microseconds()?;
+
+            if let Some(index) = freed_address.and_then(|address| live_allocations.remove(&address)) {
+                lifetimes[index].freed_at = Some((update.get_timestamp(), real_timestamp));
+            }
+
+            if let Some(address) = allocated_address {
+                live_allocations.insert(address, lifetimes.len());
+                lifetimes.push(AllocationLifetime {
+                    size: update.get_absolute_size().saturating_sub(self.right_padding),
+                    allocated_at: update.get_timestamp(),
+                    allocated_at_microseconds: real_timestamp,
+                    freed_at: None,
+                });
+            }
+        }
+
+        Ok(lifetimes)
+    }
+
+    /// Computes histograms of the sizes and lifetimes of the allocations made in a range of
+    /// operation timestamps. Allocations made in the range are counted even if they are freed
+    /// after it.
+    ///
+    /// # Arguments
+    ///
+    /// * `range`: (start, end) operation timestamps, inclusive, or None for the whole trace.
+    ///
+    /// returns: AllocationStatistics, or BadTimestamp if an update's realtime timestamp cannot be
+    /// converted
+    pub fn calculate_statistics(&self, range: Option<(usize, usize)>) -> Result<AllocationStatistics, DamselflyError> {
+        let (start, end) = range.unwrap_or((0, usize::MAX));
+        let mut size_histogram = Histogram::new();
+        let mut lifetime_histogram = Histogram::new();
+        let mut lifetime_microseconds_histogram = Histogram::new();
+        let mut live_count = 0;
+
+        for lifetime in self.pair_allocations()?
+            .iter()
+            .filter(|lifetime| (start..=end).contains(&lifetime.allocated_at)) {
+            size_histogram.add(lifetime.size as u64);
+            match lifetime.freed_at {
+                Some((freed_at, freed_at_microseconds)) => {
+                    lifetime_histogram.add((freed_at - lifetime.allocated_at) as u64);
+                    // Lifetimes are bucketed in whole microseconds, and a negative lifetime counts as 0
+                    lifetime_microseconds_histogram.add(
+                        (freed_at_microseconds - lifetime.allocated_at_microseconds).max(0.0) as u64
+                    );
+                }
+                None => live_count += 1,
+            }
+        }
+
+        Ok(AllocationStatistics::new(size_histogram, lifetime_histogram, lifetime_microseconds_histogram, live_count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::allocation_statistics::HistogramBucket;
+    use crate::damselfly::memory::allocation_statistics_factory::AllocationStatisticsFactory;
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+
+    fn initialise_test_updates() -> Vec<MemoryUpdateType> {
+        vec![
+            Allocation::new(0, 8, Arc::new(String::new()), 0, String::from("0001.000 s")).wrap_in_enum(),
+            Allocation::new(16, 12, Arc::new(String::new()), 1, String::from("0001.000 s")).wrap_in_enum(),
+            Allocation::new(32, 64, Arc::new(String::new()), 2, String::from("0001.000 s")).wrap_in_enum(),
+            Free::new(0, 8, Arc::new(String::new()), 3, String::from("0001.500 s")).wrap_in_enum(),
+            Reallocation::new(128, 32, 16, 12, Arc::new(String::new()), 4, String::from("0002.000 s")).wrap_in_enum(),
+        ]
+    }
+
+    #[test]
+    fn pair_allocations_test() {
+        let lifetimes = AllocationStatisticsFactory::new(initialise_test_updates(), 0).pair_allocations().unwrap();
+        assert_eq!(lifetimes.len(), 4);
+        assert_eq!(lifetimes[0].freed_at, Some((3, 1500000.0)));
+        assert_eq!(lifetimes[1].freed_at, Some((4, 2000000.0)));
+        assert_eq!(lifetimes[2].freed_at, None);
+        assert_eq!(lifetimes[3].size, 32);
+        assert_eq!(lifetimes[3].allocated_at, 4);
+    }
+
+    #[test]
+    fn calculate_statistics_test() {
+        let statistics = AllocationStatisticsFactory::new(initialise_test_updates(), 0).calculate_statistics(None).unwrap();
+        assert_eq!(statistics.get_allocation_count(), 4);
+        assert_eq!(statistics.get_live_count(), 2);
+        assert_eq!(statistics.get_size_histogram().get_buckets(), vec![
+            HistogramBucket { lower: 8, upper: 16, count: 2 },
+            HistogramBucket { lower: 16, upper: 32, count: 0 },
+            HistogramBucket { lower: 32, upper: 64, count: 1 },
+            HistogramBucket { lower: 64, upper: 128, count: 1 },
+        ]);
+        assert_eq!(statistics.get_lifetime_histogram().get_buckets(), vec![
+            HistogramBucket { lower: 2, upper: 4, count: 2 },
+        ]);
+        assert_eq!(statistics.get_lifetime_microseconds_histogram().get_buckets(), vec![
+            HistogramBucket { lower: 262144, upper: 524288, count: 1 },
+            HistogramBucket { lower: 524288, upper: 1048576, count: 1 },
+        ]);
+    }
+
+    #[test]
+    fn calculate_statistics_range_test() {
+        let factory = AllocationStatisticsFactory::new(initialise_test_updates(), 0);
+        let statistics = factory.calculate_statistics(Some((1, 2))).unwrap();
+        assert_eq!(statistics.get_allocation_count(), 2);
+        assert_eq!(statistics.get_live_count(), 1);
+        assert_eq!(statistics.get_lifetime_histogram().get_buckets(), vec![
+            HistogramBucket { lower: 2, upper: 4, count: 1 },
+        ]);
+
+        let statistics = factory.calculate_statistics(Some((5, 10))).unwrap();
+        assert_eq!(statistics.get_allocation_count(), 0);
+        assert!(statistics.get_size_histogram().get_buckets().is_empty());
+    }
+
+    #[test]
+    fn calculate_statistics_bad_timestamp_test() {
+        let mut updates = initialise_test_updates();
+        updates.push(Free::new(32, 64, Arc::new(String::new()), 5, String::from("2 fortnights")).wrap_in_enum());
+        let factory = AllocationStatisticsFactory::new(updates, 0);
+        assert!(matches!(factory.pair_allocations(), Err(DamselflyError::BadTimestamp { .. })));
+        assert!(matches!(factory.calculate_statistics(None), Err(DamselflyError::BadTimestamp { .. })));
+    }
+}
@@ -0,0 +1,612 @@
+//! A compact binary serialisation of ParseResults, so that a trace that has already been parsed and
+//! symbolised can be reopened without parsing text or looking up DWARF again.
+//!
+//! All integers are little-endian, and strings are a u32 length followed by UTF-8 bytes.
+//!
+//! ```text
+//! magic            8 bytes, "DMSLFLY\x01"
+//! version          u32
+//! max_timestamp    u64
+//! pools            u32 count, then for each: name (string), start (u64), size (u64), lifetime
+//!   lifetime       u8 1 if the pool is created, resized or deleted during the trace, then:
+//!   initial bounds u8 1 if the pool exists at the start, then start (u64), size (u64)
+//!   events         u32 count, then for each:
+//!     kind         u8: 0 = Create, then start (u64), size (u64)
+//!                      1 = Resize, then size (u64)
+//!                      2 = Delete
+//!     position     u64
+//!     real time    string
+//! callstacks       u32 count, then each callstack (string). Updates refer to them by index.
+//! diagnostics      u32 count, then for each: line (u64), text (string), reason (string)
+//! has_pools        u8, 1 if each update is followed by the index of its pool
+//! updates          u64 count, then for each:
+//!   kind           u8: 0 = Allocation, 1 = Free, 2 = Reallocation
+//!   address, size  u64, u64
+//!   old address    u64, u64 (Reallocation only)
+//!   and size
+//!   callstack      u32 index into callstacks
+//!   timestamp      u64
+//!   real timestamp u64 microseconds
+//!   thread ID      u8 1 if present, then u64
+//!   cycle counter  u8 1 if present, then counter (u64), nanoseconds (u64). The real timestamp is
+//!                  then rebuilt from the nanoseconds, so no precision is lost.
+//!   pool           u32 index into pools (if has_pools)
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::sync::Arc;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::cycle_counter_clock::CycleCounterReading;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::memory_parsers::{MemoryParser, ParseResults};
+use crate::damselfly::memory::memory_pool::MemoryPool;
+use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind, PoolLifetime};
+use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+use crate::damselfly::memory::utility::Utility;
+
+/// Bytes that every binary trace starts with.
+pub const BINARY_FORMAT_MAGIC: &[u8; 8] = b"DMSLFLY\x01";
+/// Version of the format written by BinaryTraceWriter. Only this version can be read.
+pub const BINARY_FORMAT_VERSION: u32 = 3;
+
+const ALLOCATION_KIND: u8 = 0;
+const FREE_KIND: u8 = 1;
+const REALLOCATION_KIND: u8 = 2;
+
+/// Loader for binary traces written by BinaryTraceWriter.
+#[derive(Default)]
+pub struct MemoryBinaryParser {}
+
+impl MemoryParser for MemoryBinaryParser {
+    /// Loads a binary trace that has been read into a String. Binary traces are rarely valid UTF-8,
+    /// so prefer parse_log or parse_bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw trace.
+    /// * `_binary_path`: Unused, as callstacks are stored already symbolised.
+    ///
+    /// returns: ParseResults
+    fn parse_log_directly(self, log: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_bytes(log.as_bytes(), "[log]")
+    }
+
+    /// Loads a binary trace using its file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: File path to the trace.
+    /// * `_binary_path`: Unused, as callstacks are stored already symbolised.
+    ///
+    /// returns: ParseResults
+    fn parse_log(self, log_path: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemoryBinaryParser::parse_log]: log: {log_path}");
+        BinaryTraceReader::new(LogReader::open(log_path)?, log_path).read_trace()
+    }
+}
+
+impl MemoryBinaryParser {
+    pub fn new() -> MemoryBinaryParser {
+        MemoryBinaryParser::default()
+    }
+
+    /// Checks if the start of a file is the magic of a binary trace.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The first bytes of a file.
+    ///
+    /// returns: bool
+    pub fn is_binary_trace(bytes: &[u8]) -> bool {
+        bytes.starts_with(BINARY_FORMAT_MAGIC)
+    }
+
+    /// Loads a binary trace from memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: Raw trace.
+    /// * `path`: Name of the trace in errors.
+    ///
+    /// returns: ParseResults
+    pub fn parse_bytes(self, bytes: &[u8], path: &str) -> Result<ParseResults, DamselflyError> {
+        BinaryTraceReader::new(bytes, path).read_trace()
+    }
+}
+
+/// Reads the fields of a binary trace, turning short reads into errors that name the trace.
+struct BinaryTraceReader<'a, R: Read> {
+    reader: R,
+    path: &'a str,
+}
+
+impl<'a, R: Read> BinaryTraceReader<'a, R> {
+    fn new(reader: R, path: &'a str) -> Self {
+        Self { reader, path }
+    }
+
+    fn read_trace(mut self) -> Result<ParseResults, DamselflyError> {
+        let mut magic = [0; 8];
+        self.read_exact(&mut magic)?;
+        if &magic != BINARY_FORMAT_MAGIC {
+            return Err(self.corrupt("not a binary trace"));
+        }
+        let version = self.read_u32()?;
+        if version != BINARY_FORMAT_VERSION {
+            return Err(self.corrupt(&format!("trace is version {version}, but only version {BINARY_FORMAT_VERSION} can be read")));
+        }
+        let max_timestamp = self.read_u64()?;
+
+        let pool_count = self.read_u32()? as usize;
+        let mut pools = Vec::with_capacity(pool_count.min(1024));
+        let mut lifetimes = Vec::with_capacity(pool_count.min(1024));
+        for _ in 0..pool_count {
+            let name = self.read_string()?;
+            let start = self.read_usize()?;
+            let size = self.read_usize()?;
+            pools.push(MemoryPool::new(start, size, name));
+            lifetimes.push(self.read_pool_lifetime()?);
+        }
+
+        let callstack_count = self.read_u32()? as usize;
+        let mut callstacks = Vec::with_capacity(callstack_count.min(1 << 16));
+        for _ in 0..callstack_count {
+            callstacks.push(Arc::new(self.read_string()?));
+        }
+
+        let diagnostic_count = self.read_u32()? as usize;
+        let mut diagnostics = Vec::with_capacity(diagnostic_count.min(1 << 16));
+        for _ in 0..diagnostic_count {
+            let line = self.read_usize()?;
+            let text = self.read_string()?;
+            let reason = self.read_string()?;
+            diagnostics.push(ParseDiagnostic::new(line, text, reason));
+        }
+
+        let has_pools = self.read_u8()? == 1;
+        let pool_names: Vec<Arc<String>> = pools.iter().map(|pool| Arc::new(pool.get_name().to_string())).collect();
+        let update_count = self.read_usize()?;
+        let mut memory_updates = Vec::with_capacity(update_count.min(1 << 20));
+        let mut update_pools = Vec::new();
+        for _ in 0..update_count {
+            memory_updates.push(self.read_update(&callstacks)?);
+            if has_pools {
+                let pool_index = self.read_u32()? as usize;
+                let pool_name = pool_names.get(pool_index)
+                    .ok_or_else(|| self.corrupt(&format!("pool index {pool_index} is out of range")))?;
+                update_pools.push(Arc::clone(pool_name));
+            }
+        }
+
+        let mut pool_list = MemoryPoolList::default();
+        for (pool, lifetime) in pools.into_iter().zip(lifetimes) {
+            match lifetime {
+                Some(lifetime) => pool_list.add_pool_with_lifetime(pool, lifetime),
+                None => pool_list.add_pool(pool),
+            }
+        }
+        let parse_results = ParseResults::from_parsed_log(memory_updates, pool_list, diagnostics)
+            .with_max_timestamp(max_timestamp);
+        if has_pools {
+            parse_results.with_update_pools(update_pools)
+        } else {
+            Ok(parse_results)
+        }
+    }
+
+    fn read_pool_lifetime(&mut self) -> Result<Option<PoolLifetime>, DamselflyError> {
+        if self.read_u8()? != 1 {
+            return Ok(None);
+        }
+        let initial_bounds = match self.read_u8()? {
+            1 => Some((self.read_usize()?, self.read_usize()?)),
+            _ => None,
+        };
+        let mut lifetime = PoolLifetime::new(initial_bounds);
+        let event_count = self.read_u32()? as usize;
+        for _ in 0..event_count {
+            let kind = match self.read_u8()? {
+                0 => PoolEventKind::Create { start: self.read_usize()?, size: self.read_usize()? },
+                1 => PoolEventKind::Resize { size: self.read_usize()? },
+                2 => PoolEventKind::Delete,
+                kind => return Err(self.corrupt(&format!("unknown pool event kind {kind}"))),
+            };
+            let position = self.read_usize()?;
+            let real_timestamp = self.read_string()?;
+            lifetime.push_event(PoolEvent { kind, position, real_timestamp });
+        }
+        Ok(Some(lifetime))
+    }
+
+    fn read_update(&mut self, callstacks: &[Arc<String>]) -> Result<MemoryUpdateType, DamselflyError> {
+        let kind = self.read_u8()?;
+        let address = self.read_usize()?;
+        let size = self.read_usize()?;
+        let old_span = if kind == REALLOCATION_KIND {
+            Some((self.read_usize()?, self.read_usize()?))
+        } else {
+            None
+        };
+        let callstack_index = self.read_u32()? as usize;
+        let callstack = callstacks.get(callstack_index)
+            .ok_or_else(|| self.corrupt(&format!("callstack index {callstack_index} is out of range")))?;
+        let callstack = Arc::clone(callstack);
+        let timestamp = self.read_usize()?;
+        let real_timestamp_microseconds = self.read_u64()?;
+        let thread_id = match self.read_u8()? {
+            0 => None,
+            _ => Some(self.read_usize()?),
+        };
+        let cycle_counter = match self.read_u8()? {
+            0 => None,
+            _ => Some(CycleCounterReading::new(self.read_u64()?, self.read_u64()?)),
+        };
+        let real_timestamp = match cycle_counter {
+            Some(cycle_counter) => cycle_counter.to_real_timestamp(),
+            None => format!("{real_timestamp_microseconds} us"),
+        };
+
+        let mut memory_update = match (kind, old_span) {
+            (ALLOCATION_KIND, _) => Allocation::new(address, size, callstack, timestamp, real_timestamp).wrap_in_enum(),
+            (FREE_KIND, _) => Free::new(address, size, callstack, timestamp, real_timestamp).wrap_in_enum(),
+            (REALLOCATION_KIND, Some((old_address, old_size))) =>
+                Reallocation::new(address, size, old_address, old_size, callstack, timestamp, real_timestamp).wrap_in_enum(),
+            _ => return Err(self.corrupt(&format!("unknown memory update kind {kind}"))),
+        };
+        memory_update.set_thread_id(thread_id);
+        memory_update.set_cycle_counter(cycle_counter);
+        Ok(memory_update)
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), DamselflyError> {
+        self.reader.read_exact(buffer).map_err(|source| match source.kind() {
+            std::io::ErrorKind::UnexpectedEof => self.corrupt("trace is truncated"),
+            _ => DamselflyError::Io { path: self.path.to_string(), source },
+        })
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DamselflyError> {
+        let mut bytes = [0; 1];
+        self.read_exact(&mut bytes)?;
+        Ok(bytes[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DamselflyError> {
+        let mut bytes = [0; 4];
+        self.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, DamselflyError> {
+        let mut bytes = [0; 8];
+        self.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_usize(&mut self) -> Result<usize, DamselflyError> {
+        let value = self.read_u64()?;
+        usize::try_from(value).map_err(|_| self.corrupt(&format!("value {value} does not fit in usize")))
+    }
+
+    fn read_string(&mut self) -> Result<String, DamselflyError> {
+        let length = self.read_u32()? as usize;
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(length as u64).read_to_end(&mut bytes)
+            .map_err(|source| DamselflyError::Io { path: self.path.to_string(), source })?;
+        if bytes.len() != length {
+            return Err(self.corrupt("trace is truncated"));
+        }
+        String::from_utf8(bytes).map_err(|_| self.corrupt("string is not valid UTF-8"))
+    }
+
+    fn corrupt(&self, reason: &str) -> DamselflyError {
+        DamselflyError::CorruptTrace { path: self.path.to_string(), reason: reason.to_string() }
+    }
+}
+
+/// Writes parse results as a binary trace, so that a trace can be reopened without parsing the
+/// original log or symbolising it again.
+pub struct BinaryTraceWriter;
+
+impl BinaryTraceWriter {
+    /// Writes a trace to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `parse_results`: The parsed trace.
+    /// * `path`: Path to write the trace to. Any existing file is overwritten.
+    ///
+    /// returns: nothing, or the error that stopped the trace from being written
+    pub fn write_to_file(parse_results: &ParseResults, path: &str) -> Result<(), DamselflyError> {
+        let io_error = |source| DamselflyError::Io { path: path.to_string(), source };
+        let file = File::create(path).map_err(io_error)?;
+        let mut writer = BufWriter::new(file);
+        Self::write(parse_results, &mut writer, path)?;
+        writer.flush().map_err(io_error)
+    }
+
+    /// Writes a trace to a writer. Nothing is written if a real timestamp or update pool is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `parse_results`: The parsed trace.
+    /// * `writer`: Where to write the trace.
+    /// * `path`: Name of the trace in errors.
+    ///
+    /// returns: nothing, BadTimestamp if a real timestamp cannot be converted to microseconds,
+    /// InconsistentPools if an update's pool is not in the pool list, or the IO error that stopped
+    /// the trace from being written
+    pub fn write(parse_results: &ParseResults, writer: impl Write, path: &str) -> Result<(), DamselflyError> {
+        let mut pools: Vec<&MemoryPool> = parse_results.pool_list.get_pools().iter().collect();
+        pools.sort();
+        let real_timestamps = parse_results.memory_updates
+            .iter()
+            .map(|update| Utility::convert_to_microseconds(update.get_real_timestamp()))
+            .collect::<Result<Vec<u64>, DamselflyError>>()?;
+        let pool_indices: HashMap<&str, usize> = pools
+            .iter()
+            .enumerate()
+            .map(|(index, pool)| (pool.get_name(), index))
+            .collect();
+        let update_pool_indices = parse_results.update_pools
+            .iter()
+            .map(|pool| pool_indices.get(pool.as_str()).copied().ok_or_else(|| DamselflyError::InconsistentPools {
+                reason: format!("pool {pool} is not in the pool list"),
+            }))
+            .collect::<Result<Vec<usize>, DamselflyError>>()?;
+        if !update_pool_indices.is_empty() && update_pool_indices.len() != parse_results.memory_updates.len() {
+            return Err(DamselflyError::InconsistentPools {
+                reason: format!("{} memory operations, but {} pool names", parse_results.memory_updates.len(), update_pool_indices.len()),
+            });
+        }
+        Self::write_trace(parse_results, &pools, &real_timestamps, &update_pool_indices, writer)
+            .map_err(|source| DamselflyError::Io { path: path.to_string(), source })
+    }
+
+    fn write_trace(parse_results: &ParseResults, pools: &[&MemoryPool], real_timestamps: &[u64], update_pool_indices: &[usize], mut writer: impl Write) -> std::io::Result<()> {
+        writer.write_all(BINARY_FORMAT_MAGIC)?;
+        writer.write_all(&BINARY_FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&parse_results.max_timestamp.to_le_bytes())?;
+
+        Self::write_u32(&mut writer, pools.len())?;
+        for pool in pools {
+            Self::write_string(&mut writer, pool.get_name())?;
+            Self::write_u64(&mut writer, pool.get_start())?;
+            Self::write_u64(&mut writer, pool.get_size())?;
+            Self::write_pool_lifetime(&mut writer, parse_results.pool_list.get_lifetime(pool.get_name()))?;
+        }
+
+        // Callstacks are shared by many updates, so each is only written once
+        let mut callstack_indices: HashMap<Arc<String>, usize> = HashMap::new();
+        let mut callstacks = Vec::new();
+        for update in &parse_results.memory_updates {
+            let callstack = update.get_callstack();
+            if !callstack_indices.contains_key(&callstack) {
+                callstack_indices.insert(Arc::clone(&callstack), callstacks.len());
+                callstacks.push(callstack);
+            }
+        }
+        Self::write_u32(&mut writer, callstacks.len())?;
+        for callstack in &callstacks {
+            Self::write_string(&mut writer, callstack)?;
+        }
+
+        Self::write_u32(&mut writer, parse_results.diagnostics.len())?;
+        for diagnostic in &parse_results.diagnostics {
+            Self::write_u64(&mut writer, diagnostic.get_line())?;
+            Self::write_string(&mut writer, diagnostic.get_text())?;
+            Self::write_string(&mut writer, diagnostic.get_reason())?;
+        }
+
+        let has_pools = !update_pool_indices.is_empty();
+        writer.write_all(&[has_pools as u8])?;
+        Self::write_u64(&mut writer, parse_results.memory_updates.len())?;
+        for (index, update) in parse_results.memory_updates.iter().enumerate() {
+            let kind = match update {
+                MemoryUpdateType::Allocation(_) => ALLOCATION_KIND,
+                MemoryUpdateType::Free(_) => FREE_KIND,
+                MemoryUpdateType::Reallocation(_) => REALLOCATION_KIND,
+            };
+            writer.write_all(&[kind])?;
+            Self::write_u64(&mut writer, update.get_absolute_address())?;
+            Self::write_u64(&mut writer, update.get_absolute_size())?;
+            if let MemoryUpdateType::Reallocation(reallocation) = update {
+                Self::write_u64(&mut writer, reallocation.get_old_address())?;
+                Self::write_u64(&mut writer, reallocation.get_old_size())?;
+            }
+            Self::write_u32(&mut writer, callstack_indices[&update.get_callstack()])?;
+            Self::write_u64(&mut writer, update.get_timestamp())?;
+            writer.write_all(&real_timestamps[index].to_le_bytes())?;
+            match update.get_thread_id() {
+                Some(thread_id) => {
+                    writer.write_all(&[1])?;
+                    Self::write_u64(&mut writer, thread_id)?;
+                }
+                None => writer.write_all(&[0])?,
+            }
+            match update.get_cycle_counter() {
+                Some(cycle_counter) => {
+                    writer.write_all(&[1])?;
+                    writer.write_all(&cycle_counter.get_counter().to_le_bytes())?;
+                    writer.write_all(&cycle_counter.get_nanoseconds().to_le_bytes())?;
+                }
+                None => writer.write_all(&[0])?,
+            }
+            if has_pools {
+                Self::write_u32(&mut writer, update_pool_indices[index])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_pool_lifetime(writer: &mut impl Write, lifetime: Option<&PoolLifetime>) -> std::io::Result<()> {
+        let Some(lifetime) = lifetime else {
+            return writer.write_all(&[0]);
+        };
+        writer.write_all(&[1])?;
+        match lifetime.get_initial_bounds() {
+            Some((start, size)) => {
+                writer.write_all(&[1])?;
+                Self::write_u64(writer, start)?;
+                Self::write_u64(writer, size)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        Self::write_u32(writer, lifetime.get_events().len())?;
+        for event in lifetime.get_events() {
+            match event.kind {
+                PoolEventKind::Create { start, size } => {
+                    writer.write_all(&[0])?;
+                    Self::write_u64(writer, start)?;
+                    Self::write_u64(writer, size)?;
+                }
+                PoolEventKind::Resize { size } => {
+                    writer.write_all(&[1])?;
+                    Self::write_u64(writer, size)?;
+                }
+                PoolEventKind::Delete => writer.write_all(&[2])?,
+            }
+            Self::write_u64(writer, event.position)?;
+            Self::write_string(writer, &event.real_timestamp)?;
+        }
+        Ok(())
+    }
+
+    fn write_u32(writer: &mut impl Write, value: usize) -> std::io::Result<()> {
+        let value = u32::try_from(value)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "value does not fit in u32"))?;
+        writer.write_all(&value.to_le_bytes())
+    }
+
+    fn write_u64(writer: &mut impl Write, value: usize) -> std::io::Result<()> {
+        writer.write_all(&(value as u64).to_le_bytes())
+    }
+
+    fn write_string(writer: &mut impl Write, string: &str) -> std::io::Result<()> {
+        Self::write_u32(writer, string.len())?;
+        writer.write_all(string.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::binary_format::{BinaryTraceWriter, MemoryBinaryParser, BINARY_FORMAT_VERSION};
+    use crate::damselfly::memory::cycle_counter_clock::CycleCounterReading;
+    use crate::damselfly::memory::memory_parsers::ParseResults;
+    use crate::damselfly::memory::memory_pool::MemoryPool;
+    use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+    use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind};
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, Reallocation};
+    use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+
+    fn initialise_test_parse_results() -> ParseResults {
+        let mut pool_list = MemoryPoolList::default();
+        pool_list.add_pool(MemoryPool::new(0, 64, String::from("a")));
+        pool_list.add_pool(MemoryPool::new(0, 64, String::from("b")));
+        pool_list.add_pool_event("b", PoolEvent { kind: PoolEventKind::Resize { size: 32 }, position: 1, real_timestamp: String::from("1 us") });
+        pool_list.add_pool_event("c", PoolEvent { kind: PoolEventKind::Create { start: 64, size: 16 }, position: 2, real_timestamp: String::from("2 us") });
+        pool_list.add_pool_event("c", PoolEvent { kind: PoolEventKind::Delete, position: 3, real_timestamp: String::from("3 us") });
+        let callstack = Arc::new(String::from("main.c:12\n"));
+        let mut free = Free::new(0, 16, Arc::clone(&callstack), 1, String::from("20 us"));
+        free.set_thread_id(Some(3));
+        let updates = vec![
+            Allocation::new(0, 16, Arc::clone(&callstack), 0, String::from("10 us")).wrap_in_enum(),
+            free.wrap_in_enum(),
+            Reallocation::new(32, 32, 0, 0, Arc::new(String::new()), 2, String::from("30 us")).wrap_in_enum(),
+        ];
+        let diagnostics = vec![ParseDiagnostic::new(4, String::from("garbage"), String::from("bad record"))];
+        ParseResults::new(updates, pool_list, 3, diagnostics)
+            .with_update_pools(vec![Arc::new(String::from("a")), Arc::new(String::from("b")), Arc::new(String::from("b"))])
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let parse_results = initialise_test_parse_results();
+        let mut trace = Vec::new();
+        BinaryTraceWriter::write(&parse_results, &mut trace, "test").unwrap();
+        assert!(MemoryBinaryParser::is_binary_trace(&trace));
+
+        let reread_results = MemoryBinaryParser::new().parse_bytes(&trace, "test").unwrap();
+        assert_eq!(reread_results.memory_updates, parse_results.memory_updates);
+        assert_eq!(reread_results.update_pools, parse_results.update_pools);
+        assert_eq!(reread_results.max_timestamp, 3);
+        assert_eq!(reread_results.pool_list.get_pools().len(), 3);
+        for pool in ["a", "b", "c"] {
+            assert_eq!(reread_results.pool_list.get_lifetime(pool), parse_results.pool_list.get_lifetime(pool));
+        }
+        assert_eq!(reread_results.diagnostics.len(), 1);
+        assert_eq!(reread_results.diagnostics[0].get_line(), 4);
+        // Identical callstacks are stored once and shared again after loading
+        assert!(Arc::ptr_eq(&reread_results.memory_updates[0].get_callstack(), &reread_results.memory_updates[1].get_callstack()));
+    }
+
+    #[test]
+    fn real_timestamp_test() {
+        let updates = vec![
+            Allocation::new(0, 16, Arc::new(String::new()), 0, String::from("0003.677 s")).wrap_in_enum(),
+            Free::new(0, 16, Arc::new(String::new()), 1, String::from("1.5 ms")).wrap_in_enum(),
+        ];
+        let parse_results = ParseResults::new(updates, MemoryPoolList::default(), 2, Vec::new());
+        let mut trace = Vec::new();
+        BinaryTraceWriter::write(&parse_results, &mut trace, "test").unwrap();
+        let reread_results = MemoryBinaryParser::new().parse_bytes(&trace, "test").unwrap();
+        assert_eq!(*reread_results.memory_updates[0].get_real_timestamp(), "3677000 us");
+        assert_eq!(*reread_results.memory_updates[1].get_real_timestamp(), "1500 us");
+        assert!(reread_results.update_pools.is_empty());
+    }
+
+    #[test]
+    fn cycle_counter_test() {
+        let reading = CycleCounterReading::new(0x039dcc2d, 3_677_002_510);
+        let mut allocation = Allocation::new(0, 16, Arc::new(String::new()), 0, reading.to_real_timestamp()).wrap_in_enum();
+        allocation.set_cycle_counter(Some(reading));
+        let parse_results = ParseResults::new(vec![allocation.clone()], MemoryPoolList::default(), 1, Vec::new());
+        let mut trace = Vec::new();
+        BinaryTraceWriter::write(&parse_results, &mut trace, "test").unwrap();
+        let reread_results = MemoryBinaryParser::new().parse_bytes(&trace, "test").unwrap();
+        assert_eq!(reread_results.memory_updates, vec![allocation]);
+        assert_eq!(reread_results.memory_updates[0].get_real_timestamp_microseconds().unwrap(), 3_677_002.51);
+    }
+
+    #[test]
+    fn corrupt_trace_test() {
+        let mut trace = Vec::new();
+        BinaryTraceWriter::write(&initialise_test_parse_results(), &mut trace, "test").unwrap();
+
+        let result = MemoryBinaryParser::new().parse_bytes(&trace[..trace.len() - 1], "test");
+        assert!(matches!(result, Err(DamselflyError::CorruptTrace { .. })));
+        let result = MemoryBinaryParser::new().parse_bytes(b"not a trace at all", "test");
+        assert!(matches!(result, Err(DamselflyError::CorruptTrace { .. })));
+
+        let mut newer_trace = trace.clone();
+        newer_trace[8] = BINARY_FORMAT_VERSION as u8 + 1;
+        let result = MemoryBinaryParser::new().parse_bytes(&newer_trace, "test");
+        assert!(matches!(result, Err(DamselflyError::CorruptTrace { .. })));
+    }
+
+    #[test]
+    fn invalid_trace_test() {
+        let updates = vec![
+            Allocation::new(0, 16, Arc::new(String::new()), 0, String::from("10 us")).wrap_in_enum(),
+            Free::new(0, 16, Arc::new(String::new()), 1, String::from("-1 us")).wrap_in_enum(),
+        ];
+        let parse_results = ParseResults::new(updates, MemoryPoolList::default(), 2, Vec::new());
+        let mut trace = Vec::new();
+        let result = BinaryTraceWriter::write(&parse_results, &mut trace, "test");
+        assert!(matches!(result, Err(DamselflyError::BadTimestamp { .. })));
+        assert!(trace.is_empty());
+
+        let mut parse_results = initialise_test_parse_results();
+        parse_results.update_pools[2] = Arc::new(String::from("d"));
+        let result = BinaryTraceWriter::write(&parse_results, &mut trace, "test");
+        assert!(matches!(result, Err(DamselflyError::InconsistentPools { .. })));
+        assert!(trace.is_empty());
+    }
+}
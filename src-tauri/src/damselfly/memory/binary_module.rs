@@ -0,0 +1,112 @@
+//! An additional ELF binary loaded by the traced program, e.g. a bootloader, overlay or DSP
+//! firmware, and the range of addresses it is loaded at. Return addresses in that range are
+//! symbolised with the module's debuginfo instead of the main binary's.
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryModule {
+    name: String,
+    path: String,
+    start: usize,
+    end: usize,
+}
+
+impl BinaryModule {
+    /// Constructor. The module is named after its file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to the module's binary for debuginfo.
+    /// * `start`: Lowest address the module is loaded at.
+    /// * `end`: Address just past the end of the module.
+    ///
+    /// returns: BinaryModule
+    pub fn new(path: String, start: usize, end: usize) -> BinaryModule {
+        let name = Self::name_of(&path);
+        BinaryModule {
+            name,
+            path,
+            start,
+            end,
+        }
+    }
+
+    /// Gets the name a binary is labelled with, which is its file name.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to the binary.
+    ///
+    /// returns: String
+    pub fn name_of(path: &str) -> String {
+        Path::new(path)
+            .file_name()
+            .map_or_else(|| path.to_string(), |name| name.to_string_lossy().into_owned())
+    }
+
+    /// Parses a module given as PATH@START-END, e.g. dsp.elf@0x80000000-0x80100000. Addresses are
+    /// hex, with or without 0x.
+    ///
+    /// # Arguments
+    ///
+    /// * `module`: The module.
+    ///
+    /// returns: BinaryModule, or why it could not be parsed
+    pub fn parse(module: &str) -> Result<BinaryModule, String> {
+        let invalid = || format!("{module} is not a module of the form PATH@START-END");
+        let (path, range) = module.rsplit_once('@').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let parse_address = |address: &str| {
+            let address = address.trim();
+            let address = address.strip_prefix("0x").or_else(|| address.strip_prefix("0X")).unwrap_or(address);
+            usize::from_str_radix(address, 16).map_err(|_| invalid())
+        };
+        let (start, end) = (parse_address(start)?, parse_address(end)?);
+        if path.is_empty() || start >= end {
+            return Err(invalid());
+        }
+        Ok(BinaryModule::new(path.to_string(), start, end))
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_start(&self) -> usize {
+        self.start
+    }
+
+    pub fn get_end(&self) -> usize {
+        self.end
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::binary_module::BinaryModule;
+
+    #[test]
+    fn parse_test() {
+        let module = BinaryModule::parse("/opt/fw/dsp@fw.elf@0x80000000-80100000").unwrap();
+        assert_eq!(module.get_name(), "dsp@fw.elf");
+        assert_eq!(module.get_path(), "/opt/fw/dsp@fw.elf");
+        assert!(module.contains(0x80000000));
+        assert!(module.contains(0x800fffff));
+        assert!(!module.contains(0x80100000));
+        assert!(!module.contains(0x7fffffff));
+
+        assert!(BinaryModule::parse("dsp.elf").is_err());
+        assert!(BinaryModule::parse("dsp.elf@0x1000").is_err());
+        assert!(BinaryModule::parse("dsp.elf@0x2000-0x1000").is_err());
+        assert!(BinaryModule::parse("dsp.elf@start-end").is_err());
+        assert!(BinaryModule::parse("@0x1000-0x2000").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! Converts readings of a free-running hardware cycle counter into realtime timestamps with
+//! sub-microsecond resolution.
+//!
+//! The first reading is anchored to the coarse realtime timestamp logged with it, and later
+//! readings are measured from there. Counters wrap around, and a gap between two logged lines can
+//! be longer than a whole wrap period, so the number of wraps is chosen to agree with the coarse
+//! timestamp rather than by comparing consecutive readings.
+use serde::{Deserialize, Serialize};
+
+/// A cycle counter reading logged with a memory update, and the realtime it was converted to.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CycleCounterReading {
+    counter: u64,
+    nanoseconds: u64,
+}
+
+impl CycleCounterReading {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `counter`: The counter reading, as logged.
+    /// * `nanoseconds`: Realtime of the reading in nanoseconds.
+    ///
+    /// returns: CycleCounterReading
+    pub fn new(counter: u64, nanoseconds: u64) -> CycleCounterReading {
+        CycleCounterReading {
+            counter,
+            nanoseconds,
+        }
+    }
+
+    pub fn get_counter(&self) -> u64 {
+        self.counter
+    }
+
+    pub fn get_nanoseconds(&self) -> u64 {
+        self.nanoseconds
+    }
+
+    pub fn get_microseconds(&self) -> f64 {
+        self.nanoseconds as f64 / 1000.0
+    }
+
+    /// Formats the realtime of the reading as a realtime timestamp e.g. "3677002.510 us", without
+    /// losing any precision.
+    ///
+    /// returns: String
+    pub fn to_real_timestamp(&self) -> String {
+        format!("{}.{:03} us", self.nanoseconds / 1000, self.nanoseconds % 1000)
+    }
+}
+
+pub struct CycleCounterClock {
+    frequency_hz: f64,
+    counter_bits: u32,
+    // (counter, coarse realtime in microseconds) of the first reading
+    anchor: Option<(u64, u64)>,
+}
+
+impl CycleCounterClock {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency_hz`: Rate at which the counter ticks.
+    /// * `counter_bits`: Width of the counter, after which it wraps to 0 (1 to 64).
+    ///
+    /// returns: CycleCounterClock
+    pub fn new(frequency_hz: f64, counter_bits: u32) -> CycleCounterClock {
+        assert!(frequency_hz > 0.0, "[CycleCounterClock::new]: Frequency must be positive");
+        assert!((1..=64).contains(&counter_bits), "[CycleCounterClock::new]: Counter must be 1 to 64 bits wide");
+        CycleCounterClock {
+            frequency_hz,
+            counter_bits,
+            anchor: None,
+        }
+    }
+
+    /// Converts a counter reading into a realtime timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `counter`: The counter reading. Bits above the counter's width are ignored.
+    /// * `coarse_microseconds`: The coarse realtime timestamp logged with the reading.
+    ///
+    /// returns: Realtime timestamp in microseconds
+    pub fn to_microseconds(&mut self, counter: u64, coarse_microseconds: u64) -> f64 {
+        let mask = u64::MAX >> (64 - self.counter_bits);
+        let counter = counter & mask;
+        let (anchor_counter, anchor_microseconds) = *self.anchor.get_or_insert((counter, coarse_microseconds));
+
+        let ticks_since_wrap = counter.wrapping_sub(anchor_counter) & mask;
+        let wrap_period = mask as f64 + 1.0;
+        let expected_ticks = coarse_microseconds.saturating_sub(anchor_microseconds) as f64 * self.frequency_hz / 1_000_000.0;
+        let wraps = ((expected_ticks - ticks_since_wrap as f64) / wrap_period).round().max(0.0);
+        let ticks = ticks_since_wrap as f64 + wraps * wrap_period;
+        anchor_microseconds as f64 + ticks * 1_000_000.0 / self.frequency_hz
+    }
+
+    /// Converts a counter reading into a realtime timestamp, rounded to the nearest nanosecond.
+    ///
+    /// # Arguments
+    ///
+    /// * `counter`: The counter reading. Bits above the counter's width are ignored.
+    /// * `coarse_microseconds`: The coarse realtime timestamp logged with the reading.
+    ///
+    /// returns: The reading and its realtime
+    pub fn read(&mut self, counter: u64, coarse_microseconds: u64) -> CycleCounterReading {
+        let microseconds = self.to_microseconds(counter, coarse_microseconds);
+        CycleCounterReading::new(counter, (microseconds * 1000.0).round() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::cycle_counter_clock::{CycleCounterClock, CycleCounterReading};
+
+    #[test]
+    fn to_microseconds_test() {
+        // 100 MHz, so each tick is 10 ns
+        let mut clock = CycleCounterClock::new(100_000_000.0, 32);
+        assert_eq!(clock.to_microseconds(0x039dcb32, 3_677_000), 3_677_000.0);
+        assert_eq!(clock.to_microseconds(0x039dcb32 + 250, 3_677_000), 3_677_002.5);
+        assert_eq!(clock.to_microseconds(0x039dcb32 + 150_000, 3_678_000), 3_678_500.0);
+    }
+
+    #[test]
+    fn wraparound_test() {
+        let mut clock = CycleCounterClock::new(100_000_000.0, 32);
+        // A 32-bit counter at 100 MHz wraps every 42.94967296 s
+        assert_eq!(clock.to_microseconds(0xffff_ff00, 1_000_000), 1_000_000.0);
+        assert!((clock.to_microseconds(0x0000_0100, 1_000_000) - 1_000_005.12).abs() < 0.001);
+        // Gap of over two wrap periods between lines
+        let microseconds = clock.to_microseconds(0x0000_0100, 86_889_000);
+        assert!((microseconds - (1_000_005.12 + 2.0 * 42_949_672.96)).abs() < 0.001);
+        // Bits above the counter's width are ignored
+        let mut clock = CycleCounterClock::new(1_000_000.0, 16);
+        assert_eq!(clock.to_microseconds(0x1_0010, 0), 0.0);
+        assert_eq!(clock.to_microseconds(0x0005, 65_000), 65_525.0);
+    }
+
+    #[test]
+    fn read_test() {
+        let mut clock = CycleCounterClock::new(100_000_000.0, 32);
+        clock.read(0x039dcb32, 3_677_000);
+        let reading = clock.read(0x039dcb32 + 251, 3_677_000);
+        assert_eq!(reading, CycleCounterReading::new(0x039dcb32 + 251, 3_677_002_510));
+        assert_eq!(reading.to_real_timestamp(), "3677002.510 us");
+        assert_eq!(reading.get_microseconds(), 3_677_002.51);
+    }
+}
@@ -0,0 +1,330 @@
+//! Parser for the JSON files written by Valgrind's DHAT heap profiler (dhat.out.*), when run in
+//! its default heap mode.
+//!
+//! DHAT records totals for each allocation site (program point) rather than individual
+//! allocations, so they are synthesised from the totals:
+//! * Each site makes as many blocks as it allocated, adding up to the bytes it allocated.
+//! * The blocks that were live at the end of the program are never freed, so they show up as leaks.
+//! * The blocks are spread evenly over the run of the program, and each freed block lives for the
+//!   site's mean lifetime.
+//!
+//! So the number of blocks, bytes and leaks at each site are exact, while their timing and
+//! addresses are approximations. DHAT's time unit (usually instructions) is used as if it were
+//! microseconds.
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::massif_parser::{MemoryMassifParser, BLOCK_ALIGNMENT};
+use crate::damselfly::memory::memory_parsers::{LenientParser, MemoryParser, ParseResults};
+use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType};
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+
+/// The parts of a DHAT profile that are used.
+#[derive(Deserialize)]
+struct DhatProfile {
+    mode: String,
+    // Whether block lifetimes (and so the blocks live at the end) were recorded
+    #[serde(default)]
+    bklt: bool,
+    // Time at the end of the program
+    te: u64,
+    pps: Vec<DhatProgramPoint>,
+    ftbl: Vec<String>,
+}
+
+/// Totals for an allocation site.
+#[derive(Deserialize)]
+struct DhatProgramPoint {
+    // Total bytes and blocks
+    tb: usize,
+    tbk: usize,
+    // Total lifetime of the blocks
+    #[serde(default)]
+    tl: u64,
+    // Bytes and blocks live at the end of the program
+    #[serde(default)]
+    eb: usize,
+    #[serde(default)]
+    ebk: usize,
+    // Indices into the frame table, innermost first
+    fs: Vec<usize>,
+}
+
+/// A synthesised allocation or free.
+struct DhatEvent {
+    time: u64,
+    is_free: bool,
+    address: usize,
+    size: usize,
+    callstack: Arc<String>,
+}
+
+/// Parser for DHAT heap profiles.
+#[derive(Default)]
+pub struct MemoryDhatParser {
+    next_address: usize,
+    // Number of the program point being parsed, counting from 1
+    line_number: usize,
+    lenient: bool,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl MemoryParser for MemoryDhatParser {
+    /// Parses a DHAT profile into a Vec of MemoryUpdateTypes.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw profile.
+    /// * `_binary_path`: Unused, as DHAT has already symbolised the allocation sites.
+    ///
+    /// returns: ParseResults
+    fn parse_log_directly(self, log: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_log_contents(log)
+    }
+
+    /// Parses a DHAT profile using its file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: File path to the profile.
+    /// * `_binary_path`: Unused, as DHAT has already symbolised the allocation sites.
+    ///
+    /// returns: ParseResults
+    fn parse_log(self, log_path: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemoryDhatParser::parse_log]: log: {log_path}");
+        let log = LogReader::read_to_string(log_path)?;
+        self.parse_log_contents(&log)
+    }
+}
+
+impl LenientParser for MemoryDhatParser {
+    fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>> {
+        self.lenient.then_some(&mut self.diagnostics)
+    }
+}
+
+impl MemoryDhatParser {
+    pub fn new() -> MemoryDhatParser {
+        MemoryDhatParser::default()
+    }
+
+    /// Constructs a parser that skips malformed allocation sites instead of failing. Each skipped
+    /// site is reported in the diagnostics of the ParseResults. The profile must still be valid
+    /// JSON.
+    pub fn new_lenient() -> MemoryDhatParser {
+        MemoryDhatParser {
+            lenient: true,
+            ..Self::new()
+        }
+    }
+
+    /// Parses a whole profile, consuming itself and returning parse results.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw profile.
+    ///
+    /// returns: ParseResults
+    fn parse_log_contents(mut self, log: &str) -> Result<ParseResults, DamselflyError> {
+        let memory_updates = self.parse_memory_updates(log)?;
+        Ok(ParseResults::from_parsed_log(memory_updates, MemoryPoolList::default(), self.diagnostics))
+    }
+
+    /// Synthesises the memory operations of every allocation site in a profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw profile.
+    ///
+    /// returns: The synthesised memory operations, in time order.
+    pub fn parse_memory_updates(&mut self, log: &str) -> Result<Vec<MemoryUpdateType>, DamselflyError> {
+        let profile: DhatProfile = serde_json::from_str(log)
+            .map_err(|error| DamselflyError::MalformedRecord { line: error.line(), reason: format!("invalid DHAT profile: {error}") })?;
+        if profile.mode != "heap" {
+            return Err(DamselflyError::MalformedRecord {
+                line: 1,
+                reason: format!("DHAT profile is in {} mode, but only heap mode profiles can be opened", profile.mode),
+            });
+        }
+
+        let mut events = Vec::new();
+        // The profile is not line based, so program points are numbered from 1 in place of lines
+        for (index, program_point) in profile.pps.iter().enumerate() {
+            self.line_number = index + 1;
+            match self.get_callstack(program_point, &profile.ftbl) {
+                Ok(callstack) => self.synthesise_blocks(program_point, callstack, &profile, &mut events),
+                Err(reason) => {
+                    let error = self.malformed_record(&format!("program point {index}: {reason}"));
+                    self.skip_or_fail("", error)?;
+                }
+            }
+        }
+        // Allocations come before frees made at the same time, so that zero-lifetime blocks are
+        // still allocated before they are freed
+        events.sort_by_key(|event| (event.time, event.is_free));
+
+        Ok(events.into_iter()
+            .enumerate()
+            .map(|(timestamp, event)| {
+                let real_timestamp = format!("{} us", event.time);
+                if event.is_free {
+                    Free::new(event.address, event.size, event.callstack, timestamp, real_timestamp).wrap_in_enum()
+                } else {
+                    Allocation::new(event.address, event.size, event.callstack, timestamp, real_timestamp).wrap_in_enum()
+                }
+            })
+            .collect())
+    }
+
+    /// Builds the callstack of an allocation site from the frame table.
+    ///
+    /// # Arguments
+    ///
+    /// * `program_point`: The allocation site.
+    /// * `frame_table`: Frames referred to by the sites.
+    ///
+    /// returns: The callstack, or the reason it could not be built.
+    fn get_callstack(&self, program_point: &DhatProgramPoint, frame_table: &[String]) -> Result<Arc<String>, String> {
+        if program_point.ebk > program_point.tbk || program_point.eb > program_point.tb {
+            return Err("more bytes or blocks live at the end than were allocated".to_string());
+        }
+        let mut callstack = String::new();
+        for &frame_index in &program_point.fs {
+            let frame = frame_table.get(frame_index)
+                .ok_or_else(|| format!("frame {frame_index} is not in the frame table"))?;
+            callstack.push_str(MemoryMassifParser::strip_frame_address(frame));
+            callstack.push('\n');
+        }
+        Ok(Arc::new(callstack))
+    }
+
+    /// Synthesises the allocations and frees of an allocation site. The blocks that are live at the
+    /// end of the program are the last to be allocated.
+    ///
+    /// # Arguments
+    ///
+    /// * `program_point`: The allocation site.
+    /// * `callstack`: Callstack of the allocation site.
+    /// * `profile`: The profile, for its end time.
+    /// * `events`: Events synthesised so far.
+    ///
+    /// returns: ()
+    fn synthesise_blocks(&mut self, program_point: &DhatProgramPoint, callstack: Arc<String>, profile: &DhatProfile, events: &mut Vec<DhatEvent>) {
+        let block_count = program_point.tbk;
+        if block_count == 0 {
+            return;
+        }
+        let (live_blocks, live_bytes) = if profile.bklt { (program_point.ebk, program_point.eb) } else { (0, 0) };
+        let freed_blocks = block_count - live_blocks;
+        let freed_bytes = program_point.tb - live_bytes;
+        let mean_lifetime = program_point.tl / block_count as u64;
+
+        for block in 0..block_count {
+            // Split the bytes as evenly as possible between the blocks of each kind
+            let size = if block < freed_blocks {
+                freed_bytes / freed_blocks + usize::from(block < freed_bytes % freed_blocks)
+            } else {
+                let live_block = block - freed_blocks;
+                live_bytes / live_blocks + usize::from(live_block < live_bytes % live_blocks)
+            };
+            let time = (profile.te as u128 * block as u128 / block_count as u128) as u64;
+            let address = self.next_address;
+            self.next_address += size.max(1).next_multiple_of(BLOCK_ALIGNMENT);
+
+            events.push(DhatEvent { time, is_free: false, address, size, callstack: callstack.clone() });
+            if block < freed_blocks {
+                let time = (time + mean_lifetime).min(profile.te);
+                events.push(DhatEvent { time, is_free: true, address, size, callstack: callstack.clone() });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::dhat_parser::MemoryDhatParser;
+    use crate::damselfly::memory::memory_update::MemoryUpdateType;
+
+    const TEST_PROFILE: &str = r#"{"dhatFileVersion":2
+,"mode":"heap","verb":"Allocated"
+,"bklt":true,"bkacc":true
+,"tu":"instrs","Mtu":"instr"
+,"cmd":"./prog"
+,"pid":1234
+,"te":1000
+,"tg":800
+,"pps":
+[{"tb":300,"tbk":3,"tl":300
+ ,"mb":200,"mbk":2,"gb":200,"gbk":2,"eb":100,"ebk":1
+ ,"fs":[1,2]
+ }
+,{"tb":50,"tbk":1,"tl":10
+ ,"mb":50,"mbk":1,"gb":0,"gbk":0,"eb":0,"ebk":0
+ ,"fs":[1,3]
+ }
+]
+,"ftbl":
+["[root]"
+,"0x4843828: malloc (vg_replace_malloc.c:431)"
+,"0x109159: main (m.c:5)"
+,"0x10917A: main (m.c:9)"
+]
+}"#;
+
+    #[test]
+    fn parse_memory_updates_test() {
+        let updates = MemoryDhatParser::new().parse_memory_updates(TEST_PROFILE).unwrap();
+        let allocations: Vec<&MemoryUpdateType> = updates.iter()
+            .filter(|update| matches!(update, MemoryUpdateType::Allocation(_)))
+            .collect();
+        let frees: Vec<&MemoryUpdateType> = updates.iter()
+            .filter(|update| matches!(update, MemoryUpdateType::Free(_)))
+            .collect();
+        assert_eq!(allocations.len(), 4);
+        // The block live at the end of the first site is never freed
+        assert_eq!(frees.len(), 3);
+
+        let first_site_bytes: usize = allocations.iter()
+            .filter(|update| *update.get_callstack() == "malloc (vg_replace_malloc.c:431)\nmain (m.c:5)\n")
+            .map(|update| update.get_absolute_size())
+            .sum();
+        assert_eq!(first_site_bytes, 300);
+
+        // Both sites start allocating at time 0, then the second site's block is freed after its
+        // lifetime of 10
+        assert_eq!(*updates[0].get_real_timestamp(), "0 us");
+        assert_eq!(*updates[1].get_real_timestamp(), "0 us");
+        assert_eq!(*updates[2].get_real_timestamp(), "10 us");
+        assert!(matches!(updates[2], MemoryUpdateType::Free(_)));
+        for (timestamp, update) in updates.iter().enumerate() {
+            assert_eq!(update.get_timestamp(), timestamp);
+        }
+    }
+
+    #[test]
+    fn bad_frame_test() {
+        let profile = TEST_PROFILE.replace("[1,3]", "[1,7]");
+        let result = MemoryDhatParser::new().parse_memory_updates(&profile);
+        assert!(matches!(result, Err(DamselflyError::MalformedRecord { line: 2, .. })));
+
+        let mut parser = MemoryDhatParser::new_lenient();
+        let updates = parser.parse_memory_updates(&profile).unwrap();
+        assert_eq!(updates.len(), 5);
+        assert_eq!(parser.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn wrong_mode_test() {
+        let profile = TEST_PROFILE.replace(r#""mode":"heap""#, r#""mode":"copy""#);
+        assert!(MemoryDhatParser::new().parse_memory_updates(&profile).is_err());
+    }
+}
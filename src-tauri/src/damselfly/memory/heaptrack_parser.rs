@@ -0,0 +1,394 @@
+//! Parser for the data files written by heaptrack (heaptrack.<program>.<pid>), once decompressed.
+//!
+//! Each line is a record whose type is given by its first character. Numbers are in hex:
+//!
+//! ```text
+//! v 10800 3                       heaptrack version and file format version
+//! s 4 main                        string (the length is only present from format version 2)
+//! i 4005b6 1 2 3 c                instruction pointer: address, module, function, file, line,
+//!                                 then (function, file, line) of the functions it was inlined
+//!                                 into, innermost first
+//! t 1 0                           trace: instruction pointer, parent trace (0 for the root)
+//! a 64 1                          allocation info: size, trace
+//! + 0                             allocation, by allocation info index
+//! - 0                             free, by allocation info index
+//! c 3e8                           time since the program started, in ms
+//! ```
+//!
+//! Strings, instruction pointers and traces are numbered from 1 in order of appearance, while
+//! allocation infos are numbered from 0. heaptrack does not record addresses, so each block is
+//! given a made-up address, and a free releases the newest live block with the same allocation
+//! info. Frees of blocks allocated before heaptrack attached are ignored.
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::log_lines::LogLines;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::massif_parser::BLOCK_ALIGNMENT;
+use crate::damselfly::memory::memory_parsers::{LenientParser, MemoryParser, ParseResults};
+use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType};
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+
+/// Parser for heaptrack data files.
+#[derive(Default)]
+pub struct MemoryHeaptrackParser {
+    time: usize,
+    real_time_ms: u64,
+    file_format_version: usize,
+    strings: Vec<String>,
+    // Frames of each instruction pointer, innermost first, each ending in a newline
+    instruction_pointers: Vec<String>,
+    // (instruction pointer, parent trace) of each trace
+    traces: Vec<(usize, usize)>,
+    // Trace -> callstack, so that allocations from the same trace share a callstack
+    callstacks: HashMap<usize, Arc<String>>,
+    // (size, trace) of each allocation info
+    allocation_infos: Vec<(usize, usize)>,
+    // Allocation info -> addresses of its live blocks, newest last
+    live_blocks: HashMap<usize, Vec<usize>>,
+    next_address: usize,
+    line_number: usize,
+    lenient: bool,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl MemoryParser for MemoryHeaptrackParser {
+    /// Parses a heaptrack data file into a Vec of MemoryUpdateTypes.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw data file.
+    /// * `_binary_path`: Unused, as heaptrack has already symbolised the traces.
+    ///
+    /// returns: ParseResults
+    fn parse_log_directly(self, log: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_log_contents(LogLines::new(log.as_bytes(), "[log]"))
+    }
+
+    /// Parses a heaptrack data file using its file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: File path to the data file.
+    /// * `_binary_path`: Unused, as heaptrack has already symbolised the traces.
+    ///
+    /// returns: ParseResults
+    fn parse_log(self, log_path: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemoryHeaptrackParser::parse_log]: log: {log_path}");
+        self.parse_log_contents(LogLines::new(LogReader::open(log_path)?, log_path))
+    }
+}
+
+impl LenientParser for MemoryHeaptrackParser {
+    fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>> {
+        self.lenient.then_some(&mut self.diagnostics)
+    }
+}
+
+impl MemoryHeaptrackParser {
+    pub fn new() -> MemoryHeaptrackParser {
+        MemoryHeaptrackParser::default()
+    }
+
+    /// Constructs a parser that skips malformed records instead of failing. Each skipped record is
+    /// reported in the diagnostics of the ParseResults.
+    pub fn new_lenient() -> MemoryHeaptrackParser {
+        MemoryHeaptrackParser {
+            lenient: true,
+            ..Self::new()
+        }
+    }
+
+    /// Parses a whole data file, consuming itself and returning parse results.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the data file.
+    ///
+    /// returns: ParseResults
+    fn parse_log_contents<R: BufRead>(mut self, mut log_lines: LogLines<R>) -> Result<ParseResults, DamselflyError> {
+        let memory_updates = self.parse_memory_updates(log_lines.by_ref())?;
+        log_lines.check_error()?;
+        Ok(ParseResults::from_parsed_log(memory_updates, MemoryPoolList::default(), self.diagnostics))
+    }
+
+    /// Parses the memory operations in a data file.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the data file.
+    ///
+    /// returns: The memory operations in the data file, in order.
+    pub fn parse_memory_updates<S: AsRef<str>>(&mut self, log_lines: impl Iterator<Item = S>) -> Result<Vec<MemoryUpdateType>, DamselflyError> {
+        let mut memory_updates = Vec::new();
+        for line in log_lines {
+            let line = line.as_ref();
+            self.line_number += 1;
+            match self.line_to_memory_update(line) {
+                Ok(Some(memory_update)) => memory_updates.push(memory_update),
+                Ok(None) => {},
+                Err(error) => self.skip_or_fail(line, error)?,
+            }
+        }
+        Ok(memory_updates)
+    }
+
+    /// Parses a record of the data file, updating the string, instruction pointer and trace tables.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the data file.
+    ///
+    /// returns: The memory update, or None if the record is not an allocation or free.
+    fn line_to_memory_update(&mut self, line: &str) -> Result<Option<MemoryUpdateType>, DamselflyError> {
+        let Some(record_type) = line.chars().next() else {
+            return Ok(None);
+        };
+        let fields = line.get(1..).unwrap_or("").trim_start();
+        match record_type {
+            'v' => {
+                self.file_format_version = fields.split_whitespace()
+                    .nth(1)
+                    .map(|version| self.parse_hex(version))
+                    .transpose()?
+                    .unwrap_or(0);
+            }
+            's' => {
+                let string = if self.file_format_version >= 2 {
+                    let (length, string) = fields.split_once(' ').unwrap_or((fields, ""));
+                    let length = self.parse_hex(length)?;
+                    string.get(..length).unwrap_or(string)
+                } else {
+                    fields
+                };
+                self.strings.push(string.to_string());
+            }
+            'i' => {
+                let frames = self.parse_instruction_pointer(fields)?;
+                self.instruction_pointers.push(frames);
+            }
+            't' => {
+                let values = self.parse_hex_fields(fields, 2, "trace")?;
+                self.traces.push((values[0], values[1]));
+            }
+            'a' => {
+                let values = self.parse_hex_fields(fields, 2, "allocation info")?;
+                self.allocation_infos.push((values[0], values[1]));
+            }
+            'c' => self.real_time_ms = self.parse_hex(fields)? as u64,
+            '+' => return self.process_allocation(fields).map(Some),
+            '-' => return self.process_free(fields),
+            // Comments, the command line, system info, RSS samples, attach markers, suppressions
+            // and modules do not affect the heap
+            '#' | 'X' | 'I' | 'R' | 'A' | 'S' | 'm' => {},
+            unknown => return Err(self.malformed_record(&format!("invalid record type {unknown}"))),
+        }
+        Ok(None)
+    }
+
+    /// Allocates a new block for an allocation info.
+    fn process_allocation(&mut self, fields: &str) -> Result<MemoryUpdateType, DamselflyError> {
+        let allocation_info = self.parse_hex(fields)?;
+        let &(size, trace) = self.allocation_infos.get(allocation_info)
+            .ok_or_else(|| self.malformed_record(&format!("allocation info {allocation_info:x} has not been defined")))?;
+        let callstack = self.get_callstack(trace)?;
+        let address = self.next_address;
+        self.next_address += size.max(1).next_multiple_of(BLOCK_ALIGNMENT);
+        self.live_blocks.entry(allocation_info).or_default().push(address);
+
+        let memory_update = Allocation::new(address, size, callstack, self.time, self.real_timestamp()).wrap_in_enum();
+        self.time += 1;
+        Ok(memory_update)
+    }
+
+    /// Frees the newest live block of an allocation info.
+    ///
+    /// returns: The free, or None if the allocation info has no live blocks.
+    fn process_free(&mut self, fields: &str) -> Result<Option<MemoryUpdateType>, DamselflyError> {
+        let allocation_info = self.parse_hex(fields)?;
+        let &(size, trace) = self.allocation_infos.get(allocation_info)
+            .ok_or_else(|| self.malformed_record(&format!("allocation info {allocation_info:x} has not been defined")))?;
+        let Some(address) = self.live_blocks.get_mut(&allocation_info).and_then(|blocks| blocks.pop()) else {
+            return Ok(None);
+        };
+        let callstack = self.get_callstack(trace)?;
+
+        let memory_update = Free::new(address, size, callstack, self.time, self.real_timestamp()).wrap_in_enum();
+        self.time += 1;
+        Ok(Some(memory_update))
+    }
+
+    /// Formats the frames of an instruction pointer record as "function (file:line)", one per
+    /// line. If the function was inlined, the functions it was inlined into follow it.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields`: Fields of the record: address, module, then optionally function, file, line and
+    ///   (function, file, line) of each function it was inlined into.
+    ///
+    /// returns: The frames, innermost first, each ending in a newline.
+    fn parse_instruction_pointer(&self, fields: &str) -> Result<String, DamselflyError> {
+        let values = fields.split_whitespace()
+            .map(|value| self.parse_hex(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [address, module, rest @ ..] = values.as_slice() else {
+            return Err(self.malformed_record("instruction pointer record is missing its address or module"));
+        };
+        let Some((&function, locations)) = rest.split_first() else {
+            return Ok(format!("{address:#x} ({})\n", self.get_string(*module)?));
+        };
+
+        let mut frames = self.format_frame(function, locations.first().copied(), locations.get(1).copied())?;
+        for inlined in locations.get(2..).unwrap_or(&[]).chunks(3) {
+            let [function, file, line] = inlined else {
+                return Err(self.malformed_record("inlined frame is missing its file or line"));
+            };
+            frames.push_str(&self.format_frame(*function, Some(*file), Some(*line))?);
+        }
+        Ok(frames)
+    }
+
+    /// Formats a frame as "function (file:line)", or just "function" if the file is not known.
+    fn format_frame(&self, function: usize, file: Option<usize>, line: Option<usize>) -> Result<String, DamselflyError> {
+        let function = self.get_string(function)?;
+        match (file, line) {
+            (Some(file), Some(line)) if file != 0 => Ok(format!("{function} ({}:{line})\n", self.get_string(file)?)),
+            _ => Ok(format!("{function}\n")),
+        }
+    }
+
+    /// Builds the callstack of a trace by walking up to the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `trace`: Trace index, starting from 1. 0 is the empty trace.
+    ///
+    /// returns: The callstack, innermost frame first.
+    fn get_callstack(&mut self, trace: usize) -> Result<Arc<String>, DamselflyError> {
+        if let Some(callstack) = self.callstacks.get(&trace) {
+            return Ok(callstack.clone());
+        }
+        let mut callstack = String::new();
+        let mut current_trace = trace;
+        // Parents are defined before their children, so this always reaches the root
+        while current_trace != 0 {
+            let &(instruction_pointer, parent) = self.traces.get(current_trace - 1)
+                .filter(|(_, parent)| *parent < current_trace)
+                .ok_or_else(|| self.malformed_record(&format!("trace {current_trace:x} has not been defined")))?;
+            let frames = instruction_pointer.checked_sub(1)
+                .and_then(|index| self.instruction_pointers.get(index))
+                .ok_or_else(|| self.malformed_record(&format!("instruction pointer {instruction_pointer:x} has not been defined")))?;
+            callstack.push_str(frames);
+            current_trace = parent;
+        }
+        let callstack = Arc::new(callstack);
+        self.callstacks.insert(trace, callstack.clone());
+        Ok(callstack)
+    }
+
+    /// Gets a string by its index, starting from 1. 0 is the empty string.
+    fn get_string(&self, index: usize) -> Result<&str, DamselflyError> {
+        if index == 0 {
+            return Ok("");
+        }
+        self.strings.get(index - 1)
+            .map(String::as_str)
+            .ok_or_else(|| self.malformed_record(&format!("string {index:x} has not been defined")))
+    }
+
+    fn real_timestamp(&self) -> String {
+        format!("{} ms", self.real_time_ms)
+    }
+
+    /// Parses a given number of hex fields from a record.
+    fn parse_hex_fields(&self, fields: &str, count: usize, record_name: &str) -> Result<Vec<usize>, DamselflyError> {
+        let values = fields.split_whitespace()
+            .take(count)
+            .map(|value| self.parse_hex(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.len() < count {
+            return Err(self.malformed_record(&format!("{record_name} record has {} fields, expected {count}", values.len())));
+        }
+        Ok(values)
+    }
+
+    /// Parses a hex number, without a 0x prefix, from the line currently being parsed.
+    fn parse_hex(&self, text: &str) -> Result<usize, DamselflyError> {
+        usize::from_str_radix(text.trim(), 16)
+            .map_err(|_| self.malformed_record(&format!("invalid hex number \"{text}\"")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::heaptrack_parser::MemoryHeaptrackParser;
+    use crate::damselfly::memory::memory_update::MemoryUpdateType;
+
+    const TEST_LOG: &str = "v 10800 3
+# heaptrack.prog.1234
+X ./prog
+s 6 ./prog
+s 4 main
+s 6 main.c
+s a make_table
+s 7 table.h
+I 1000 1f7e9
+i 4005b6 1 2 3 c
+i 4005f0 1 4 5 8 2 3 14
+t 1 0
+t 2 1
+a 64 2
+a 10 1
+c 0
++ 0
++ 1
+c 3e8
++ 0
+- 0
+- 1
+- 1
+c 7d0
+";
+
+    #[test]
+    fn parse_memory_updates_test() {
+        let mut parser = MemoryHeaptrackParser::new();
+        let updates = parser.parse_memory_updates(TEST_LOG.lines()).unwrap();
+        // The second free of allocation info 1 has no live block left
+        assert_eq!(updates.len(), 5);
+
+        assert!(matches!(updates[0], MemoryUpdateType::Allocation(_)));
+        assert_eq!(updates[0].get_absolute_size(), 100);
+        assert_eq!(*updates[0].get_callstack(), "make_table (table.h:8)\nmain (main.c:20)\nmain (main.c:12)\n");
+        assert_eq!(*updates[1].get_callstack(), "main (main.c:12)\n");
+        assert_eq!(*updates[2].get_real_timestamp(), "1000 ms");
+
+        // Frees release the newest block of their allocation info
+        assert!(matches!(updates[3], MemoryUpdateType::Free(_)));
+        assert_eq!(updates[3].get_absolute_address(), updates[2].get_absolute_address());
+        assert_eq!(updates[4].get_absolute_address(), updates[1].get_absolute_address());
+        assert_eq!(updates[4].get_timestamp(), 4);
+    }
+
+    #[test]
+    fn unknown_symbol_test() {
+        let log = "v 10800 3\ns 6 ./prog\ni 4005b6 1\nt 1 0\na 8 1\n+ 0\n";
+        let updates = MemoryHeaptrackParser::new().parse_memory_updates(log.lines()).unwrap();
+        assert_eq!(*updates[0].get_callstack(), "0x4005b6 (./prog)\n");
+    }
+
+    #[test]
+    fn undefined_allocation_info_test() {
+        let log = "v 10800 3\n+ 3\n";
+        let result = MemoryHeaptrackParser::new().parse_memory_updates(log.lines());
+        assert!(matches!(result, Err(DamselflyError::MalformedRecord { line: 2, .. })));
+    }
+}
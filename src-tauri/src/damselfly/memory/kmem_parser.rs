@@ -0,0 +1,365 @@
+//! Parser for Linux kernel kmem trace events, as printed by trace_pipe or trace-cmd report, so that
+//! slab usage can be inspected:
+//!
+//! ```text
+//! bash-1234  [001] ....  5678.123456: kmalloc: call_site=__alloc_skb+0x8e/0x1d0 ptr=ffff9a0c4e8b1000 bytes_req=640 bytes_alloc=1024 gfp_flags=GFP_KERNEL
+//! bash-1234  [001] ....  5678.123501: kmem_cache_alloc: call_site=d_alloc+0x1f/0xa0 ptr=ffff9a0c41d2c0c0 bytes_req=192 bytes_alloc=192 gfp_flags=GFP_KERNEL name=dentry
+//! bash-1234  [001] ....  5678.124010: kfree: call_site=skb_release_data+0x12e/0x1a0 ptr=ffff9a0c4e8b1000
+//! ```
+//!
+//! Each slab cache is put in its own pool. kmem_cache_alloc events name their cache on newer
+//! kernels; kmalloc events are put in the kmalloc-<bytes_alloc> cache that serves them. Caches share
+//! the same address range, so operations are sorted into pools by cache rather than by address.
+//! Frees of objects that were allocated before tracing started are ignored, as their cache and
+//! size are not known. Other trace events are skipped.
+//!
+//! The call site is used as a single-frame callstack, and the task's PID as its thread ID.
+//! Realtime timestamps are relative to the first kmem event.
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::sync::Arc;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::log_lines::LogLines;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::memory_parsers::{LenientParser, MemoryParser, ParseResults};
+use crate::damselfly::memory::memory_pool::MemoryPool;
+use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType};
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+
+/// Events that allocate an object.
+const ALLOCATION_EVENTS: [&str; 4] = ["kmalloc", "kmalloc_node", "kmem_cache_alloc", "kmem_cache_alloc_node"];
+/// Events that free an object.
+const FREE_EVENTS: [&str; 2] = ["kfree", "kmem_cache_free"];
+/// Largest kmalloc served by a kmalloc-<size> cache. Larger allocations come straight from the
+/// page allocator.
+const KMALLOC_MAX_CACHE_SIZE: usize = 8192;
+
+/// A kmem event, split into its parts.
+struct KmemEvent<'a> {
+    name: &'a str,
+    pid: Option<usize>,
+    timestamp: &'a str,
+    fields: HashMap<&'a str, &'a str>,
+}
+
+/// Parser for kmem trace events.
+#[derive(Default)]
+pub struct MemoryKmemParser {
+    time: usize,
+    first_timestamp_microseconds: Option<u64>,
+    // Address -> (size, cache) of each live object
+    live_objects: HashMap<usize, (usize, Arc<String>)>,
+    caches: HashMap<String, Arc<String>>,
+    // Cache -> (lowest address, highest end address) of its objects
+    cache_bounds: BTreeMap<Arc<String>, (usize, usize)>,
+    update_pools: Vec<Arc<String>>,
+    line_number: usize,
+    lenient: bool,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl MemoryParser for MemoryKmemParser {
+    /// Parses kmem trace events into a Vec of MemoryUpdateTypes.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw trace.
+    /// * `_binary_path`: Unused, as the kernel has already symbolised the call sites.
+    ///
+    /// returns: ParseResults
+    fn parse_log_directly(self, log: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_log_contents(LogLines::new(log.as_bytes(), "[log]"))
+    }
+
+    /// Parses kmem trace events using the trace's file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: File path to the trace.
+    /// * `_binary_path`: Unused, as the kernel has already symbolised the call sites.
+    ///
+    /// returns: ParseResults
+    fn parse_log(self, log_path: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemoryKmemParser::parse_log]: log: {log_path}");
+        self.parse_log_contents(LogLines::new(LogReader::open(log_path)?, log_path))
+    }
+}
+
+impl LenientParser for MemoryKmemParser {
+    fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>> {
+        self.lenient.then_some(&mut self.diagnostics)
+    }
+}
+
+impl MemoryKmemParser {
+    pub fn new() -> MemoryKmemParser {
+        MemoryKmemParser::default()
+    }
+
+    /// Constructs a parser that skips malformed events instead of failing. Each skipped event is
+    /// reported in the diagnostics of the ParseResults.
+    pub fn new_lenient() -> MemoryKmemParser {
+        MemoryKmemParser {
+            lenient: true,
+            ..Self::new()
+        }
+    }
+
+    /// Parses a whole trace, consuming itself and returning parse results with a pool for each
+    /// slab cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the trace.
+    ///
+    /// returns: ParseResults
+    fn parse_log_contents<R: BufRead>(mut self, mut log_lines: LogLines<R>) -> Result<ParseResults, DamselflyError> {
+        let memory_updates = self.parse_memory_updates(log_lines.by_ref())?;
+        log_lines.check_error()?;
+        let mut pool_list = MemoryPoolList::default();
+        for (cache, (start, end)) in &self.cache_bounds {
+            pool_list.add_pool(MemoryPool::new(*start, end - start, cache.to_string()));
+        }
+        ParseResults::from_parsed_log(memory_updates, pool_list, self.diagnostics)
+            .with_update_pools(self.update_pools)
+    }
+
+    /// Parses the kmem events in a trace. The cache of each memory operation is recorded in
+    /// update_pools.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the trace.
+    ///
+    /// returns: The memory operations in the trace, in order.
+    pub fn parse_memory_updates<S: AsRef<str>>(&mut self, log_lines: impl Iterator<Item = S>) -> Result<Vec<MemoryUpdateType>, DamselflyError> {
+        let mut memory_updates = Vec::new();
+        for line in log_lines {
+            let line = line.as_ref();
+            self.line_number += 1;
+            match self.line_to_memory_update(line) {
+                Ok(Some((memory_update, cache))) => {
+                    memory_updates.push(memory_update);
+                    self.update_pools.push(cache);
+                }
+                Ok(None) => {},
+                Err(error) => self.skip_or_fail(line, error)?,
+            }
+        }
+        Ok(memory_updates)
+    }
+
+    /// Parses a line of the trace into a memory update.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the trace.
+    ///
+    /// returns: The memory update and the cache it belongs to, or None if the line is not a kmem
+    /// event or does not change the heap (e.g. kfree(NULL)).
+    fn line_to_memory_update(&mut self, line: &str) -> Result<Option<(MemoryUpdateType, Arc<String>)>, DamselflyError> {
+        let Some(event) = Self::split_event(line) else {
+            return Ok(None);
+        };
+        let real_timestamp = self.real_timestamp(event.timestamp)?;
+        let address = self.parse_address(self.field(&event, "ptr")?)?;
+        // Failed allocations and frees of NULL do nothing
+        if address == 0 {
+            return Ok(None);
+        }
+        let callstack = Arc::new(event.fields.get("call_site")
+            .map(|call_site| format!("{}\n", Self::strip_function_size(call_site)))
+            .unwrap_or_default());
+
+        let (mut memory_update, cache) = if ALLOCATION_EVENTS.contains(&event.name) {
+            let bytes_alloc = self.field(&event, "bytes_alloc")?;
+            let size: usize = bytes_alloc.parse()
+                .map_err(|_| DamselflyError::BadSize { line: self.line_number, text: bytes_alloc.to_string() })?;
+            let cache = match event.fields.get("name") {
+                Some(name) => self.intern_cache(name),
+                None if event.name.starts_with("kmalloc") && size <= KMALLOC_MAX_CACHE_SIZE => self.intern_cache(&format!("kmalloc-{size}")),
+                None if event.name.starts_with("kmalloc") => self.intern_cache("kmalloc-large"),
+                None => self.intern_cache("kmem_cache"),
+            };
+            self.live_objects.insert(address, (size, cache.clone()));
+            let bounds = self.cache_bounds.entry(cache.clone()).or_insert((address, address + size));
+            *bounds = (bounds.0.min(address), bounds.1.max(address + size));
+            (Allocation::new(address, size, callstack, self.time, real_timestamp).wrap_in_enum(), cache)
+        } else {
+            let Some((size, cache)) = self.live_objects.remove(&address) else {
+                return Ok(None);
+            };
+            (Free::new(address, size, callstack, self.time, real_timestamp).wrap_in_enum(), cache)
+        };
+        memory_update.set_thread_id(event.pid);
+        self.time += 1;
+        Ok(Some((memory_update, cache)))
+    }
+
+    /// Splits a kmem event into its task PID, timestamp, name and fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the trace, e.g.
+    ///   bash-1234 [001] .... 5678.123456: kfree: call_site=skb_release_data+0x12e/0x1a0 ptr=ffff9a0c4e8b1000
+    ///
+    /// returns: The event, or None if the line is not a kmem event.
+    fn split_event(line: &str) -> Option<KmemEvent<'_>> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let event_index = tokens.iter().position(|token| {
+            token.strip_suffix(':').is_some_and(|name| ALLOCATION_EVENTS.contains(&name) || FREE_EVENTS.contains(&name))
+        })?;
+        let name = tokens[event_index].strip_suffix(':')?;
+        let timestamp = tokens.get(event_index.checked_sub(1)?)?.strip_suffix(':')?;
+        // The task name can contain spaces and dashes, so the PID is after the last dash before the CPU
+        let pid = line.find(" [")
+            .and_then(|cpu_pos| line[..cpu_pos].trim().rsplit_once('-'))
+            .and_then(|(_, pid)| pid.parse().ok());
+        let fields = tokens[event_index + 1..].iter()
+            .filter_map(|field| field.split_once('='))
+            .collect();
+        Some(KmemEvent { name, pid, timestamp, fields })
+    }
+
+    /// Removes the function size from a call site, e.g. __alloc_skb+0x8e/0x1d0 becomes __alloc_skb+0x8e.
+    fn strip_function_size(call_site: &str) -> &str {
+        call_site.split_once('/').map_or(call_site, |(call_site, _)| call_site)
+    }
+
+    /// Gets the shared name of a cache, so that every operation in a cache refers to the same String.
+    fn intern_cache(&mut self, name: &str) -> Arc<String> {
+        self.caches.entry(name.to_string())
+            .or_insert_with(|| Arc::new(name.to_string()))
+            .clone()
+    }
+
+    /// Converts a trace timestamp in seconds (e.g. 5678.123456) to a realtime timestamp relative to
+    /// the first event. The conversion is exact, as the timestamp is split at the decimal point.
+    fn real_timestamp(&mut self, timestamp: &str) -> Result<String, DamselflyError> {
+        let bad_timestamp = || DamselflyError::BadTimestamp { line: self.line_number, text: timestamp.to_string() };
+        let (seconds, fraction) = timestamp.split_once('.').unwrap_or((timestamp, ""));
+        let seconds: u64 = seconds.parse().map_err(|_| bad_timestamp())?;
+        if !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(bad_timestamp());
+        }
+        // Digits past microseconds are dropped
+        let fraction = fraction.get(..6).unwrap_or(fraction);
+        let microseconds: u64 = format!("{fraction:0<6}").parse().map_err(|_| bad_timestamp())?;
+        let timestamp_microseconds = seconds.checked_mul(1_000_000)
+            .and_then(|timestamp| timestamp.checked_add(microseconds))
+            .ok_or_else(bad_timestamp)?;
+        let first_timestamp_microseconds = *self.first_timestamp_microseconds.get_or_insert(timestamp_microseconds);
+        Ok(format!("{} us", timestamp_microseconds.saturating_sub(first_timestamp_microseconds)))
+    }
+
+    /// Gets a field of an event, failing if it is missing.
+    fn field<'a>(&self, event: &KmemEvent<'a>, name: &str) -> Result<&'a str, DamselflyError> {
+        event.fields.get(name)
+            .copied()
+            .ok_or_else(|| self.malformed_record(&format!("{} event is missing its {name}", event.name)))
+    }
+
+    /// Parses a hex pointer, with or without a 0x prefix, from the line currently being parsed.
+    fn parse_address(&self, text: &str) -> Result<usize, DamselflyError> {
+        usize::from_str_radix(text.trim_start_matches("0x"), 16)
+            .map_err(|_| DamselflyError::BadAddress { line: self.line_number, text: text.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::kmem_parser::MemoryKmemParser;
+    use crate::damselfly::memory::memory_parsers::MemoryParser;
+    use crate::damselfly::memory::memory_update::MemoryUpdateType;
+
+    const TEST_LOG: &str = "# tracer: nop
+#
+           <idle>-0       [002] d.s.  5678.100000: softirq_entry: vec=3 [action=NET_RX]
+             bash-1234    [001] ....  5678.123456: kmalloc: call_site=__alloc_skb+0x8e/0x1d0 ptr=ffff9a0c4e8b1000 bytes_req=640 bytes_alloc=1024 gfp_flags=GFP_KERNEL node=-1 accounted=false
+             bash-1234    [001] ....  5678.123501: kmem_cache_alloc: call_site=d_alloc+0x1f/0xa0 ptr=ffff9a0c4e8b0400 bytes_req=192 bytes_alloc=192 gfp_flags=GFP_KERNEL node=-1 accounted=false name=dentry
+ kworker/u16:2-88     [003] ....  5678.124000: kfree: call_site=rcu_do_batch+0x19d/0x4c0 ptr=0000000000000000
+ kworker/u16:2-88     [003] ....  5678.124010: kfree: call_site=skb_release_data+0x12e/0x1a0 ptr=ffff9a0c4e8b1000
+ kworker/u16:2-88     [003] ....  5678.124020: kmem_cache_free: call_site=dput+0x1a5/0x2f0 ptr=ffff9a0c41000000 name=dentry
+";
+
+    #[test]
+    fn parse_memory_updates_test() {
+        let mut parser = MemoryKmemParser::new();
+        let updates = parser.parse_memory_updates(TEST_LOG.lines()).unwrap();
+        // kfree(NULL) and the free of an object allocated before tracing started are ignored
+        assert_eq!(updates.len(), 3);
+
+        assert!(matches!(updates[0], MemoryUpdateType::Allocation(_)));
+        assert_eq!(updates[0].get_absolute_address(), 0xffff9a0c4e8b1000);
+        assert_eq!(updates[0].get_absolute_size(), 1024);
+        assert_eq!(*updates[0].get_callstack(), "__alloc_skb+0x8e\n");
+        assert_eq!(*updates[0].get_real_timestamp(), "0 us");
+        assert_eq!(updates[0].get_thread_id(), Some(1234));
+        assert_eq!(*updates[1].get_real_timestamp(), "45 us");
+
+        assert!(matches!(updates[2], MemoryUpdateType::Free(_)));
+        assert_eq!(updates[2].get_absolute_size(), 1024);
+        assert_eq!(updates[2].get_thread_id(), Some(88));
+        assert_eq!(updates[2].get_timestamp(), 2);
+
+        let update_pools: Vec<&str> = parser.update_pools.iter().map(|pool| pool.as_str()).collect();
+        assert_eq!(update_pools, vec!["kmalloc-1024", "dentry", "kmalloc-1024"]);
+    }
+
+    #[test]
+    fn split_by_pools_test() {
+        let pools = MemoryKmemParser::new()
+            .parse_log_directly(TEST_LOG, "")
+            .unwrap()
+            .split_by_pools(0, 0)
+            .unwrap();
+        // Pools are in the same order every time the trace is opened
+        let pool_names: Vec<&str> = pools.iter().map(|pool| pool.pool.get_name()).collect();
+        assert_eq!(pool_names, vec!["dentry", "kmalloc-1024"]);
+        assert_eq!(pools.len(), 2);
+        for pool in pools {
+            match pool.pool.get_name() {
+                // The dentry pool is inside the kmalloc-1024 pool's range, but gets none of its operations
+                "dentry" => {
+                    assert_eq!(pool.memory_updates.len(), 1);
+                    assert_eq!(pool.pool.get_start(), 0xffff9a0c4e8b0400);
+                    assert_eq!(pool.pool.get_size(), 192);
+                }
+                "kmalloc-1024" => assert_eq!(pool.memory_updates.len(), 2),
+                name => panic!("Unexpected pool {name}"),
+            }
+        }
+    }
+
+    #[test]
+    fn real_timestamp_test() {
+        let mut parser = MemoryKmemParser::new();
+        assert_eq!(parser.real_timestamp("10.5").unwrap(), "0 us");
+        assert_eq!(parser.real_timestamp("11.000001").unwrap(), "500001 us");
+        assert!(matches!(parser.real_timestamp("1o.5"), Err(DamselflyError::BadTimestamp { .. })));
+        assert!(matches!(parser.real_timestamp("11.1234\u{FFFD}"), Err(DamselflyError::BadTimestamp { .. })));
+        assert!(matches!(parser.real_timestamp("11.-5"), Err(DamselflyError::BadTimestamp { .. })));
+    }
+
+    #[test]
+    fn non_ascii_timestamp_test() {
+        let log = "bash-1234 [001] .... 5678.1234\u{FFFD}: kfree: call_site=x+0x1/0x2 ptr=ffff9a0c4e8b1000";
+        let parse_results = MemoryKmemParser::new_lenient().parse_log_directly(log, "").unwrap();
+        assert!(parse_results.memory_updates.is_empty());
+        assert_eq!(parse_results.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn malformed_event_test() {
+        let log = "bash-1234 [001] .... 5678.123456: kmalloc: call_site=f+0x1/0x2 ptr=ffff9a0c4e8b1000\n";
+        let result = MemoryKmemParser::new().parse_memory_updates(log.lines());
+        assert!(matches!(result, Err(DamselflyError::MalformedRecord { line: 1, .. })));
+    }
+}
@@ -0,0 +1,193 @@
+//! Finds the allocations that are still live at a point in the trace (by default, the end of the
+//! trace) and groups them by the callstack that made them, so that the largest leaks can be found
+//! at a glance.
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
+
+/// Allocations made from the same callstack that are still live.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeakGroup {
+    callstack: Arc<String>,
+    count: usize,
+    total_bytes: usize,
+    addresses: Vec<usize>,
+}
+
+impl LeakGroup {
+    pub fn get_callstack(&self) -> Arc<String> {
+        self.callstack.clone()
+    }
+
+    /// Gets the number of live allocations made from this callstack.
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+
+    /// Gets the number of bytes held by live allocations made from this callstack.
+    pub fn get_total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Gets the addresses of the live allocations, in ascending order.
+    pub fn get_addresses(&self) -> &Vec<usize> {
+        &self.addresses
+    }
+}
+
+impl Serialize for LeakGroup {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut state = serializer.serialize_struct("LeakGroup", 4)?;
+        state.serialize_field("callstack", &*self.callstack)?;
+        state.serialize_field("count", &self.count)?;
+        state.serialize_field("total_bytes", &self.total_bytes)?;
+        state.serialize_field("addresses", &self.addresses)?;
+        state.end()
+    }
+}
+
+pub struct LeakReportFactory {
+    memory_updates: Vec<MemoryUpdateType>,
+    left_padding: usize,
+    right_padding: usize,
+}
+
+impl LeakReportFactory {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_updates`: Updates to search for leaks, in any order.
+    /// * `left_padding`: Padding that has been applied to the left of each update. It is removed
+    ///   from the reported addresses.
+    /// * `right_padding`: Padding that has been applied to the right of each update. It is removed
+    ///   from the reported sizes.
+    ///
+    /// returns: LeakReportFactory
+    pub fn new(mut memory_updates: Vec<MemoryUpdateType>, left_padding: usize, right_padding: usize) -> LeakReportFactory {
+        memory_updates.sort_by_key(|update| update.get_timestamp());
+        LeakReportFactory {
+            memory_updates,
+            left_padding,
+            right_padding,
+        }
+    }
+
+    /// Finds every allocation that is live at a timestamp, grouped by callstack.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp`: Operation timestamp to check at, inclusive. None checks at the end of the trace.
+    ///
+    /// returns: Vec<LeakGroup>, largest total size first
+    pub fn calculate_leaks(&self, timestamp: Option<usize>) -> Vec<LeakGroup> {
+        let timestamp = timestamp.unwrap_or(usize::MAX);
+        let mut live_allocations: HashMap<usize, &MemoryUpdateType> = HashMap::new();
+        for update in self.memory_updates
+            .iter()
+            .take_while(|update| update.get_timestamp() <= timestamp) {
+            match update {
+                MemoryUpdateType::Allocation(allocation) => {
+                    live_allocations.insert(allocation.get_absolute_address(), update);
+                }
+                MemoryUpdateType::Free(free) => {
+                    live_allocations.remove(&free.get_absolute_address());
+                }
+                MemoryUpdateType::Reallocation(reallocation) => {
+                    live_allocations.remove(&reallocation.get_old_address());
+                    if reallocation.get_absolute_size() > self.right_padding {
+                        live_allocations.insert(reallocation.get_absolute_address(), update);
+                    }
+                }
+            }
+        }
+
+        let mut groups: HashMap<Arc<String>, LeakGroup> = HashMap::new();
+        for (address, update) in live_allocations {
+            let group = groups.entry(update.get_callstack()).or_insert_with(|| LeakGroup {
+                callstack: update.get_callstack(),
+                count: 0,
+                total_bytes: 0,
+                addresses: Vec::new(),
+            });
+            group.count += 1;
+            group.total_bytes += update.get_absolute_size().saturating_sub(self.right_padding);
+            group.addresses.push(address + self.left_padding);
+        }
+
+        let mut groups: Vec<LeakGroup> = groups.into_values().collect();
+        for group in groups.iter_mut() {
+            group.addresses.sort_unstable();
+        }
+        groups.sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes)
+            .then(b.count.cmp(&a.count))
+            .then(a.callstack.cmp(&b.callstack)));
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::damselfly::memory::leak_report_factory::LeakReportFactory;
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+
+    fn initialise_test_updates() -> Vec<MemoryUpdateType> {
+        let first_callstack = Arc::new(String::from("first"));
+        let second_callstack = Arc::new(String::from("second"));
+        vec![
+            Allocation::new(0, 8, first_callstack.clone(), 0, String::from("0001.676 s")).wrap_in_enum(),
+            Allocation::new(16, 8, first_callstack.clone(), 1, String::from("0001.677 s")).wrap_in_enum(),
+            Allocation::new(32, 64, second_callstack.clone(), 2, String::from("0001.678 s")).wrap_in_enum(),
+            Free::new(0, 8, first_callstack.clone(), 3, String::from("0001.679 s")).wrap_in_enum(),
+            Free::new(32, 64, second_callstack.clone(), 4, String::from("0001.680 s")).wrap_in_enum(),
+        ]
+    }
+
+    #[test]
+    fn calculate_leaks_at_end_test() {
+        let leaks = LeakReportFactory::new(initialise_test_updates(), 0, 0).calculate_leaks(None);
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(*leaks[0].get_callstack(), "first");
+        assert_eq!(leaks[0].get_count(), 1);
+        assert_eq!(leaks[0].get_total_bytes(), 8);
+        assert_eq!(leaks[0].get_addresses(), &vec![16]);
+    }
+
+    #[test]
+    fn calculate_leaks_at_timestamp_test() {
+        let leaks = LeakReportFactory::new(initialise_test_updates(), 0, 0).calculate_leaks(Some(2));
+        assert_eq!(leaks.len(), 2);
+        assert_eq!(*leaks[0].get_callstack(), "second");
+        assert_eq!(leaks[0].get_total_bytes(), 64);
+        assert_eq!(*leaks[1].get_callstack(), "first");
+        assert_eq!(leaks[1].get_count(), 2);
+        assert_eq!(leaks[1].get_total_bytes(), 16);
+        assert_eq!(leaks[1].get_addresses(), &vec![0, 16]);
+    }
+
+    #[test]
+    fn calculate_leaks_realloc_test() {
+        let mut updates = initialise_test_updates();
+        updates.push(Reallocation::new(128, 32, 16, 8, Arc::new(String::from("resize")), 5, String::from("0001.681 s")).wrap_in_enum());
+        let leaks = LeakReportFactory::new(updates, 0, 0).calculate_leaks(None);
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(*leaks[0].get_callstack(), "resize");
+        assert_eq!(leaks[0].get_total_bytes(), 32);
+        assert_eq!(leaks[0].get_addresses(), &vec![128]);
+    }
+
+    #[test]
+    fn calculate_leaks_padding_test() {
+        let mut updates = initialise_test_updates();
+        for update in updates.iter_mut() {
+            update.set_absolute_address(update.get_absolute_address() + 64);
+            update.set_absolute_size(update.get_absolute_size() + 4);
+        }
+        let leaks = LeakReportFactory::new(updates, 2, 4).calculate_leaks(None);
+        assert_eq!(leaks[0].get_total_bytes(), 8);
+        assert_eq!(leaks[0].get_addresses(), &vec![82]);
+    }
+}
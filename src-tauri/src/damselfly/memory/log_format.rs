@@ -0,0 +1,312 @@
+//! Chooses a MemoryParser at runtime, so that the GUI and headless binary can open logs in any
+//! supported format.
+use std::fmt::{Display, Formatter};
+use std::io::BufRead;
+use std::str::FromStr;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::memory_parsers::{MemoryParser, MemorySysTraceParser, ParseResults};
+use crate::damselfly::memory::mtrace_parser::MemoryMtraceParser;
+use crate::damselfly::memory::massif_parser::MemoryMassifParser;
+use crate::damselfly::memory::dhat_parser::MemoryDhatParser;
+use crate::damselfly::memory::heaptrack_parser::MemoryHeaptrackParser;
+use crate::damselfly::memory::kmem_parser::MemoryKmemParser;
+use crate::damselfly::memory::native_format::MemoryNativeParser;
+use crate::damselfly::memory::binary_format::MemoryBinaryParser;
+use crate::damselfly::memory::systrace_layout::SysTraceLayout;
+use crate::damselfly::memory::symbol_options::SymbolOptions;
+
+/// Number of lines to look at when detecting the format of a log.
+const DETECTION_LINES: usize = 64;
+
+/// Formats of log that can be parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    SysTrace,
+    Mtrace,
+    Massif,
+    Dhat,
+    Heaptrack,
+    Kmem,
+    Native,
+    Binary,
+}
+
+impl LogFormat {
+    /// Guesses the format of a log from its first few lines. Logs that are not recognised are
+    /// assumed to be SysTrace logs, so that errors are reported by the default parser.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: Path to the log.
+    ///
+    /// returns: LogFormat, or an error if the log cannot be read
+    pub fn detect(log_path: &str) -> Result<LogFormat, DamselflyError> {
+        let io_error = |source| DamselflyError::Io { path: log_path.to_string(), source };
+        let mut reader = LogReader::open(log_path)?;
+        if MemoryBinaryParser::is_binary_trace(reader.fill_buf().map_err(io_error)?) {
+            return Ok(LogFormat::Binary);
+        }
+        let mut lines = Vec::new();
+        for line in reader.lines().take(DETECTION_LINES) {
+            match line {
+                Ok(line) => lines.push(line),
+                // Binary data that is not UTF-8 cannot be any of the text formats
+                Err(error) if error.kind() == std::io::ErrorKind::InvalidData => break,
+                Err(error) => return Err(io_error(error)),
+            }
+        }
+        Ok(Self::detect_from_lines(&lines))
+    }
+
+    /// Guesses the format of a log from its first few lines.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines`: The first lines of the log.
+    ///
+    /// returns: LogFormat
+    pub fn detect_from_lines<S: AsRef<str>>(lines: &[S]) -> LogFormat {
+        if lines.first().is_some_and(|line| MemoryNativeParser::is_header(line.as_ref())) {
+            return LogFormat::Native;
+        }
+        if lines.iter().any(|line| line.as_ref().contains("\"dhatFileVersion\"")) {
+            return LogFormat::Dhat;
+        }
+        // heaptrack data files start with "v <heaptrack version> <file format version>"
+        let is_heaptrack_version = |line: &str| {
+            let fields: Vec<&str> = line.split(' ').collect();
+            fields.len() == 3 && fields[0] == "v"
+                && fields[1..].iter().all(|field| !field.is_empty() && field.chars().all(|char| char.is_ascii_hexdigit()))
+        };
+        if lines.first().is_some_and(|line| is_heaptrack_version(line.as_ref())) {
+            return LogFormat::Heaptrack;
+        }
+        if lines.iter().any(|line| line.as_ref().contains("call_site=")) {
+            return LogFormat::Kmem;
+        }
+        let is_massif_header = lines.first().is_some_and(|line| line.as_ref().starts_with("desc:"));
+        if is_massif_header && lines.iter().any(|line| line.as_ref().starts_with("time_unit:")) {
+            return LogFormat::Massif;
+        }
+        let is_mtrace_line = |line: &str| {
+            line == "= Start" || line.starts_with("@ ")
+                || ["+ 0x", "- 0x", "< 0x", "> 0x"].iter().any(|prefix| line.starts_with(prefix))
+        };
+        if lines.iter().any(|line| is_mtrace_line(line.as_ref().trim())) {
+            return LogFormat::Mtrace;
+        }
+        LogFormat::SysTrace
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "systrace" => Ok(LogFormat::SysTrace),
+            "mtrace" => Ok(LogFormat::Mtrace),
+            "massif" => Ok(LogFormat::Massif),
+            "dhat" => Ok(LogFormat::Dhat),
+            "heaptrack" => Ok(LogFormat::Heaptrack),
+            "kmem" => Ok(LogFormat::Kmem),
+            "damselfly" => Ok(LogFormat::Native),
+            "binary" => Ok(LogFormat::Binary),
+            unknown => Err(format!("Unknown log format {unknown} (expected systrace, mtrace, massif, dhat, heaptrack, kmem, damselfly or binary)")),
+        }
+    }
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::SysTrace => write!(f, "systrace"),
+            LogFormat::Mtrace => write!(f, "mtrace"),
+            LogFormat::Massif => write!(f, "massif"),
+            LogFormat::Dhat => write!(f, "dhat"),
+            LogFormat::Heaptrack => write!(f, "heaptrack"),
+            LogFormat::Kmem => write!(f, "kmem"),
+            LogFormat::Native => write!(f, "damselfly"),
+            LogFormat::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// Parses logs in a format chosen at runtime by passing them to the parser for that format.
+pub struct FormatParser {
+    format: LogFormat,
+    lenient: bool,
+    systrace_layout: SysTraceLayout,
+    symbol_options: SymbolOptions,
+}
+
+impl FormatParser {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `format`: Format of the logs to parse.
+    /// * `lenient`: Whether to skip malformed records instead of failing.
+    ///
+    /// returns: FormatParser
+    pub fn new(format: LogFormat, lenient: bool) -> FormatParser {
+        FormatParser {
+            format,
+            lenient,
+            systrace_layout: SysTraceLayout::default(),
+            symbol_options: SymbolOptions::default(),
+        }
+    }
+
+    /// Sets the line layout used if the logs are SysTrace logs.
+    ///
+    /// # Arguments
+    ///
+    /// * `systrace_layout`: Where the fields of each SysTrace line are found.
+    ///
+    /// returns: FormatParser
+    pub fn with_systrace_layout(mut self, systrace_layout: SysTraceLayout) -> FormatParser {
+        self.systrace_layout = systrace_layout;
+        self
+    }
+
+    /// Sets where separate debuginfo files are searched for and how source paths are remapped,
+    /// for formats that are symbolised using the binary.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol_options`: Debuginfo search directories and source path remappings.
+    ///
+    /// returns: FormatParser
+    pub fn with_symbol_options(mut self, symbol_options: SymbolOptions) -> FormatParser {
+        self.symbol_options = symbol_options;
+        self
+    }
+
+    pub fn get_format(&self) -> LogFormat {
+        self.format
+    }
+
+    fn systrace_parser(&self) -> MemorySysTraceParser {
+        let parser = if self.lenient { MemorySysTraceParser::new_lenient() } else { MemorySysTraceParser::new() };
+        parser.with_layout(self.systrace_layout.clone())
+            .with_symbol_options(self.symbol_options.clone())
+    }
+
+    fn mtrace_parser(&self) -> MemoryMtraceParser {
+        let parser = if self.lenient { MemoryMtraceParser::new_lenient() } else { MemoryMtraceParser::new() };
+        parser.with_symbol_options(self.symbol_options.clone())
+    }
+
+    fn massif_parser(&self) -> MemoryMassifParser {
+        if self.lenient { MemoryMassifParser::new_lenient() } else { MemoryMassifParser::new() }
+    }
+
+    fn dhat_parser(&self) -> MemoryDhatParser {
+        if self.lenient { MemoryDhatParser::new_lenient() } else { MemoryDhatParser::new() }
+    }
+
+    fn heaptrack_parser(&self) -> MemoryHeaptrackParser {
+        if self.lenient { MemoryHeaptrackParser::new_lenient() } else { MemoryHeaptrackParser::new() }
+    }
+
+    fn kmem_parser(&self) -> MemoryKmemParser {
+        if self.lenient { MemoryKmemParser::new_lenient() } else { MemoryKmemParser::new() }
+    }
+
+    fn native_parser(&self) -> MemoryNativeParser {
+        if self.lenient { MemoryNativeParser::new_lenient() } else { MemoryNativeParser::new() }
+    }
+}
+
+impl MemoryParser for FormatParser {
+    fn parse_log_directly(self, log: &str, binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        match self.format {
+            LogFormat::SysTrace => self.systrace_parser().parse_log_directly(log, binary_path),
+            LogFormat::Mtrace => self.mtrace_parser().parse_log_directly(log, binary_path),
+            LogFormat::Massif => self.massif_parser().parse_log_directly(log, binary_path),
+            LogFormat::Dhat => self.dhat_parser().parse_log_directly(log, binary_path),
+            LogFormat::Heaptrack => self.heaptrack_parser().parse_log_directly(log, binary_path),
+            LogFormat::Kmem => self.kmem_parser().parse_log_directly(log, binary_path),
+            LogFormat::Native => self.native_parser().parse_log_directly(log, binary_path),
+            LogFormat::Binary => MemoryBinaryParser::new().parse_log_directly(log, binary_path),
+        }
+    }
+
+    fn parse_log(self, log_path: &str, binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        match self.format {
+            LogFormat::SysTrace => self.systrace_parser().parse_log(log_path, binary_path),
+            LogFormat::Mtrace => self.mtrace_parser().parse_log(log_path, binary_path),
+            LogFormat::Massif => self.massif_parser().parse_log(log_path, binary_path),
+            LogFormat::Dhat => self.dhat_parser().parse_log(log_path, binary_path),
+            LogFormat::Heaptrack => self.heaptrack_parser().parse_log(log_path, binary_path),
+            LogFormat::Kmem => self.kmem_parser().parse_log(log_path, binary_path),
+            LogFormat::Native => self.native_parser().parse_log(log_path, binary_path),
+            LogFormat::Binary => MemoryBinaryParser::new().parse_log(log_path, binary_path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::log_format::{FormatParser, LogFormat};
+    use crate::damselfly::memory::memory_parsers::MemoryParser;
+
+    #[test]
+    fn detect_from_lines_test() {
+        let mtrace_log = ["= Start", "@ ./prog:[0x4005b6] + 0x1c9f460 0x64"];
+        assert_eq!(LogFormat::detect_from_lines(&mtrace_log), LogFormat::Mtrace);
+        let mtrace_log_no_callers = ["+ 0x1c9f460 0x64"];
+        assert_eq!(LogFormat::detect_from_lines(&mtrace_log_no_callers), LogFormat::Mtrace);
+        let systrace_log = ["00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14"];
+        assert_eq!(LogFormat::detect_from_lines(&systrace_log), LogFormat::SysTrace);
+        let massif_log = ["desc: --time-unit=ms", "cmd: ./prog", "time_unit: ms", "#-----------", "snapshot=0"];
+        assert_eq!(LogFormat::detect_from_lines(&massif_log), LogFormat::Massif);
+        let dhat_log = ["{\"dhatFileVersion\":2", ",\"mode\":\"heap\",\"verb\":\"Allocated\""];
+        assert_eq!(LogFormat::detect_from_lines(&dhat_log), LogFormat::Dhat);
+        let heaptrack_log = ["v 10800 3", "X ./prog", "+ 0"];
+        assert_eq!(LogFormat::detect_from_lines(&heaptrack_log), LogFormat::Heaptrack);
+        let kmem_log = ["# tracer: nop", "bash-1234 [001] .... 5678.124010: kfree: call_site=skb_release_data+0x12e/0x1a0 ptr=ffff9a0c4e8b1000"];
+        assert_eq!(LogFormat::detect_from_lines(&kmem_log), LogFormat::Kmem);
+        let native_log = ["{\"format\": \"damselfly\", \"version\": 1}"];
+        assert_eq!(LogFormat::detect_from_lines(&native_log), LogFormat::Native);
+    }
+
+    #[test]
+    fn from_str_test() {
+        assert_eq!("mtrace".parse::<LogFormat>(), Ok(LogFormat::Mtrace));
+        assert_eq!("SysTrace".parse::<LogFormat>(), Ok(LogFormat::SysTrace));
+        assert_eq!("DHAT".parse::<LogFormat>(), Ok(LogFormat::Dhat));
+        assert_eq!("binary".parse::<LogFormat>(), Ok(LogFormat::Binary));
+        assert!("callgrind".parse::<LogFormat>().is_err());
+        assert_eq!(LogFormat::Mtrace.to_string().parse::<LogFormat>(), Ok(LogFormat::Mtrace));
+    }
+
+    const NATIVE_LOG: &str = r#"{"format":"damselfly","version":1}
+{"Allocation":{"address":4096,"size":64,"callstack":"","timestamp":0,"real_timestamp":"1 s","thread_id":null}}
+{"Free":{"address":4096,"size":64,"callstack":"","timestamp":1,"real_timestamp":"2 minutes","thread_id":null}}
+"#;
+
+    #[test]
+    fn lenient_test() {
+        // (format, log, number of updates parsed leniently, lines skipped)
+        let cases = [
+            (LogFormat::Mtrace, "+ 0x1000 0x8\n+ 0xzz 0x8\n? 0x1000\n- 0x1000", 2, vec![2, 3]),
+            (LogFormat::Massif, "snapshot=0\ntime=0\nmem_heap_B=ten\n", 0, vec![3]),
+            (LogFormat::Heaptrack, "v 10800 3\n+ 3\n", 0, vec![2]),
+            (LogFormat::Kmem, "bash-1234 [001] .... 5678.123456: kmalloc: call_site=f+0x1/0x2 ptr=ffff9a0c4e8b1000\n", 0, vec![1]),
+            (LogFormat::Native, NATIVE_LOG, 1, vec![3]),
+        ];
+        for (format, log, update_count, skipped_lines) in cases {
+            let error = FormatParser::new(format, false).parse_log_directly(log, "").err().unwrap();
+            assert_eq!(error.line(), Some(skipped_lines[0]), "{format}");
+
+            let parse_results = FormatParser::new(format, true).parse_log_directly(log, "").unwrap();
+            assert_eq!(parse_results.memory_updates.len(), update_count, "{format}");
+            let diagnostic_lines: Vec<usize> = parse_results.diagnostics.iter().map(|diagnostic| diagnostic.get_line()).collect();
+            assert_eq!(diagnostic_lines, skipped_lines, "{format}");
+        }
+    }
+}
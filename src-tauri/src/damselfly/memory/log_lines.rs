@@ -0,0 +1,91 @@
+//! Iterates over the lines of a log from any BufRead, so that logs never have to be loaded into
+//! memory in full.
+//!
+//! Lines that are not valid UTF-8 are decoded lossily rather than aborting the read. An IO error
+//! ends the iteration early; call check_error afterwards to find out if this happened.
+use std::io::BufRead;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+
+pub struct LogLines<R: BufRead> {
+    reader: R,
+    path: String,
+    buffer: Vec<u8>,
+    error: Option<std::io::Error>,
+}
+
+impl<R: BufRead> LogLines<R> {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader`: Reader over the log.
+    /// * `path`: Path to the log, used when reporting IO errors.
+    ///
+    /// returns: LogLines<R>
+    pub fn new(reader: R, path: &str) -> Self {
+        Self {
+            reader,
+            path: path.to_string(),
+            buffer: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Returns the IO error that ended the iteration early, if there was one.
+    pub fn check_error(&mut self) -> Result<(), DamselflyError> {
+        match self.error.take() {
+            Some(source) => Err(DamselflyError::Io { path: self.path.clone(), source }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LogLines<R> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        self.buffer.clear();
+        match self.reader.read_until(b'\n', &mut self.buffer) {
+            Ok(0) => None,
+            Ok(_) => {
+                if self.buffer.last() == Some(&b'\n') {
+                    self.buffer.pop();
+                }
+                if self.buffer.last() == Some(&b'\r') {
+                    self.buffer.pop();
+                }
+                Some(String::from_utf8_lossy(&self.buffer).into_owned())
+            }
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::log_lines::LogLines;
+
+    #[test]
+    fn split_lines_test() {
+        let log = "first\nsecond\r\n\nlast";
+        let lines: Vec<String> = LogLines::new(log.as_bytes(), "log").collect();
+        assert_eq!(lines, vec!["first", "second", "", "last"]);
+    }
+
+    #[test]
+    fn invalid_utf8_test() {
+        let log: &[u8] = b"+ e150202c \xff14\nnext";
+        let mut log_lines = LogLines::new(log, "log");
+        assert_eq!(log_lines.next().unwrap(), "+ e150202c \u{FFFD}14");
+        assert_eq!(log_lines.next().unwrap(), "next");
+        assert!(log_lines.next().is_none());
+        assert!(log_lines.check_error().is_ok());
+    }
+}
@@ -0,0 +1,126 @@
+//! Opens logs for reading, decompressing gzip and zstd logs on the fly. Compression is detected
+//! from the first bytes of the file rather than its extension, so archived traces can be opened
+//! directly whatever they are named.
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+
+use flate2::bufread::MultiGzDecoder;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression of a log file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression of a file from its first bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The first bytes of the file.
+    ///
+    /// returns: Compression
+    pub fn detect(bytes: &[u8]) -> Compression {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+pub struct LogReader {}
+
+impl LogReader {
+    /// Opens a log, decompressing it as it is read if it is compressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: Path to the log.
+    ///
+    /// returns: Reader over the decompressed log, or an error if it cannot be opened
+    pub fn open(log_path: &str) -> Result<Box<dyn BufRead>, DamselflyError> {
+        let io_error = |source| DamselflyError::Io { path: log_path.to_string(), source };
+        let file = File::open(log_path).map_err(io_error)?;
+        Self::wrap(BufReader::new(file)).map_err(io_error)
+    }
+
+    /// Reads a whole log into a String, decompressing it if it is compressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: Path to the log.
+    ///
+    /// returns: The decompressed log, or an error if it cannot be read
+    pub fn read_to_string(log_path: &str) -> Result<String, DamselflyError> {
+        let mut log = String::new();
+        Self::open(log_path)?.read_to_string(&mut log)
+            .map_err(|source| DamselflyError::Io { path: log_path.to_string(), source })?;
+        Ok(log)
+    }
+
+    /// Wraps a reader in a decompressor matching its first bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader`: Reader over a possibly compressed log.
+    ///
+    /// returns: Reader over the decompressed log
+    pub fn wrap<R: BufRead + 'static>(mut reader: R) -> std::io::Result<Box<dyn BufRead>> {
+        match Compression::detect(reader.fill_buf()?) {
+            // Multi-member, so that logs compressed in chunks (e.g. by log rotation) are read in full
+            Compression::Gzip => Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader)))),
+            Compression::Zstd => Ok(Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?))),
+            Compression::None => Ok(Box::new(reader)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+    use flate2::write::GzEncoder;
+    use crate::damselfly::memory::log_reader::{Compression, LogReader};
+
+    const TEST_LOG: &str = "+ 0x1c9f460 0x64\n- 0x1c9f460\n";
+
+    fn read_all(bytes: Vec<u8>) -> String {
+        let mut log = String::new();
+        LogReader::wrap(Cursor::new(bytes)).unwrap().read_to_string(&mut log).unwrap();
+        log
+    }
+
+    #[test]
+    fn detect_test() {
+        assert_eq!(Compression::detect(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+        assert_eq!(Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]), Compression::Zstd);
+        assert_eq!(Compression::detect(TEST_LOG.as_bytes()), Compression::None);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+    }
+
+    #[test]
+    fn decompress_test() {
+        assert_eq!(read_all(TEST_LOG.as_bytes().to_vec()), TEST_LOG);
+
+        // Two gzip members, as produced by appending to a compressed log
+        let mut gzip = Vec::new();
+        for half in [&TEST_LOG[..17], &TEST_LOG[17..]] {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(half.as_bytes()).unwrap();
+            gzip.extend(encoder.finish().unwrap());
+        }
+        assert_eq!(read_all(gzip), TEST_LOG);
+
+        let zstd = zstd::encode_all(TEST_LOG.as_bytes(), 0).unwrap();
+        assert_eq!(read_all(zstd), TEST_LOG);
+    }
+}
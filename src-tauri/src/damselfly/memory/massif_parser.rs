@@ -0,0 +1,460 @@
+//! Parser for the massif.out.* files written by Valgrind's Massif heap profiler.
+//!
+//! Massif records snapshots of the total heap usage, and for some snapshots a tree of the
+//! allocation sites responsible for it:
+//!
+//! snapshot=3
+//! time=1200
+//! mem_heap_B=1000
+//! heap_tree=detailed
+//! n2: 1000 (heap allocation functions) malloc/new/new[], --alloc-fns, etc.
+//!  n0: 600 0x4005B6: make_table (table.c:12)
+//!  n0: 400 in 3 places, below massif's threshold (1.00%)
+//!
+//! Massif does not record individual allocations, so they are synthesised: between snapshots,
+//! each allocation site's blocks are allocated, shrunk (by reallocating in place) or freed so that
+//! the bytes live at each site match the snapshot. The addresses of these blocks are made up, so
+//! the memory map shows how usage is split between sites rather than where it really was.
+//!
+//! Snapshots without a tree keep the sites of the previous snapshot. Growth in usage is attributed
+//! to an unknown site, and shrinkage is taken from the largest sites first.
+//! Snapshot times are in the file's time unit. Milliseconds become realtime timestamps, while
+//! instructions and bytes are used as if they were microseconds.
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::sync::Arc;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::log_lines::LogLines;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::memory_parsers::{LenientParser, MemoryParser, ParseResults};
+use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+
+/// Alignment of the synthesised blocks.
+pub const BLOCK_ALIGNMENT: usize = 16;
+/// Site that growth in usage is attributed to when a snapshot has no tree.
+const UNKNOWN_SITE: &str = "[allocation site unknown: snapshot has no heap tree]\n";
+
+/// A snapshot that is being parsed.
+#[derive(Default)]
+struct Snapshot {
+    time: u64,
+    heap_bytes: usize,
+    // Callstack -> live bytes, if the snapshot has a tree
+    sites: Option<BTreeMap<String, usize>>,
+}
+
+/// Parser for Massif output files.
+#[derive(Default)]
+pub struct MemoryMassifParser {
+    time: usize,
+    time_unit: String,
+    next_address: usize,
+    // Callstack -> live blocks (address, size) at that site, oldest first
+    live_blocks: BTreeMap<String, Vec<(usize, usize)>>,
+    snapshot: Option<Snapshot>,
+    // Frames from the root of the heap tree to the node being parsed
+    tree_path: Vec<String>,
+    line_number: usize,
+    lenient: bool,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl MemoryParser for MemoryMassifParser {
+    /// Parses a Massif output file into a Vec of MemoryUpdateTypes.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw output file.
+    /// * `_binary_path`: Unused, as Massif has already symbolised the allocation sites.
+    ///
+    /// returns: ParseResults
+    fn parse_log_directly(self, log: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_log_contents(LogLines::new(log.as_bytes(), "[log]"))
+    }
+
+    /// Parses a Massif output file using its file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: File path to the output file.
+    /// * `_binary_path`: Unused, as Massif has already symbolised the allocation sites.
+    ///
+    /// returns: ParseResults
+    fn parse_log(self, log_path: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemoryMassifParser::parse_log]: log: {log_path}");
+        self.parse_log_contents(LogLines::new(LogReader::open(log_path)?, log_path))
+    }
+}
+
+impl LenientParser for MemoryMassifParser {
+    fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>> {
+        self.lenient.then_some(&mut self.diagnostics)
+    }
+}
+
+impl MemoryMassifParser {
+    pub fn new() -> MemoryMassifParser {
+        MemoryMassifParser::default()
+    }
+
+    /// Constructs a parser that skips malformed lines instead of failing. Each skipped line is
+    /// reported in the diagnostics of the ParseResults.
+    pub fn new_lenient() -> MemoryMassifParser {
+        MemoryMassifParser {
+            lenient: true,
+            ..Self::new()
+        }
+    }
+
+    /// Parses a whole output file, consuming itself and returning parse results.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the output file.
+    ///
+    /// returns: ParseResults
+    fn parse_log_contents<R: BufRead>(mut self, mut log_lines: LogLines<R>) -> Result<ParseResults, DamselflyError> {
+        let memory_updates = self.parse_memory_updates(log_lines.by_ref())?;
+        log_lines.check_error()?;
+        Ok(ParseResults::from_parsed_log(memory_updates, MemoryPoolList::default(), self.diagnostics))
+    }
+
+    /// Synthesises the memory operations that take the heap from each snapshot to the next.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the output file.
+    ///
+    /// returns: The synthesised memory operations, in order.
+    pub fn parse_memory_updates<S: AsRef<str>>(&mut self, log_lines: impl Iterator<Item = S>) -> Result<Vec<MemoryUpdateType>, DamselflyError> {
+        let mut memory_updates = Vec::new();
+        for line in log_lines {
+            let line = line.as_ref();
+            self.line_number += 1;
+            if let Err(error) = self.parse_line(line, &mut memory_updates) {
+                self.skip_or_fail(line, error)?;
+            }
+        }
+        if let Some(snapshot) = self.snapshot.take() {
+            self.apply_snapshot(snapshot, &mut memory_updates);
+        }
+        Ok(memory_updates)
+    }
+
+    /// Parses a line of the output file. Finishing a snapshot adds the operations that reach it
+    /// to memory_updates.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the output file.
+    /// * `memory_updates`: Memory operations synthesised so far.
+    ///
+    /// returns: nothing, or an error if the line is malformed.
+    fn parse_line(&mut self, line: &str, memory_updates: &mut Vec<MemoryUpdateType>) -> Result<(), DamselflyError> {
+        let trimmed_line = line.trim();
+        if trimmed_line.is_empty() || trimmed_line.starts_with('#')
+            || trimmed_line.starts_with("desc:") || trimmed_line.starts_with("cmd:") {
+            return Ok(());
+        }
+        if let Some(time_unit) = trimmed_line.strip_prefix("time_unit:") {
+            self.time_unit = time_unit.trim().to_string();
+            return Ok(());
+        }
+        if trimmed_line.starts_with("snapshot=") {
+            if let Some(snapshot) = self.snapshot.replace(Snapshot::default()) {
+                self.apply_snapshot(snapshot, memory_updates);
+            }
+            return Ok(());
+        }
+
+        let line_number = self.line_number;
+        let snapshot = self.snapshot.as_mut()
+            .ok_or_else(|| DamselflyError::MalformedRecord { line: line_number, reason: "line is not in a snapshot".to_string() })?;
+        if let Some((key, value)) = trimmed_line.split_once('=') {
+            match key {
+                "time" => snapshot.time = value.parse()
+                    .map_err(|_| DamselflyError::BadTimestamp { line: line_number, text: value.to_string() })?,
+                "mem_heap_B" => snapshot.heap_bytes = value.parse()
+                    .map_err(|_| DamselflyError::BadSize { line: line_number, text: value.to_string() })?,
+                // Allocator overhead and stacks are not heap blocks
+                "mem_heap_extra_B" | "mem_stacks_B" => {},
+                "heap_tree" => {
+                    snapshot.sites = (value != "empty").then(BTreeMap::new);
+                    self.tree_path.clear();
+                }
+                unknown => return Err(self.malformed_record(&format!("unknown snapshot field {unknown}"))),
+            }
+            return Ok(());
+        }
+        self.parse_tree_node(line)
+    }
+
+    /// Parses a node of a snapshot's heap tree, e.g. "  n0: 600 0x4005B6: make_table (table.c:12)".
+    /// The depth of the node is given by its indentation. Leaf nodes are the allocation sites.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the output file.
+    ///
+    /// returns: nothing, or an error if the node is malformed.
+    fn parse_tree_node(&mut self, line: &str) -> Result<(), DamselflyError> {
+        let line_number = self.line_number;
+        let depth = line.len() - line.trim_start().len();
+        let (child_count, node) = line.trim()
+            .strip_prefix('n')
+            .and_then(|node| node.split_once(':'))
+            .ok_or_else(|| self.malformed_record("unrecognised line"))?;
+        let child_count: usize = child_count.parse()
+            .map_err(|_| self.malformed_record(&format!("invalid child count {child_count}")))?;
+        let (bytes, frame) = node.trim().split_once(' ').unwrap_or((node.trim(), ""));
+        let bytes: usize = bytes.parse()
+            .map_err(|_| DamselflyError::BadSize { line: line_number, text: bytes.to_string() })?;
+        if depth > self.tree_path.len() {
+            return Err(self.malformed_record("heap tree node is not a child of the previous node"));
+        }
+
+        self.tree_path.truncate(depth);
+        self.tree_path.push(Self::strip_frame_address(frame).to_string());
+        if child_count > 0 || bytes == 0 {
+            return Ok(());
+        }
+        // The root is the allocation function itself, so the callstack starts at its caller
+        let callstack = match &self.tree_path[1..] {
+            [] => format!("{}\n", self.tree_path[0]),
+            frames => frames.iter().map(|frame| format!("{frame}\n")).collect(),
+        };
+        let sites = self.snapshot.as_mut()
+            .and_then(|snapshot| snapshot.sites.as_mut())
+            .ok_or_else(|| DamselflyError::MalformedRecord { line: line_number, reason: "heap tree node in a snapshot without a heap tree".to_string() })?;
+        *sites.entry(callstack).or_insert(0) += bytes;
+        Ok(())
+    }
+
+    /// Removes the address from a Valgrind frame, e.g. "0x4005B6: main (m.c:5)" becomes
+    /// "main (m.c:5)". Frames without an address are returned unchanged.
+    pub fn strip_frame_address(frame: &str) -> &str {
+        match frame.split_once(": ") {
+            Some((address, rest)) if address.starts_with("0x") => rest,
+            _ => frame,
+        }
+    }
+
+    /// Synthesises the memory operations that take each site from its live bytes in the previous
+    /// snapshot to its live bytes in this snapshot. Frees and shrinks come before allocations.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot`: The snapshot that has just been parsed.
+    /// * `memory_updates`: Memory operations synthesised so far.
+    ///
+    /// returns: ()
+    fn apply_snapshot(&mut self, snapshot: Snapshot, memory_updates: &mut Vec<MemoryUpdateType>) {
+        let current_sites: BTreeMap<String, usize> = self.live_blocks.iter()
+            .map(|(callstack, blocks)| (callstack.clone(), blocks.iter().map(|(_, size)| size).sum()))
+            .collect();
+        let target_sites = match snapshot.sites {
+            Some(sites) => sites,
+            None => Self::carry_sites_forward(current_sites.clone(), snapshot.heap_bytes),
+        };
+        let real_timestamp = match self.time_unit.as_str() {
+            "ms" => format!("{} ms", snapshot.time),
+            _ => format!("{} us", snapshot.time),
+        };
+
+        for (callstack, &current_bytes) in &current_sites {
+            let target_bytes = target_sites.get(callstack).copied().unwrap_or(0);
+            if target_bytes < current_bytes {
+                self.shrink_site(callstack, current_bytes - target_bytes, &real_timestamp, memory_updates);
+            }
+        }
+        for (callstack, &target_bytes) in &target_sites {
+            let current_bytes = current_sites.get(callstack).copied().unwrap_or(0);
+            if target_bytes > current_bytes {
+                self.grow_site(callstack, target_bytes - current_bytes, &real_timestamp, memory_updates);
+            }
+        }
+        self.live_blocks.retain(|_, blocks| !blocks.is_empty());
+    }
+
+    /// Estimates the sites of a snapshot without a tree from those of the previous snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `sites`: Live bytes of each site in the previous snapshot.
+    /// * `heap_bytes`: Total live bytes in the snapshot.
+    ///
+    /// returns: Live bytes of each site, adding up to heap_bytes.
+    fn carry_sites_forward(mut sites: BTreeMap<String, usize>, heap_bytes: usize) -> BTreeMap<String, usize> {
+        sites.remove(UNKNOWN_SITE);
+        let known_bytes: usize = sites.values().sum();
+        if heap_bytes >= known_bytes {
+            if heap_bytes > known_bytes {
+                sites.insert(UNKNOWN_SITE.to_string(), heap_bytes - known_bytes);
+            }
+            return sites;
+        }
+
+        let mut excess = known_bytes - heap_bytes;
+        let mut largest_sites: Vec<(String, usize)> = sites.clone().into_iter().collect();
+        largest_sites.sort_by(|(_, prev), (_, next)| next.cmp(prev));
+        for (callstack, bytes) in largest_sites {
+            if excess == 0 {
+                break;
+            }
+            let removed = bytes.min(excess);
+            sites.insert(callstack, bytes - removed);
+            excess -= removed;
+        }
+        sites
+    }
+
+    /// Allocates a new block at a site.
+    fn grow_site(&mut self, callstack: &str, bytes: usize, real_timestamp: &str, memory_updates: &mut Vec<MemoryUpdateType>) {
+        let address = self.next_address;
+        self.next_address += bytes.next_multiple_of(BLOCK_ALIGNMENT);
+        self.live_blocks.entry(callstack.to_string()).or_default().push((address, bytes));
+        memory_updates.push(
+            Allocation::new(address, bytes, Arc::new(callstack.to_string()), self.time, real_timestamp.to_string()).wrap_in_enum()
+        );
+        self.time += 1;
+    }
+
+    /// Frees the newest blocks at a site, shrinking the last one in place if only part of it needs
+    /// to be released.
+    fn shrink_site(&mut self, callstack: &str, mut bytes: usize, real_timestamp: &str, memory_updates: &mut Vec<MemoryUpdateType>) {
+        let blocks = self.live_blocks.get_mut(callstack)
+            .expect("[MemoryMassifParser::shrink_site]: Site has no live blocks");
+        while bytes > 0 {
+            let Some((address, size)) = blocks.pop() else {
+                break;
+            };
+            let callstack = Arc::new(callstack.to_string());
+            if size <= bytes {
+                memory_updates.push(Free::new(address, size, callstack, self.time, real_timestamp.to_string()).wrap_in_enum());
+                bytes -= size;
+            } else {
+                memory_updates.push(
+                    Reallocation::new(address, size - bytes, address, size, callstack, self.time, real_timestamp.to_string()).wrap_in_enum()
+                );
+                blocks.push((address, size - bytes));
+                bytes = 0;
+            }
+            self.time += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::massif_parser::{MemoryMassifParser, UNKNOWN_SITE};
+    use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
+
+    const TEST_LOG: &str = "desc: (none)
+cmd: ./prog
+time_unit: ms
+#-----------
+snapshot=0
+#-----------
+time=0
+mem_heap_B=0
+mem_heap_extra_B=0
+mem_stacks_B=0
+heap_tree=empty
+#-----------
+snapshot=1
+#-----------
+time=10
+mem_heap_B=1000
+mem_heap_extra_B=24
+mem_stacks_B=0
+heap_tree=detailed
+n2: 1000 (heap allocation functions) malloc/new/new[], --alloc-fns, etc.
+ n1: 600 0x4005B6: make_table (table.c:12)
+  n0: 600 0x4005F0: main (main.c:5)
+ n0: 400 in 3 places, below massif's threshold (1.00%)
+#-----------
+snapshot=2
+#-----------
+time=20
+mem_heap_B=500
+mem_heap_extra_B=24
+mem_stacks_B=0
+heap_tree=detailed
+n1: 500 (heap allocation functions) malloc/new/new[], --alloc-fns, etc.
+ n1: 500 0x4005B6: make_table (table.c:12)
+  n0: 500 0x4005F0: main (main.c:5)
+#-----------
+snapshot=3
+#-----------
+time=30
+mem_heap_B=700
+mem_heap_extra_B=24
+mem_stacks_B=0
+heap_tree=empty
+";
+
+    #[test]
+    fn parse_memory_updates_test() {
+        let mut parser = MemoryMassifParser::new();
+        let updates = parser.parse_memory_updates(TEST_LOG.lines()).unwrap();
+        assert_eq!(updates.len(), 5);
+
+        // Snapshot 1: both sites allocate, in order of callstack
+        assert!(matches!(updates[0], MemoryUpdateType::Allocation(_)));
+        assert_eq!(*updates[0].get_callstack(), "in 3 places, below massif's threshold (1.00%)\n");
+        assert_eq!(updates[0].get_absolute_size(), 400);
+        assert_eq!(*updates[0].get_real_timestamp(), "10 ms");
+        assert_eq!(*updates[1].get_callstack(), "make_table (table.c:12)\nmain (main.c:5)\n");
+        assert_eq!(updates[1].get_absolute_address(), 400);
+
+        // Snapshot 2: one site shrinks and the other is freed
+        assert_eq!(*updates[2].get_callstack(), "in 3 places, below massif's threshold (1.00%)\n");
+        assert!(matches!(updates[2], MemoryUpdateType::Free(_)));
+        match &updates[3] {
+            MemoryUpdateType::Reallocation(reallocation) => {
+                assert_eq!(reallocation.get_absolute_address(), 400);
+                assert_eq!(reallocation.get_absolute_size(), 500);
+                assert_eq!(reallocation.get_old_size(), 600);
+            }
+            _ => panic!("Expected a reallocation"),
+        }
+
+        // Snapshot 3 has no tree, so its growth is unattributed
+        assert_eq!(*updates[4].get_callstack(), UNKNOWN_SITE);
+        assert_eq!(updates[4].get_absolute_size(), 200);
+        assert_eq!(updates[4].get_timestamp(), 4);
+    }
+
+    #[test]
+    fn carry_sites_forward_test() {
+        let sites = [(String::from("a\n"), 100), (String::from("b\n"), 300), (String::from(UNKNOWN_SITE), 50)]
+            .into_iter()
+            .collect();
+        let sites = MemoryMassifParser::carry_sites_forward(sites, 250);
+        assert_eq!(sites.get("a\n"), Some(&100));
+        assert_eq!(sites.get("b\n"), Some(&150));
+        assert_eq!(sites.get(UNKNOWN_SITE), None);
+    }
+
+    #[test]
+    fn strip_frame_address_test() {
+        assert_eq!(MemoryMassifParser::strip_frame_address("0x4005B6: main (m.c:5)"), "main (m.c:5)");
+        assert_eq!(MemoryMassifParser::strip_frame_address("in 1 place, below massif's threshold (1.00%)"),
+                   "in 1 place, below massif's threshold (1.00%)");
+    }
+
+    #[test]
+    fn malformed_line_test() {
+        let log = "snapshot=0\ntime=0\nmem_heap_B=ten\n";
+        let result = MemoryMassifParser::new().parse_memory_updates(log.lines());
+        assert!(matches!(result, Err(DamselflyError::BadSize { line: 3, .. })));
+    }
+}
@@ -2,27 +2,45 @@
 //! To implement your own parser (for other log formats), write a struct that implements MemoryParser.
 //! Then, in main.rs, change initialise_viewer to accept your struct instead.
 //! MemorySysTraceParser parses logfiles generated by SysTraceParser, and is the default parsing struct.
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::Read;
-use std::iter::Peekable;
-use std::str::{FromStr, Split};
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use addr2line::Context;
-use owo_colors::OwoColorize;
+use object::{Object, ObjectSegment, ReadCache};
 
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::binary_module::BinaryModule;
+use crate::damselfly::memory::log_lines::LogLines;
+use crate::damselfly::memory::log_reader::LogReader;
 use crate::damselfly::memory::memory_pool::MemoryPool;
 use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
-use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType};
+use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind, PoolLifetime};
+use crate::damselfly::memory::symbol_cache::SymbolCache;
+use crate::damselfly::memory::symbol_frame::SymbolFrame;
+use crate::damselfly::memory::symbol_options::SymbolOptions;
+use crate::damselfly::memory::symbol_table::SymbolTable;
+use crate::damselfly::memory::systrace_layout::SysTraceLayout;
+use crate::damselfly::memory::cycle_counter_clock::{CycleCounterClock, CycleCounterReading};
+use crate::damselfly::memory::utility::Utility;
+
+/// A binary parsed by MemorySysTraceParser::parse_binary, whose sections are read lazily.
+pub type BinaryObject<'data> = object::File<'data, &'data ReadCache<File>>;
 
 /// Raw text in logs are parsed into one of the following.
 #[derive(Clone)]
 pub enum RecordType {
-    // (address, size, callstack, real_timestamp)
-    Allocation(usize, usize, String, String),
-    // (address, callstack, real_timestamp)
-    Free(usize, String, String),
+    // (address, size, callstack, real_timestamp, thread_id, cycle_counter)
+    Allocation(usize, usize, String, String, Option<usize>, Option<CycleCounterReading>),
+    // (address, callstack, real_timestamp, thread_id, cycle_counter)
+    Free(usize, String, String, Option<usize>, Option<CycleCounterReading>),
+    // (old_address, address, size, callstack, real_timestamp, thread_id, cycle_counter)
+    Reallocation(usize, usize, usize, String, String, Option<usize>, Option<CycleCounterReading>),
     // (address, callstack)
     StackTrace(usize, String),
     // (address, size)
@@ -33,9 +51,66 @@ pub enum RecordType {
 
 /// Required methods for a MemoryParser.
 pub trait MemoryParser {
-    fn parse_log_directly(self, log: &str, binary_path: &str) -> ParseResults;
-    fn parse_log(self, log_path: &str, binary_path: &str) -> ParseResults;
-    fn parse_log_contents_split_by_pools(self, log: &str, binary_path: &str, left_padding: usize, right_padding: usize) -> Vec<PoolRestrictedParseResults>;
+    fn parse_log_directly(self, log: &str, binary_path: &str) -> Result<ParseResults, DamselflyError>;
+    fn parse_log(self, log_path: &str, binary_path: &str) -> Result<ParseResults, DamselflyError>;
+
+    /// Parses a log using its file path and splits its memory operations into pools.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: File path to the log.
+    /// * `binary_path`: File path to the binary for debuginfo, if the format needs one.
+    /// * `left_padding`: Padding to add to the left of each operation (by shifting its address left)
+    /// * `right_padding`: Padding to add to the right of each operation (by increasing its size)
+    ///
+    /// returns: Vec<PoolRestrictedParseResults, Global>
+    fn parse_log_contents_split_by_pools(self, log: &str, binary_path: &str, left_padding: usize, right_padding: usize) -> Result<Vec<PoolRestrictedParseResults>, DamselflyError>
+    where
+        Self: Sized,
+    {
+        self.parse_log(log, binary_path)?.split_by_pools(left_padding, right_padding)
+    }
+}
+
+/// Line counting and lenient mode, shared by the parsers of line-based logs.
+pub trait LenientParser {
+    /// Gets the number of the line being parsed, starting from 1.
+    fn get_line_number(&self) -> usize;
+
+    /// Gets the diagnostics that skipped records are reported in, or None if the parser is not
+    /// lenient and malformed records are errors.
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>>;
+
+    /// In lenient mode, records the error as a diagnostic so that parsing can continue.
+    /// Otherwise, returns the error.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw text of the offending line.
+    /// * `error`: Why the line could not be parsed.
+    ///
+    /// returns: () if the line was skipped
+    fn skip_or_fail(&mut self, line: &str, error: DamselflyError) -> Result<(), DamselflyError> {
+        let line_number = error.line().unwrap_or(self.get_line_number());
+        match self.get_lenient_diagnostics() {
+            Some(diagnostics) => {
+                diagnostics.push(ParseDiagnostic::new(line_number, line.to_string(), error.reason()));
+                Ok(())
+            }
+            None => Err(error),
+        }
+    }
+
+    /// Creates a MalformedRecord error for the line currently being parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason`: Why the record is malformed.
+    ///
+    /// returns: DamselflyError
+    fn malformed_record(&self, reason: &str) -> DamselflyError {
+        DamselflyError::MalformedRecord { line: self.get_line_number(), reason: reason.to_string() }
+    }
 }
 
 /// Parser for SysTraceParser logs.
@@ -43,12 +118,17 @@ pub trait MemoryParser {
 pub struct MemorySysTraceParser {
     time: usize,
     record_queue: Vec<RecordType>,
-    memory_updates: Vec<MemoryUpdateType>,
+    allocation_sizes: HashMap<usize, usize>,
     potential_pool: MemoryPool,
     pool_list: MemoryPoolList,
     symbols: HashMap<usize, String>,
-    prefix: String,
     counter: u64,
+    line_number: usize,
+    lenient: bool,
+    diagnostics: Vec<ParseDiagnostic>,
+    layout: SysTraceLayout,
+    cycle_counter_clock: Option<CycleCounterClock>,
+    symbol_options: SymbolOptions,
 }
 
 /// MemoryParsers should return this: memory operations sorted into pools along with the max timestamp.
@@ -56,6 +136,9 @@ pub struct PoolRestrictedParseResults {
     pub memory_updates: Vec<MemoryUpdateType>,
     pub max_timestamp: u64,
     pub pool: MemoryPool,
+    /// When the pool is created, resized and deleted, with positions counting only the operations
+    /// in this pool. None if the pool exists for the whole trace.
+    pub pool_lifetime: Option<PoolLifetime>,
 }
 
 impl PoolRestrictedParseResults {
@@ -72,16 +155,35 @@ impl PoolRestrictedParseResults {
         Self {
             memory_updates,
             max_timestamp,
-            pool
+            pool,
+            pool_lifetime: None,
         }
     }
+
+    /// Sets when the pool is created, resized and deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_lifetime`: Lifetime of the pool, with positions counting only the operations in
+    ///   memory_updates.
+    ///
+    /// returns: PoolRestrictedParseResults
+    pub fn with_pool_lifetime(mut self, pool_lifetime: Option<PoolLifetime>) -> Self {
+        self.pool_lifetime = pool_lifetime;
+        self
+    }
 }
 
 /// Struct for returning memory updates without sorting them into pools, but instead providing them separately.
+/// Records skipped by a lenient parser are listed in diagnostics.
 pub struct ParseResults {
     pub memory_updates: Vec<MemoryUpdateType>,
     pub max_timestamp: u64,
     pub pool_list: MemoryPoolList,
+    pub diagnostics: Vec<ParseDiagnostic>,
+    /// Name of the pool each memory operation belongs to, for logs whose pools overlap in memory.
+    /// If empty, operations are sorted into pools by address.
+    pub update_pools: Vec<Arc<String>>,
 }
 
 impl ParseResults {
@@ -92,80 +194,170 @@ impl ParseResults {
     /// * `memory_updates`: Vec of memory operations.
     /// * `pool_list`: List of pools.
     /// * `max_timestamp`: Max timestamp across all pools.
+    /// * `diagnostics`: Records that were skipped while parsing.
     /// 
     /// returns: ParseResults 
-    pub fn new(memory_updates: Vec<MemoryUpdateType>, pool_list: MemoryPoolList, max_timestamp: u64) -> Self {
+    pub fn new(memory_updates: Vec<MemoryUpdateType>, pool_list: MemoryPoolList, max_timestamp: u64, diagnostics: Vec<ParseDiagnostic>) -> Self {
         Self {
             memory_updates,
             pool_list,
             max_timestamp,
+            diagnostics,
+            update_pools: Vec::new(),
         }
     }
-}
 
-impl MemoryParser for MemorySysTraceParser {
-    /// Parses a log file into a Vec of MemoryUpdateTypes, each containing an Allocation or a Free.
+    /// Constructor for a log that has been read to the end. Reports that parsing is complete and
+    /// how many records were skipped, and takes the max timestamp to be the number of memory
+    /// operations. Use with_max_timestamp if the log counts time differently.
     ///
     /// # Arguments
     ///
-    /// * `log`: Raw log file.
-    /// * `binary_path`: File path to threadxApp binary for debuginfo.
+    /// * `memory_updates`: Vec of memory operations.
+    /// * `pool_list`: List of pools.
+    /// * `diagnostics`: Records that were skipped while parsing.
     ///
-    /// returns: ParseResults 
-    fn parse_log_directly(self, log: &str, binary_path: &str) -> ParseResults {
-        self.parse_log_contents(log, binary_path)
+    /// returns: ParseResults
+    pub fn from_parsed_log(memory_updates: Vec<MemoryUpdateType>, pool_list: MemoryPoolList, diagnostics: Vec<ParseDiagnostic>) -> Self {
+        eprintln!("Processing complete.");
+        if !diagnostics.is_empty() {
+            eprintln!("Skipped {} malformed records.", diagnostics.len());
+        }
+        let max_timestamp = memory_updates.len() as u64;
+        Self::new(memory_updates, pool_list, max_timestamp, diagnostics)
     }
 
-    /// Parses a log using its file path.
-    /// 
-    /// # Arguments 
-    /// 
-    /// * `log_path`: File path to log
-    /// * `binary_path`: File path to threadxApp binary for debuginfo.
-    /// 
-    /// returns: ParseResults 
-    fn parse_log(self, log_path: &str, binary_path: &str) -> ParseResults {
-        eprintln!("[MemorySysTraceParser::parse_log]: log: {log_path} binary: {binary_path}");
-        let log = std::fs::read_to_string(log_path).unwrap();
-        self.parse_log_contents(log.as_str(), binary_path)
+    /// Sets the max timestamp across all pools.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_timestamp`: Max timestamp across all pools.
+    ///
+    /// returns: ParseResults
+    pub fn with_max_timestamp(mut self, max_timestamp: u64) -> Self {
+        self.max_timestamp = max_timestamp;
+        self
+    }
+
+    /// Sorts memory operations into pools by name instead of by address. Use this when pools
+    /// overlap in memory, e.g. slab caches that share pages of the same address range.
+    ///
+    /// # Arguments
+    ///
+    /// * `update_pools`: Name of the pool of each memory operation, in the same order as
+    ///   memory_updates. Every name must be in the pool list.
+    ///
+    /// returns: ParseResults, or InconsistentPools if a memory operation has no pool or its pool
+    /// is not in the pool list
+    pub fn with_update_pools(mut self, update_pools: Vec<Arc<String>>) -> Result<Self, DamselflyError> {
+        if update_pools.len() != self.memory_updates.len() {
+            return Err(DamselflyError::InconsistentPools {
+                reason: format!("{} memory operations, but {} pool names", self.memory_updates.len(), update_pools.len()),
+            });
+        }
+        let pool_names: HashSet<&str> = self.pool_list.get_pools().iter().map(|pool| pool.get_name()).collect();
+        if let Some(pool) = update_pools.iter().find(|pool| !pool_names.contains(pool.as_str())) {
+            return Err(DamselflyError::InconsistentPools { reason: format!("pool {pool} is not in the pool list") });
+        }
+        self.update_pools = update_pools;
+        Ok(self)
     }
 
-    /// Parses a log file and splits its memory operations into pools.
+    /// Splits memory operations into pools. If the log did not define any pools, a default pool
+    /// spanning every operation is used. Pools are ordered by start address, then by name.
     /// 
     /// # Arguments 
     /// 
-    /// * `log`: Raw log file.
-    /// * `binary_path`: File path to threadxApp binary for debuginfo.
     /// * `left_padding`: Padding to add to the left of each operation (by shifting its address left)
     /// * `right_padding`: Padding to add to the right of each operation (by increasing its size)
     /// 
     /// returns: Vec<PoolRestrictedParseResults, Global> 
-    fn parse_log_contents_split_by_pools(self, log: &str, binary_path: &str, left_padding: usize, right_padding: usize) -> Vec<PoolRestrictedParseResults> {
-        let mut parse_results = self.parse_log(log, binary_path);
-        if parse_results.pool_list.get_pools().is_empty() {
-            let span = Self::get_updates_span(&parse_results.memory_updates);
-            parse_results.pool_list.add_pool(MemoryPool::new(span.0 as usize, (span.1 - span.0) as usize, "_default pool".to_string()));
+    pub fn split_by_pools(mut self, left_padding: usize, right_padding: usize) -> Result<Vec<PoolRestrictedParseResults>, DamselflyError> {
+        if self.pool_list.get_pools().is_empty() {
+            let span = MemorySysTraceParser::get_updates_span(&self.memory_updates)?;
+            self.pool_list.add_pool(MemoryPool::new(span.0 as usize, (span.1 - span.0) as usize, "_default pool".to_string()));
         }
 
         let mut pool_restricted_parse_results = Vec::new();
-        let shifted_pools: Vec<MemoryPool> = parse_results.pool_list.get_pools()
+        let mut shifted_pools: Vec<MemoryPool> = self.pool_list.get_pools()
             .iter().cloned()
             .map(|mut pool| {
-                pool.set_start(pool.get_start() - left_padding);
+                pool.set_start(pool.get_start().saturating_sub(left_padding));
                 pool.set_size(pool.get_size() + right_padding);
                 pool
             })
             .collect();
+        // The pool list is unordered, so sort the pools to show them in the same order every time
+        shifted_pools.sort_by(|pool, other_pool| pool.cmp(other_pool).then_with(|| pool.get_name().cmp(other_pool.get_name())));
         for pool in shifted_pools {
-            let updates_in_pool = parse_results.memory_updates
+            let positions_in_pool: Vec<usize> = if self.update_pools.is_empty() {
+                self.memory_updates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, update)| pool.contains(update.get_start(), update.get_end()))
+                    .map(|(position, _)| position)
+                    .collect()
+            } else {
+                self.update_pools
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, update_pool)| update_pool.as_str() == pool.get_name())
+                    .map(|(position, _)| position)
+                    .collect()
+            };
+            let updates_in_pool = positions_in_pool
                 .iter()
-                .filter(|update| pool.contains(update.get_start(), update.get_end()))
-                .cloned()
+                .map(|&position| self.memory_updates[position].clone())
                 .collect();
-            pool_restricted_parse_results.push(PoolRestrictedParseResults::new(updates_in_pool, parse_results.max_timestamp, pool.clone()));
+            let pool_lifetime = self.pool_list.get_lifetime(pool.get_name())
+                .map(|lifetime| lifetime.renumber(&positions_in_pool).pad(left_padding, right_padding));
+            pool_restricted_parse_results.push(
+                PoolRestrictedParseResults::new(updates_in_pool, self.max_timestamp, pool.clone())
+                    .with_pool_lifetime(pool_lifetime)
+            );
         }
 
-        pool_restricted_parse_results
+        Ok(pool_restricted_parse_results)
+    }
+}
+
+impl MemoryParser for MemorySysTraceParser {
+    /// Parses a log file into a Vec of MemoryUpdateTypes, each containing an Allocation or a Free.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw log file.
+    /// * `binary_path`: File path to threadxApp binary for debuginfo.
+    ///
+    /// returns: ParseResults 
+    fn parse_log_directly(self, log: &str, binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_log_contents(|| Ok(LogLines::new(log.as_bytes(), "[log]")), binary_path)
+    }
+
+    /// Parses a log using its file path.
+    /// 
+    /// # Arguments 
+    /// 
+    /// * `log_path`: File path to log
+    /// * `binary_path`: File path to threadxApp binary for debuginfo.
+    /// 
+    /// returns: ParseResults 
+    fn parse_log(self, log_path: &str, binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemorySysTraceParser::parse_log]: log: {log_path} binary: {binary_path}");
+        let open_log = || {
+            Ok(LogLines::new(LogReader::open(log_path)?, log_path))
+        };
+        self.parse_log_contents(open_log, binary_path)
+    }
+}
+
+impl LenientParser for MemorySysTraceParser {
+    fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>> {
+        self.lenient.then_some(&mut self.diagnostics)
     }
 }
 
@@ -174,42 +366,97 @@ impl MemorySysTraceParser {
         MemorySysTraceParser {
             time: 0,
             record_queue: Vec::new(),
-            memory_updates: Vec::new(),
+            allocation_sizes: HashMap::new(),
             potential_pool: MemoryPool::default(),
             pool_list: MemoryPoolList::default(),
             symbols: HashMap::new(),
-            prefix: String::new(),
             counter: 0,
+            line_number: 0,
+            lenient: false,
+            diagnostics: Vec::new(),
+            layout: SysTraceLayout::default(),
+            cycle_counter_clock: None,
+            symbol_options: SymbolOptions::default(),
+        }
+    }
+
+    /// Constructs a parser that skips malformed records instead of failing. Each skipped record is
+    /// reported in the diagnostics of the ParseResults.
+    pub fn new_lenient() -> MemorySysTraceParser {
+        MemorySysTraceParser {
+            lenient: true,
+            ..Self::new()
         }
     }
 
+    /// Sets where the fields of each line are found, for logs whose line headers differ from the
+    /// default SysTrace layout.
+    ///
+    /// # Arguments
+    ///
+    /// * `layout`: Layout of the log's lines.
+    ///
+    /// returns: MemorySysTraceParser
+    pub fn with_layout(mut self, layout: SysTraceLayout) -> MemorySysTraceParser {
+        self.cycle_counter_clock = layout.counter_frequency_hz
+            .map(|frequency_hz| CycleCounterClock::new(frequency_hz, layout.counter_bits));
+        self.layout = layout;
+        self
+    }
+
+    /// Sets where separate debuginfo files are searched for and how source paths are remapped
+    /// when symbolising stack traces.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol_options`: Debuginfo search directories and source path remappings.
+    ///
+    /// returns: MemorySysTraceParser
+    pub fn with_symbol_options(mut self, symbol_options: SymbolOptions) -> MemorySysTraceParser {
+        self.symbol_options = symbol_options;
+        self
+    }
+
     /// Parses a raw log, consuming itself and returning parse results.
+    /// The log is read twice: once to collect the addresses that need symbolising, then again to
+    /// parse memory operations. Neither pass holds the whole log in memory.
     /// 
     /// # Arguments 
     /// 
-    /// * `log`: Raw log file.
+    /// * `open_log`: Opens a new reader over the log. Called once per pass.
     /// * `binary_path`: File path to threadxApp binary for debuginfo.
     /// 
     /// returns: ParseResults 
-    fn parse_log_contents(mut self, log: &str, binary_path: &str) -> ParseResults {
-        self.parse_symbols(log, binary_path);
-        let mut log_iter = log.split('\n').peekable();
-        while let Some(line) = log_iter.peek() {
-            println!("Reading line: {}", line.cyan());
-            if self.is_line_useless_and_load_pool(line) {
-                log_iter.next();
-                continue;
-            }
-            println!("Processing valid instruction: {}", line.green());
-            let memory_update = self.process_instruction(&mut log_iter);
-            self.memory_updates.push(memory_update);
-            self.counter += 1;
-        }
-        println!("Processing complete.");
-        ParseResults::new(self.memory_updates, self.pool_list, self.counter)
+    fn parse_log_contents<R: BufRead>(
+        mut self,
+        open_log: impl Fn() -> Result<LogLines<R>, DamselflyError>,
+        binary_path: &str
+    ) -> Result<ParseResults, DamselflyError> {
+        let mut log_lines = open_log()?;
+        self.parse_symbols(log_lines.by_ref(), binary_path)?;
+        log_lines.check_error()?;
+
+        let memory_updates = self.stream_memory_updates(open_log()?)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ParseResults::from_parsed_log(memory_updates, self.pool_list, self.diagnostics)
+            .with_max_timestamp(self.counter))
     }
-    
 
+    /// Parses memory operations from a log one at a time. Symbols must already have been loaded
+    /// with parse_symbols.
+    /// 
+    /// # Arguments 
+    /// 
+    /// * `log_lines`: Lines of the log.
+    /// 
+    /// returns: An iterator over the memory operations in the log.
+    pub fn stream_memory_updates<R: BufRead>(&mut self, log_lines: LogLines<R>) -> MemoryUpdateStream<'_, R> {
+        MemoryUpdateStream {
+            parser: self,
+            log_lines,
+            finished: false,
+        }
+    }
 
     /// Computes the range of addresses spanned by a list of updates.
     /// This is used to compute the bounds of the default pool if no pools are specified in the log.
@@ -218,14 +465,14 @@ impl MemorySysTraceParser {
     /// 
     /// * `updates`: Vec of memory operations.
     /// 
-    /// returns: (pool_start: u128, pool_end: u128) 
-    pub fn get_updates_span(updates: &[MemoryUpdateType]) -> (u128, u128) {
+    /// returns: (pool_start: u128, pool_end: u128), or an error if there are no updates
+    pub fn get_updates_span(updates: &[MemoryUpdateType]) -> Result<(u128, u128), DamselflyError> {
         let lowest_address = updates
             .iter()
             .min_by(|prev, next| {
                 prev.get_absolute_address().cmp(&next.get_absolute_address())
             })
-            .expect("[MemorySysTraceParser::[get_updates_span]: Unable to find lowest address for default pool")
+            .ok_or(DamselflyError::EmptyTrace)?
             .get_start();
 
         let highest_address = updates
@@ -233,42 +480,47 @@ impl MemorySysTraceParser {
             .max_by(|prev, next| {
                 (prev.get_absolute_address() + prev.get_absolute_size()).cmp(&(next.get_absolute_address() + next.get_absolute_size()))
             })
-            .expect("[MemorySysTraceParser::[get_updates_span]: Unable to find lowest address for default pool")
+            .ok_or(DamselflyError::EmptyTrace)?
             .get_end();
 
-        (lowest_address as u128, highest_address as u128)
+        Ok((lowest_address as u128, highest_address as u128))
     }
 
     /// Checks if a line in the log contains none of the following:
     /// Allocation information
     /// Free information
+    /// Reallocation information
     /// Stacktrace information
     /// Pool information (calls load_poolbounds and load_poolname accordingly)
+    /// Pool lifecycle information (calls load_pool_event)
     ///
     /// # Arguments
     ///
     /// * `line`: the line to check
     ///
-    /// returns: true if useless, false if useful
-    pub fn is_line_useless_and_load_pool(&mut self, line: &str) -> bool {
-        let split_line = line.split('>').collect::<Vec<_>>();
-        if let Some(latter_half) = split_line.get(1) {
-            let trimmed_string = latter_half.trim();
+    /// returns: true if useless, false if useful, or an error if a pool line is malformed
+    pub fn is_line_useless_and_load_pool(&mut self, line: &str) -> Result<bool, DamselflyError> {
+        if let Some((header, payload)) = self.layout.split_line(line) {
+            let trimmed_string = payload.trim();
             if trimmed_string.starts_with("POOLBOUNDS") {
-                self.load_poolbounds(trimmed_string);
+                self.load_poolbounds(trimmed_string)?;
                 // mark pool lines as useless to avoid interfering with alloc/free parsing
-                return true;
+                return Ok(true);
             }
             if trimmed_string.starts_with("POOLNAME") {
                 // mark pool lines as useless to avoid interfering with alloc/free parsing
-                self.load_poolname(trimmed_string);
-                return true;
+                self.load_poolname(trimmed_string)?;
+                return Ok(true);
             }
-            if trimmed_string.starts_with("+ ") || trimmed_string.starts_with("- ") || trimmed_string.starts_with("^ ") {
-                return false;
+            if ["POOLCREATE", "POOLRESIZE", "POOLDELETE"].iter().any(|record| trimmed_string.starts_with(record)) {
+                self.load_pool_event(header, trimmed_string)?;
+                return Ok(true);
+            }
+            if trimmed_string.starts_with("+ ") || trimmed_string.starts_with("- ") || trimmed_string.starts_with("~ ") || trimmed_string.starts_with("^ ") {
+                return Ok(false);
             }
         }
-        true
+        Ok(true)
     }
 
     /// Loads pool bounds into a potential pool stored within the parser.
@@ -284,24 +536,23 @@ impl MemorySysTraceParser {
     /// * `line`: Raw line from the log.
     /// 
     /// returns: () 
-    fn load_poolbounds(&mut self, line: &str) {
+    fn load_poolbounds(&mut self, line: &str) -> Result<(), DamselflyError> {
         let split_line = line.split(' ').collect::<Vec<_>>();
-        if split_line.first().expect("[MemorySysTraceParser::load_poolbounds]: Line has length 0")
-            != &"POOLBOUNDS" {
-            panic!("[MemorySysTraceParser::load_poolbounds]: Attempting to load poolbounds from invalid line");
+        if split_line.first() != Some(&"POOLBOUNDS") {
+            return Err(self.malformed_record("attempting to load poolbounds from invalid line"));
         }
-        let start = usize::from_str_radix(
-            split_line
+        let start = split_line
             .get(1)
-            .expect("[MemorySysTraceParser::load_poolbounds]: Failed to split line to get start address"), 16)
-            .expect("[MemorySysTraceParser::load_poolbounds]: Failed to parse string to usize");
-        let size = usize::from_str(
-            split_line
-                .get(2)
-                .expect("[MemorySysTraceParser::load_poolbounds]: Failed to split line to get size"))
-            .expect("[MemorySysTraceParser::load_poolbounds]: Failed to parse string to usize");
+            .ok_or_else(|| self.malformed_record("POOLBOUNDS record is missing its start address"))?;
+        let start = self.parse_address(start)?;
+        let size = split_line
+            .get(2)
+            .ok_or_else(|| self.malformed_record("POOLBOUNDS record is missing its size"))?;
+        let size = usize::from_str(size)
+            .map_err(|_| DamselflyError::BadSize { line: self.line_number, text: size.to_string() })?;
         self.potential_pool.set_start(start);
         self.potential_pool.set_size(size);
+        Ok(())
     }
 
     /// Loads pool name into a potential pool stored within the parser.
@@ -317,16 +568,65 @@ impl MemorySysTraceParser {
     /// * `line`: Raw line from the log.
     /// 
     /// returns: () 
-    fn load_poolname(&mut self, line: &str) {
+    fn load_poolname(&mut self, line: &str) -> Result<(), DamselflyError> {
         let split_line = line.split(' ').collect::<Vec<_>>();
-        if split_line.first().expect("[MemorySysTraceParser::load_poolname]: Line has length 0")
-            != &"POOLNAME" {
-            panic!("[MemorySysTraceParser::load_poolname]: Attempting to load poolname from invalid line");
+        if split_line.first() != Some(&"POOLNAME") {
+            return Err(self.malformed_record("attempting to load poolname from invalid line"));
         }
-        self.potential_pool.set_name(split_line.get(1)
-            .expect("[MemorySysTraceParser::load_poolname]: Failed to split line").to_string());
+        let name = split_line
+            .get(1)
+            .ok_or_else(|| self.malformed_record("POOLNAME record is missing its name"))?;
+        self.potential_pool.set_name(name.to_string());
         self.pool_list.add_pool(self.potential_pool.clone());
         self.potential_pool = MemoryPool::default();
+        Ok(())
+    }
+
+    /// Records that a pool was created, resized or deleted. Events are placed after the memory
+    /// operations that came before them in the log, including one that has not been baked yet.
+    /// Records have the form:
+    ///
+    /// POOLCREATE name start_address(hex) size(decimal)
+    /// POOLRESIZE name size(decimal)
+    /// POOLDELETE name
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: The part of the line before the separator.
+    /// * `line`: The trimmed payload of the line.
+    ///
+    /// returns: (), or an error if the record is malformed or resizes or deletes an unknown pool
+    fn load_pool_event(&mut self, header: &str, line: &str) -> Result<(), DamselflyError> {
+        let split_line = line.split(' ').collect::<Vec<_>>();
+        let record = split_line[0];
+        let field = |index: usize, name: &str| {
+            split_line.get(index)
+                .copied()
+                .ok_or_else(|| self.malformed_record(&format!("{record} record is missing its {name}")))
+        };
+        let parse_size = |size: &str| usize::from_str(size)
+            .map_err(|_| DamselflyError::BadSize { line: self.line_number, text: size.to_string() });
+        let name = field(1, "name")?;
+        let kind = match record {
+            "POOLCREATE" => PoolEventKind::Create {
+                start: self.parse_address(field(2, "start address")?)?,
+                size: parse_size(field(3, "size")?)?,
+            },
+            "POOLRESIZE" => PoolEventKind::Resize { size: parse_size(field(2, "size")?)? },
+            "POOLDELETE" => PoolEventKind::Delete,
+            unknown => return Err(self.malformed_record(&format!("invalid pool record {unknown}"))),
+        };
+        let (timestamp, units) = self.layout.parse_timestamp(header)
+            .map_err(|reason| self.malformed_record(reason))?;
+        let event = PoolEvent {
+            kind,
+            position: self.time + usize::from(!self.record_queue.is_empty()),
+            real_timestamp: format!("{timestamp} {units}"),
+        };
+        if !self.pool_list.add_pool_event(name, event) {
+            return Err(self.malformed_record(&format!("{record} record names unknown pool {name}")));
+        }
+        Ok(())
     }
 
     /// Extracts all memory addresses from the log, ignoring lines that are deemed useless by
@@ -334,27 +634,34 @@ impl MemorySysTraceParser {
     ///
     /// # Arguments
     ///
-    /// * `log`: The entire log file.
+    /// * `log_lines`: Lines of the log.
     ///
     /// returns: A Vec of every address in the log relevant to memory tracing
-    fn extract_addresses_from_log(&mut self, log: &str) -> Vec<usize> {
+    fn extract_addresses_from_log<S: AsRef<str>>(&mut self, log_lines: impl Iterator<Item = S>) -> Result<Vec<usize>, DamselflyError> {
         let mut set = HashSet::new();
-        let log_iter = log.split('\n');
-        for line in log_iter {
-            if self.is_line_useless_and_load_pool(line) {
-                continue;
+        for (index, line) in log_lines.enumerate() {
+            let line = line.as_ref();
+            self.line_number = index + 1;
+            // In lenient mode, bad lines are reported by the main pass instead
+            match self.is_line_useless_and_load_pool(line) {
+                Ok(true) => continue,
+                Ok(false) => {},
+                Err(_) if self.lenient => continue,
+                Err(error) => return Err(error),
             }
             if line.contains('^') {
-                set.insert(Self::extract_trace_address(line));
+                match self.parse_address(&Self::extract_trace_address(line)) {
+                    Ok(address) => { set.insert(address); },
+                    Err(_) if self.lenient => continue,
+                    Err(error) => return Err(error),
+                }
             }
         }
-        set
-            .into_iter()
-            .map(|address_string| {
-                usize::from_str_radix(address_string.as_str(), 16)
-                    .expect("[MemorySysTraceParser::extract_addresses_from_log]: Failed to cast address from String to u64")
-            })
-            .collect()
+        // The main pass counts lines again from the start, and places pool events among the
+        // memory operations it parses
+        self.line_number = 0;
+        self.pool_list.clear_pool_events();
+        Ok(set.into_iter().collect())
     }
 
     /// Gets the memory address pointed to by a stacktrace line. This method does not check if the
@@ -377,38 +684,292 @@ impl MemorySysTraceParser {
         address
     }
 
-    /// Finds symbols (FILENAME:LINE_NO) of addresses in a log. Also computes the longest prefix
-    /// common to all symbols and stores it.
+    /// Finds the symbols of the addresses in a log and stores them.
     ///
     /// # Arguments
     ///
-    /// * `log`: The entire log.
+    /// * `log_lines`: Lines of the log.
     /// * `binary_path`: Path to the threadApp binary for debuginfo.
     ///
-    /// returns: nothing, as the longest prefix and symbols are stored as struct fields.
-    fn parse_symbols(&mut self, log: &str, binary_path: &str) {
-        let addresses = self.extract_addresses_from_log(log);
-        let mut file = File::open(binary_path).unwrap();
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).unwrap();
-        let object = object::File::parse(&*buffer).unwrap();
-        let ctx = Context::new(&object).unwrap();
+    /// returns: nothing, as the symbols are stored as a struct field.
+    pub fn parse_symbols<S: AsRef<str>>(&mut self, log_lines: impl Iterator<Item = S>, binary_path: &str) -> Result<(), DamselflyError> {
+        let addresses = self.extract_addresses_from_log(log_lines)?;
+        let symbols = Self::symbolise(&addresses, binary_path, &self.symbol_options)?;
+        self.symbols = addresses.into_iter().zip(symbols).collect();
+        Ok(())
+    }
+
+    /// Symbolises addresses using the debuginfo in a binary. Each frame of an address is a line of
+    /// the form `function (file:line)`, innermost inlined function first. Source files are
+    /// rewritten by the path remappings if there are any, otherwise the directories common to every
+    /// source file are trimmed, e.g. /home/work/dev/hp/dune.
+    /// Addresses inside one of the modules are symbolised with that module's binary instead, and
+    /// if there are any modules, each frame is prefixed by the name of its binary, e.g.
+    /// `dsp.elf!function (file:line)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses`: Addresses to look up.
+    /// * `binary_path`: Path to the binary for debuginfo.
+    /// * `symbol_options`: Where to search for separate debuginfo, source path remappings and
+    ///   modules.
+    ///
+    /// returns: The symbol of each address, in the same order, or an error if a binary has no
+    /// debuginfo. Addresses that cannot be found are [UNKNOWN SYMBOL]. No binary is read if there
+    /// are no addresses.
+    pub fn symbolise(addresses: &[usize], binary_path: &str, symbol_options: &SymbolOptions) -> Result<Vec<String>, DamselflyError> {
+        if addresses.is_empty() {
+            return Ok(Vec::new());
+        }
+        let binary_file = Self::open_binary(binary_path)?;
+        let object = Self::parse_binary(&binary_file, binary_path)?;
+        Self::symbolise_in_binary(addresses, binary_path, &object, symbol_options)
+    }
+
+    /// Symbolises addresses like symbolise, using a binary that has already been parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses`: Addresses to look up.
+    /// * `binary_path`: Path to the binary, to find its separate debuginfo.
+    /// * `object`: The binary, as given by parse_binary.
+    /// * `symbol_options`: Where to search for separate debuginfo, source path remappings and
+    ///   modules.
+    ///
+    /// returns: The symbol of each address, in the same order, or an error if a binary has no
+    /// debuginfo.
+    pub fn symbolise_in_binary(addresses: &[usize], binary_path: &str, object: &BinaryObject, symbol_options: &SymbolOptions) -> Result<Vec<String>, DamselflyError> {
+        let mut frames = vec![Vec::new(); addresses.len()];
+        // Module index (None for the main binary) -> indices of its addresses
+        let mut addresses_by_module: BTreeMap<Option<usize>, Vec<usize>> = BTreeMap::new();
+        for (index, address) in addresses.iter().enumerate() {
+            addresses_by_module.entry(symbol_options.find_module(*address)).or_default().push(index);
+        }
+        for (module_index, indices) in addresses_by_module {
+            let module = module_index.map(|module_index| &symbol_options.get_modules()[module_index]);
+            let module_addresses: Vec<usize> = indices.iter().map(|&index| addresses[index]).collect();
+            let (module_frames, name) = match module {
+                Some(module) => {
+                    let module_file = Self::open_binary(module.get_path())?;
+                    let module_object = Self::parse_binary(&module_file, module.get_path())?;
+                    let module_frames = Self::resolve_symbols(&module_addresses, module.get_path(), &module_object,
+                                                              Some(module.get_start()), symbol_options)?;
+                    (module_frames, module.get_name().to_string())
+                }
+                None => (Self::resolve_symbols(&module_addresses, binary_path, object, None, symbol_options)?,
+                         BinaryModule::name_of(binary_path)),
+            };
+            for (index, mut address_frames) in indices.into_iter().zip(module_frames) {
+                if !symbol_options.get_modules().is_empty() {
+                    address_frames.iter_mut().for_each(|frame| frame.set_module(name.clone()));
+                }
+                frames[index] = address_frames;
+            }
+        }
+        let prefix = if symbol_options.get_path_remaps().is_empty() {
+            let files: Vec<String> = frames.iter()
+                .flatten()
+                .filter_map(|frame| frame.get_file())
+                .map(str::to_string)
+                .collect();
+            Self::longest_common_prefix(&files)
+        } else {
+            for frame in frames.iter_mut().flatten() {
+                if let Some(file) = frame.get_file().and_then(|file| symbol_options.remap_path(file)) {
+                    frame.set_file(file);
+                }
+            }
+            String::new()
+        };
+        Ok(frames.iter().map(|frames| SymbolFrame::format_frames(frames, &prefix)).collect())
+    }
+
+    /// Opens a binary so that it can be parsed with parse_binary. The binary is read lazily, so
+    /// only the parts that are used are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_path`: Path to the binary.
+    ///
+    /// returns: The binary, or an error if it cannot be opened
+    pub fn open_binary(binary_path: &str) -> Result<ReadCache<File>, DamselflyError> {
+        File::open(binary_path)
+            .map(ReadCache::new)
+            .map_err(|source| DamselflyError::Io { path: binary_path.to_string(), source })
+    }
+
+    /// Parses a binary's headers, sections and symbol table. Other sections, e.g. the DWARF
+    /// debuginfo, are only read when they are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_file`: The binary, as given by open_binary.
+    /// * `binary_path`: Path to the binary, for errors.
+    ///
+    /// returns: The parsed binary, or an error if it is not an object file
+    pub fn parse_binary<'data>(binary_file: &'data ReadCache<File>, binary_path: &str) -> Result<BinaryObject<'data>, DamselflyError> {
+        object::File::parse(binary_file)
+            .map_err(|error| DamselflyError::BadBinary { path: binary_path.to_string(), reason: error.to_string() })
+    }
+
+    /// Finds the frames of addresses using the debuginfo in a binary, including frames for inlined
+    /// functions. Function names are demangled. If the binary has been stripped of its debuginfo,
+    /// the debuginfo is read from a separate file found by build ID or .gnu_debuglink instead.
+    /// Addresses that the debuginfo does not cover, or every address if no debuginfo can be found,
+    /// are looked up in the ELF symbol table instead.
+    /// If a cache directory is set, frames cached for this build of the binary are reused. The
+    /// cache is looked up before any debuginfo is read, and the debuginfo is only read for
+    /// addresses that are not cached yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses`: Addresses to look up.
+    /// * `binary_path`: Path to the binary, to find its separate debuginfo.
+    /// * `object`: The binary, as given by parse_binary.
+    /// * `load_address`: Where the binary was loaded. Position independent binaries, whose lowest
+    ///   segment is at 0, are relocated to this address. Other binaries are assumed to be loaded at
+    ///   the addresses they were linked at.
+    /// * `symbol_options`: Where to search for separate debuginfo, and where to cache frames.
+    ///
+    /// returns: The frames of each address, innermost first, in the same order as the addresses,
+    /// or an error if the binary has neither debuginfo nor a symbol table. Addresses that cannot be
+    /// found have no frames.
+    pub fn resolve_symbols(addresses: &[usize], binary_path: &str, object: &BinaryObject, load_address: Option<usize>, symbol_options: &SymbolOptions) -> Result<Vec<Vec<SymbolFrame>>, DamselflyError> {
+        if addresses.is_empty() {
+            return Ok(Vec::new());
+        }
+        let bias = match load_address {
+            Some(load_address) if Self::get_image_base(object) == 0 => load_address,
+            _ => 0,
+        };
+        let has_dwarf = object.section_by_name(".debug_info").is_some();
+        let binary_id = SymbolCache::binary_id(binary_path, object);
+        let debuginfo_id = match has_dwarf {
+            true => None,
+            false => symbol_options.find_debug_file(binary_path, object).and_then(|path| SymbolCache::file_id(&path)),
+        };
+        let cache_dir = symbol_options.get_cache_dir().zip(binary_id.as_deref());
+        let mut cache = match cache_dir {
+            Some((cache_dir, binary_id)) => SymbolCache::load(cache_dir, binary_id, debuginfo_id),
+            None => SymbolCache::new(debuginfo_id),
+        };
+
+        let binary_addresses: Vec<usize> = addresses.iter().map(|address| address.wrapping_sub(bias)).collect();
+        let uncached_addresses = |cache: &SymbolCache| -> Vec<usize> {
+            binary_addresses.iter()
+                .copied()
+                .filter(|address| !cache.contains(*address))
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect()
+        };
+        let mut addresses_to_find = uncached_addresses(&cache);
+        if addresses_to_find.is_empty() {
+            eprintln!("[MemorySysTraceParser::resolve_symbols]: Using cached symbols for {binary_path}");
+        } else {
+            // The debuginfo is only read if some addresses are not cached
+            let debug_file = match has_dwarf {
+                true => None,
+                false => symbol_options.open_debug_file(binary_path, object)
+                    .map(|(debug_path, file)| (debug_path.to_string_lossy().into_owned(), ReadCache::new(file))),
+            };
+            let debug_object = match &debug_file {
+                Some((debug_path, debug_file)) => {
+                    eprintln!("[MemorySysTraceParser::resolve_symbols]: Using debuginfo for {binary_path} from {debug_path}");
+                    Some(Self::parse_binary(debug_file, debug_path)?)
+                }
+                None => None,
+            };
+            // If the debuginfo actually opened differs from the one the cache was built with, discard the cache
+            let debuginfo_id = debug_file.as_ref().and_then(|(debug_path, _)| SymbolCache::file_id(Path::new(debug_path)));
+            if cache.get_debuginfo_id() != debuginfo_id.as_deref() {
+                cache = SymbolCache::new(debuginfo_id);
+                addresses_to_find = uncached_addresses(&cache);
+            }
+            let dwarf_object = debug_object.as_ref().unwrap_or(object);
+            let frames = Self::find_frames(&addresses_to_find, binary_path, object, dwarf_object)?;
+            for (address, frames) in addresses_to_find.iter().zip(frames) {
+                cache.insert(*address, frames);
+            }
+            if let Some((cache_dir, binary_id)) = cache_dir {
+                cache.save(cache_dir, binary_id);
+            }
+        }
+        Ok(binary_addresses.iter()
+            .map(|address| cache.get(*address).cloned().unwrap_or_default())
+            .collect())
+    }
+
+    /// Finds the frames of addresses in the DWARF debuginfo, falling back to the ELF symbol table.
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses`: Addresses in the binary, i.e. after relocation.
+    /// * `binary_path`: Path to the binary, for errors.
+    /// * `object`: The binary.
+    /// * `dwarf_object`: The binary, or its separate debuginfo file.
+    ///
+    /// returns: The frames of each address, or an error if there is neither debuginfo nor a symbol
+    /// table
+    fn find_frames(addresses: &[usize], binary_path: &str, object: &BinaryObject, dwarf_object: &BinaryObject) -> Result<Vec<Vec<SymbolFrame>>, DamselflyError> {
+        let mut symbol_table = SymbolTable::from_object(object);
+        if symbol_table.is_empty() {
+            symbol_table = SymbolTable::from_object(dwarf_object);
+        }
+        let ctx = match dwarf_object.section_by_name(".debug_info") {
+            Some(_) => Context::new(dwarf_object).map_err(|error| error.to_string()),
+            None => Err("no .debug_info section or separate debuginfo file".to_string()),
+        };
+        let ctx = match ctx {
+            Ok(ctx) => Some(ctx),
+            Err(reason) if symbol_table.is_empty() =>
+                return Err(DamselflyError::MissingDwarf { path: binary_path.to_string(), reason: format!("{reason}, and no symbol table") }),
+            Err(reason) => {
+                eprintln!("[MemorySysTraceParser::find_frames]: {reason} in {binary_path}, so only its symbol table is used");
+                None
+            }
+        };
 
         let mut symbols = Vec::new();
-        for address in &addresses {
-            let mut symbol = String::new();
-            if let Ok(Some(location)) = ctx.find_location(*address as u64) {
-                symbol.push_str(location.file.unwrap());
-                symbol.push(':');
-                symbol.push_str(location.line.unwrap().to_string().as_str());
-                symbols.push(symbol);
-            } else {
-                symbols.push("[UNKNOWN SYMBOL]".to_string());
+        for &address in addresses {
+            let mut frames = Vec::new();
+            if let Some(Ok(mut frame_iter)) = ctx.as_ref().map(|ctx| ctx.find_frames(address as u64).skip_all_loads()) {
+                while let Ok(Some(frame)) = frame_iter.next() {
+                    let function = frame.function
+                        .as_ref()
+                        .and_then(|function| function.demangle().ok())
+                        .map(|function| function.into_owned());
+                    let (file, line) = match frame.location {
+                        Some(location) => (location.file.map(str::to_string), location.line),
+                        None => (None, None),
+                    };
+                    frames.push(SymbolFrame::new(function, file, line));
+                }
+            }
+            if frames.is_empty() {
+                if let Some((function, offset)) = symbol_table.lookup(address as u64) {
+                    frames.push(SymbolFrame::from_symbol_table(function.to_string(), offset));
+                }
             }
+            symbols.push(frames);
         }
+        Ok(symbols)
+    }
 
-        self.prefix = Self::longest_common_prefix(&symbols);
-        self.symbols = addresses.into_iter().zip(symbols).collect();
+    /// Gets the lowest address that a binary is loaded at, which is 0 for position independent
+    /// binaries.
+    ///
+    /// # Arguments
+    ///
+    /// * `object`: The binary.
+    ///
+    /// returns: usize
+    pub fn get_image_base(object: &BinaryObject) -> usize {
+        object.segments()
+            .filter(|segment| segment.size() > 0)
+            .map(|segment| segment.address() as usize)
+            .min()
+            .unwrap_or(0)
     }
 
     /// Looks up the symbol corresponding to a hex address.
@@ -418,24 +979,20 @@ impl MemorySysTraceParser {
     /// * `query`: The address in hex without a 0x prefix.
     ///
     /// returns: The symbol if found, None otherwise
-    fn lookup_symbol(&self, query: String) -> Option<String> {
-        let full_symbol = self.symbols.get(&usize::from_str_radix(query.as_str(), 16)
-            .expect("[MemorySysTraceParser::lookup_symbol_str_short]: Failed to parse hex address string into usize"));
-        full_symbol?;
-        Some(full_symbol.unwrap()
-            .trim_start_matches(&self.prefix)
-            .to_string())
+    fn lookup_symbol(&self, query: String) -> Result<Option<String>, DamselflyError> {
+        let address = self.parse_address(&query)?;
+        Ok(self.symbols.get(&address).cloned())
     }
 
     /// Finds the longest common prefix in a list of strings.
-    /// Useful for trimming common prefixes from callstack strings such as /home/work/dev/hp/dune etc.
+    /// Useful for trimming common prefixes from source files such as /home/work/dev/hp/dune etc.
     /// 
     /// # Arguments 
     /// 
     /// * `strings`: Vec of strings to search.
     /// 
     /// returns: The longest common prefix.
-    fn longest_common_prefix(strings: &Vec<String>) -> String {
+    pub fn longest_common_prefix(strings: &[String]) -> String {
         if strings.is_empty() {
             return String::new();
         }
@@ -444,8 +1001,11 @@ impl MemorySysTraceParser {
         let shortest = strings
             .iter()
             .filter(|string| string.starts_with('/'))
-            .min_by_key(|s| s.len())
-            .expect("[MemorySysTraceParser::longest_common_prefix]: Failed to identify shortest stacktrace path (log might be empty)");
+            .min_by_key(|s| s.len());
+        // Nothing to trim if no symbol resolved to a path
+        let Some(shortest) = shortest else {
+            return String::new();
+        };
 
         let mut prefix = String::new();
         for (i, char) in shortest.char_indices() {
@@ -458,7 +1018,11 @@ impl MemorySysTraceParser {
             }
         }
 
-        prefix
+        // Only trim whole directories, so that file names are kept even if there is only one file
+        match prefix.rfind('/') {
+            Some(last_slash) => prefix[..=last_slash].to_string(),
+            None => prefix,
+        }
     }
 
     /// Call this to iterate through the log until an operation is fully formed (with stacktracing)
@@ -468,26 +1032,54 @@ impl MemorySysTraceParser {
     /// 
     /// * `log_iter`: Iterator to the log file.
     /// 
-    /// returns: The newly formed memory update from iterating through the log.
-    pub fn process_instruction(&mut self, log_iter: &mut Peekable<Split<char>>) -> MemoryUpdateType {
+    /// returns: The newly formed memory update from iterating through the log, or None if the log
+    /// has no memory updates left.
+    pub fn process_instruction<S: AsRef<str>>(&mut self, log_iter: &mut impl Iterator<Item = S>) -> Result<Option<MemoryUpdateType>, DamselflyError> {
         let mut baked_instruction = None;
         for line in &mut *log_iter {
-            if self.is_line_useless_and_load_pool(line) {
+            let line = line.as_ref();
+            self.line_number += 1;
+            if self.is_line_useless_or_skipped(line)? {
                 continue;
             }
-            let record = self.line_to_record(line)
-                .expect("[MemorySysTraceParser::process_operation]: Failed to process line");
+            let record = match self.line_to_record(line).and_then(|record| self.apply_cycle_counter(line, record)) {
+                Ok(record) => record,
+                Err(error) => {
+                    self.skip_or_fail(line, error)?;
+                    continue;
+                }
+            };
             match record {
-                RecordType::StackTrace(_, _) => self.process_stacktrace(record),
-                _ => { baked_instruction = self.process_alloc_or_free(Some(record)) },
+                RecordType::StackTrace(_, _) => {
+                    if let Err(error) = self.process_stacktrace(record) {
+                        self.skip_or_fail(line, error)?;
+                    }
+                },
+                _ => { baked_instruction = self.process_alloc_or_free(Some(record))? },
             }
             if baked_instruction.is_some() { break; }
         }
         // EOF but last instruction left in queue
         if baked_instruction.is_none() && !self.record_queue.is_empty() {
-            baked_instruction = Some(self.bake_memory_update());
+            baked_instruction = Some(self.bake_memory_update()?);
+            self.record_queue.clear();
+        }
+        Ok(baked_instruction)
+    }
+
+    /// Checks if a line is useless (see is_line_useless_and_load_pool). In lenient mode, malformed
+    /// pool lines are reported as diagnostics and treated as useless.
+    /// 
+    /// # Arguments 
+    /// 
+    /// * `line`: The line to check.
+    /// 
+    /// returns: true if useless or skipped, false if useful
+    fn is_line_useless_or_skipped(&mut self, line: &str) -> Result<bool, DamselflyError> {
+        match self.is_line_useless_and_load_pool(line) {
+            Ok(is_line_useless) => Ok(is_line_useless),
+            Err(error) => self.skip_or_fail(line, error).map(|_| true),
         }
-        baked_instruction.unwrap()
     }
 
     /// Processes an allocation or a free record into an update.
@@ -498,22 +1090,23 @@ impl MemorySysTraceParser {
     /// * `record`: A record parsed from the log file.
     /// 
     /// returns: A memory update if it was possible to create one.
-    fn process_alloc_or_free(&mut self, record: Option<RecordType>) -> Option<MemoryUpdateType> {
+    fn process_alloc_or_free(&mut self, record: Option<RecordType>) -> Result<Option<MemoryUpdateType>, DamselflyError> {
         // If this is the first record in the log, push it into the queue and wait for StackTrace records
         if self.record_queue.is_empty() {
-            self.record_queue.push(record.expect("[MemorySysTraceParser::process_alloc_or_free]: Queue is empty, but record is also None"));
-            return None;
+            let record = record.ok_or_else(|| self.malformed_record("record queue is empty, but record is also None"))?;
+            self.record_queue.push(record);
+            return Ok(None);
         }
 
         // Else, bake the previously stored alloc/free, clear the queue and push the latest record into it
-        let baked_memory_update = self.bake_memory_update();
+        let baked_memory_update = self.bake_memory_update()?;
         self.record_queue.clear();
         if let Some(record) = record {
             self.record_queue.push(record);
         } else {
             // EOF. empty else block for clarity
         }
-        Some(baked_memory_update)
+        Ok(Some(baked_memory_update))
     }
     
     /// Bakes an update by going through the current queue of records and compressing them into 
@@ -536,32 +1129,39 @@ impl MemorySysTraceParser {
     /// * `record`: A record parsed from the log file.
     ///
     /// returns: A memory update if it was possible to create one.
-    fn bake_memory_update(&mut self) -> MemoryUpdateType {
+    fn bake_memory_update(&mut self) -> Result<MemoryUpdateType, DamselflyError> {
         let mut iter = self.record_queue.iter();
-        let mut first_rec: RecordType = iter.next().expect("[MemorySysTraceParser::bake_memory_update]: Record queue empty").clone();
+        let mut first_rec: RecordType = iter.next()
+            .ok_or_else(|| self.malformed_record("record queue is empty"))?
+            .clone();
         for rec in iter {
             if let RecordType::StackTrace(trace_address, trace_callstack) = rec {
                 match first_rec {
-                    RecordType::Allocation(alloc_address, _, ref mut allocation_callstack, _) => {
+                    RecordType::Allocation(alloc_address, _, ref mut allocation_callstack, _, _, _) => {
                         // Check if we are tracing the correct address
                         if *trace_address == alloc_address {
                             allocation_callstack.push_str(trace_callstack);
                             allocation_callstack.push('\n');
                         }
                     },
-                    RecordType::Free(free_address, ref mut free_callstack, _) => {
+                    RecordType::Free(free_address, ref mut free_callstack, _, _, _) => {
                         // Check if we are tracing the correct address
                         if *trace_address == free_address {
                             free_callstack.push_str(trace_callstack);
                             free_callstack.push('\n');
                         }
                     }
+                    RecordType::Reallocation(_, realloc_address, _, ref mut realloc_callstack, _, _, _) => {
+                        // Reallocations are traced by their new address
+                        if *trace_address == realloc_address {
+                            realloc_callstack.push_str(trace_callstack);
+                            realloc_callstack.push('\n');
+                        }
+                    }
                     RecordType::StackTrace(_, _) =>
-                        panic!("[MemorySysTraceParser::bake_memory_update]: First instruction in instruction queue is a stacktrace, but it should be an alloc/free"),
-                    RecordType::PoolBounds(_, _) =>
-                        panic!("[MemorySysTraceParser::bake_memory_update]: First instruction in instruction queue is a poolbounds, but it should be an alloc/free"),
-                    RecordType::PoolName(_) =>
-                        panic!("[MemorySysTraceParser::bake_memory_update]: First instruction in instruction queue is a poolname, but it should be an alloc/free"),
+                        return Err(DamselflyError::OrphanStackTrace { line: self.line_number }),
+                    RecordType::PoolBounds(_, _) | RecordType::PoolName(_) =>
+                        return Err(self.malformed_record("pool record found where an alloc/free was expected")),
                 }
             }
         }
@@ -569,26 +1169,44 @@ impl MemorySysTraceParser {
         // Stack tracing complete, so we instantiate the MemoryUpdateType with the required data and return it
         let memory_update;
         match first_rec {
-            RecordType::Allocation(address, size, callstack, real_timestamp) => {
-                memory_update = Allocation::new(address, size, Arc::new(callstack), self.time, real_timestamp).wrap_in_enum();
+            RecordType::Allocation(address, size, callstack, real_timestamp, thread_id, cycle_counter) => {
+                self.allocation_sizes.insert(address, size);
+                let mut allocation = Allocation::new(address, size, Arc::new(callstack), self.time, real_timestamp);
+                allocation.set_thread_id(thread_id);
+                allocation.set_cycle_counter(cycle_counter);
+                memory_update = allocation.wrap_in_enum();
                 self.time += 1;
             },
-            RecordType::Free(address, callstack, real_timestamp) => {
+            RecordType::Free(address, callstack, real_timestamp, thread_id, cycle_counter) => {
                 // We manually calculate the bytes to free, since the log file does not say how many bytes are freed
                 let free_size = self.find_latest_allocation_size(address);
-                memory_update = Free::new(address, free_size, Arc::new(callstack), self.time, real_timestamp).wrap_in_enum();
+                let mut free = Free::new(address, free_size, Arc::new(callstack), self.time, real_timestamp);
+                free.set_thread_id(thread_id);
+                free.set_cycle_counter(cycle_counter);
+                memory_update = free.wrap_in_enum();
                 self.time += 1;
             },
-            RecordType::StackTrace(..) => panic!("[MemorySysTraceParser::bake_memory_update]: First instruction in instruction queue is a stacktrace, but it should be an alloc/free"),
-            RecordType::PoolBounds(..) => panic!("[MemorySysTraceParser::bake_memory_update]: First instruction in instruction queue is a poolbounds, but it should be an alloc/free"),
-            RecordType::PoolName(..) => panic!("[MemorySysTraceParser::bake_memory_update]: First instruction in instruction queue is a poolname, but it should be an alloc/free"),
+            RecordType::Reallocation(old_address, address, size, callstack, real_timestamp, thread_id, cycle_counter) => {
+                // As with frees, the log does not say how big the old allocation was
+                let old_size = self.find_latest_allocation_size(old_address);
+                self.allocation_sizes.remove(&old_address);
+                self.allocation_sizes.insert(address, size);
+                let mut reallocation = Reallocation::new(address, size, old_address, old_size, Arc::new(callstack), self.time, real_timestamp);
+                reallocation.set_thread_id(thread_id);
+                reallocation.set_cycle_counter(cycle_counter);
+                memory_update = reallocation.wrap_in_enum();
+                self.time += 1;
+            },
+            RecordType::StackTrace(..) => return Err(DamselflyError::OrphanStackTrace { line: self.line_number }),
+            RecordType::PoolBounds(..) | RecordType::PoolName(..) =>
+                return Err(self.malformed_record("pool record found where an alloc/free was expected")),
         }
-        memory_update
+        Ok(memory_update)
     }
 
     /// Finds the size of a free. Since frees do not list the size of memory freed in the log, we must
-    /// iterate backwards through the list of allocations to find the latest allocation corresponding
-    /// to the free. The size of this allocation is the size of the free. 
+    /// look up the latest allocation corresponding to the free. The size of this allocation is the
+    /// size of the free. 
     /// 
     /// # Arguments 
     /// 
@@ -596,14 +1214,7 @@ impl MemorySysTraceParser {
     /// 
     /// returns: usize 
     fn find_latest_allocation_size(&self, address: usize) -> usize {
-        for memory_update in self.memory_updates.iter().rev() {
-            if let MemoryUpdateType::Allocation(allocation) = memory_update {
-                if allocation.get_absolute_address() == address {
-                    return allocation.get_absolute_size();
-                }
-            }
-        }
-        0
+        self.allocation_sizes.get(&address).copied().unwrap_or(0)
     }
 
     /// Processes a StackTrace record by pushing it to the record queue in the parser, which must later
@@ -614,11 +1225,12 @@ impl MemorySysTraceParser {
     /// * `record`: The StackTrace record.
     /// 
     /// returns: () 
-    fn process_stacktrace(&mut self, record: RecordType) {
+    fn process_stacktrace(&mut self, record: RecordType) -> Result<(), DamselflyError> {
         if self.record_queue.is_empty() {
-            panic!("[MemorySysTraceParser::process_stacktrace]: First instruction in instruction queue cannot be a stacktrace");
+            return Err(DamselflyError::OrphanStackTrace { line: self.line_number });
         }
         self.record_queue.push(record);
+        Ok(())
     }
 
     /// Parses a raw log line into a Record.
@@ -627,111 +1239,163 @@ impl MemorySysTraceParser {
     /// 
     /// * `line`: Raw line from the log.
     /// 
-    /// returns: Result<RecordType, String> 
-    fn line_to_record(&self, line: &str) -> Result<RecordType, String> {
-        let line_parts = line.split('>').collect::<Vec<_>>();
-        let dataline = line_parts.get(1);
-        if dataline.is_none() {
-            return Err(String::from("[MemorySysTraceParser::parse_line]: Failed to split by > char"));
-        }
+    /// returns: Result<RecordType, DamselflyError> 
+    fn line_to_record(&self, line: &str) -> Result<RecordType, DamselflyError> {
+        let (timestamp_dataline, dataline) = self.layout.split_line(line)
+            .ok_or_else(|| self.malformed_record(&format!("failed to split by {} separator", self.layout.payload_separator)))?;
 
-        let timestamp_dataline = line_parts.get(0);
-        if timestamp_dataline.is_none() {
-            return Err(String::from("[MemorySysTraceParser::parse_line]: Failed to split by > char"));
-        }
-
-        let timestamp_dataline = timestamp_dataline.unwrap().trim();
-        let timestamp_parts = timestamp_dataline
-            .split(' ')
-            .filter(|part| !part.is_empty())
-            .collect::<Vec<_>>();
-
-        let timestamp = timestamp_parts.get(5);
-        if timestamp.is_none() {
-            return Err(String::from("[MemorySysTraceParser::parse_line]: Failed to get timestamp"));
-        }
+        let (timestamp, units) = self.layout.parse_timestamp(timestamp_dataline)
+            .map_err(|reason| self.malformed_record(reason))?;
+        let full_timestamp = format!("{timestamp} {units}");
+        Utility::convert_to_microseconds(&full_timestamp)
+            .map_err(|_| DamselflyError::BadTimestamp { line: self.line_number, text: full_timestamp.clone() })?;
 
-        let timestamp = timestamp.unwrap().trim();
-        let units = timestamp_parts.get(6);
-        if units.is_none() {
-            return Err(String::from("[MemorySysTraceParser::parse_line]: Failed to get timestamp units"));
-        }
+        let thread_id = self.layout.parse_task_id(timestamp_dataline);
 
-        let units = units.unwrap().trim();
-        let full_timestamp = format!("{timestamp} {units}");
-        let dataline = dataline.unwrap().trim();
+        let dataline = dataline.trim();
         let split_dataline = dataline.split(' ').collect::<Vec<_>>();
         if split_dataline.len() < 2 {
-            return Err(String::from("[MemorySysTraceParser::parse_line]: Line length mismatch"));
+            return Err(self.malformed_record("line length mismatch"));
         }
+        let field = |index: usize, name: &str| {
+            split_dataline.get(index)
+                .copied()
+                .ok_or_else(|| self.malformed_record(&format!("{} record is missing its {name}", split_dataline[0])))
+        };
 
-        let mut address_needed = false;
-        let mut record;
-        match split_dataline[0] {
+        let record = match split_dataline[0] {
             "+" => {
-                record = RecordType::Allocation(0, 0, String::new(), String::new());
-                address_needed = true;
+                let address = self.parse_address(field(1, "address")?)?;
+                let size = field(2, "size")?;
+                let size = usize::from_str_radix(size, 16)
+                    .map_err(|_| DamselflyError::BadSize { line: self.line_number, text: size.to_string() })?;
+                RecordType::Allocation(address, size, String::new(), full_timestamp, thread_id, None)
             },
             "-" => {
-                record = RecordType::Free(0, String::new(), String::new());
-                address_needed = true;
-            },
-            "^" => {
-                record = {
-                    let symbol = self.lookup_symbol(Self::extract_trace_address(split_dataline[2]))
-                        .or(Some("[INVALID_SYMBOL]".to_string()));
-                    RecordType::StackTrace(0, symbol.unwrap())
-                };
-                address_needed = true;
-            },
-            "POOLBOUNDS" => record = RecordType::PoolBounds(0, 0),
-            "POOLNAME" => record = RecordType::PoolName(String::new()),
-            unknown  => return Err(format!("[MemorySysTraceParser::parse_line]: Invalid operation type {}", unknown)),
-        }
-
-        let mut address = 0;
-        if address_needed {
-            address = usize::from_str_radix(split_dataline[1], 16)
-                .expect("[MemorySysTraceParser::parse_line]: Failed to convert address to decimal");
-        }
-
-        match record {
-            RecordType::Allocation(ref mut default_address, ref mut default_size, _, ref mut default_real_timestamp) => {
-                *default_address = address;
-                *default_size = usize::from_str_radix(split_dataline[2], 16)
-                    .expect("[MemorySysTraceParser::parse_line]: Failed to read size");
-                *default_real_timestamp = full_timestamp;
+                let address = self.parse_address(field(1, "address")?)?;
+                RecordType::Free(address, String::new(), full_timestamp, thread_id, None)
             },
-            RecordType::Free(ref mut default_address, _, ref mut default_real_timestamp) => {
-                *default_address = address;
-                *default_real_timestamp = full_timestamp;
+            "~" => {
+                let old_address = self.parse_address(field(1, "old address")?)?;
+                let address = self.parse_address(field(2, "new address")?)?;
+                let size = field(3, "size")?;
+                let size = usize::from_str_radix(size, 16)
+                    .map_err(|_| DamselflyError::BadSize { line: self.line_number, text: size.to_string() })?;
+                RecordType::Reallocation(old_address, address, size, String::new(), full_timestamp, thread_id, None)
             },
-            RecordType::StackTrace(ref mut default_address, _) => *default_address = address,
-            RecordType::PoolBounds(ref mut default_address, ref mut default_size) => {
-                *default_address = usize::from_str_radix(split_dataline[1], 16)
-                    .expect("[MemorySysTraceParser::parse_line]: Failed to convert pool address to decimal");
-                *default_size = usize::from_str(split_dataline[2])
-                    .expect("[MemorySysTraceParser::parse_line]: Failed to convert pool size to usize");
+            "^" => {
+                let address = self.parse_address(field(1, "address")?)?;
+                let symbol = self.lookup_symbol(Self::extract_trace_address(field(2, "return address")?))?
+                    .unwrap_or("[INVALID_SYMBOL]".to_string());
+                RecordType::StackTrace(address, symbol)
             },
-            RecordType::PoolName(ref mut default_name) => {
-                  *default_name = split_dataline[1].to_string();
+            "POOLBOUNDS" => {
+                let address = self.parse_address(field(1, "start address")?)?;
+                let size = field(2, "size")?;
+                let size = usize::from_str(size)
+                    .map_err(|_| DamselflyError::BadSize { line: self.line_number, text: size.to_string() })?;
+                RecordType::PoolBounds(address, size)
             },
-        }
+            "POOLNAME" => RecordType::PoolName(split_dataline[1].to_string()),
+            unknown => return Err(self.malformed_record(&format!("invalid operation type {unknown}"))),
+        };
 
         Ok(record)
     }
 
+    /// Reads the cycle counter of an allocation, free or reallocation, if the layout gives the
+    /// counter's clock rate. The reading is kept on the record, and its realtime replaces the
+    /// record's coarse realtime timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the log.
+    /// * `record`: The record parsed from the line.
+    ///
+    /// returns: The record with its cycle counter reading
+    fn apply_cycle_counter(&mut self, line: &str, mut record: RecordType) -> Result<RecordType, DamselflyError> {
+        let Some(cycle_counter_clock) = self.cycle_counter_clock.as_mut() else {
+            return Ok(record);
+        };
+        let (real_timestamp, cycle_counter) = match &mut record {
+            RecordType::Allocation(_, _, _, real_timestamp, _, cycle_counter)
+            | RecordType::Free(_, _, real_timestamp, _, cycle_counter)
+            | RecordType::Reallocation(_, _, _, _, real_timestamp, _, cycle_counter) => (real_timestamp, cycle_counter),
+            _ => return Ok(record),
+        };
+        let (header, _) = self.layout.split_line(line)
+            .ok_or_else(|| DamselflyError::MalformedRecord { line: self.line_number, reason: String::from("failed to split line") })?;
+        let counter = self.layout.parse_counter(header)
+            .map_err(|text| DamselflyError::BadTimestamp { line: self.line_number, text })?;
+        let coarse_microseconds = Utility::convert_to_microseconds(real_timestamp)
+            .map_err(|_| DamselflyError::BadTimestamp { line: self.line_number, text: real_timestamp.clone() })?;
+        let reading = cycle_counter_clock.read(counter, coarse_microseconds);
+        *real_timestamp = reading.to_real_timestamp();
+        *cycle_counter = Some(reading);
+        Ok(record)
+    }
+
+    /// Parses a hex address (without a 0x prefix) from the line currently being parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `text`: The address in hex.
+    ///
+    /// returns: Result<usize, DamselflyError>
+    fn parse_address(&self, text: &str) -> Result<usize, DamselflyError> {
+        usize::from_str_radix(text, 16)
+            .map_err(|_| DamselflyError::BadAddress { line: self.line_number, text: text.to_string() })
+    }
+
     pub fn get_pool_list(&self) -> &MemoryPoolList {
         &self.pool_list
     }
 }
 
+/// Iterator over the memory operations in a log, parsed one at a time.
+/// Created by MemorySysTraceParser::stream_memory_updates.
+pub struct MemoryUpdateStream<'a, R: BufRead> {
+    parser: &'a mut MemorySysTraceParser,
+    log_lines: LogLines<R>,
+    finished: bool,
+}
+
+impl<R: BufRead> Iterator for MemoryUpdateStream<'_, R> {
+    type Item = Result<MemoryUpdateType, DamselflyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.parser.process_instruction(&mut self.log_lines) {
+            Ok(Some(memory_update)) => {
+                self.parser.counter += 1;
+                Some(Ok(memory_update))
+            }
+            Ok(None) => {
+                self.finished = true;
+                self.log_lines.check_error().err().map(Err)
+            }
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::damselfly::consts::TEST_BINARY_PATH;
-    use crate::damselfly::memory::memory_parsers::{MemoryParser, MemorySysTraceParser, RecordType};
+    use std::sync::Arc;
+    use crate::damselfly::memory::memory_parsers::{MemoryParser, MemorySysTraceParser, ParseResults, RecordType};
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::cycle_counter_clock::CycleCounterReading;
+    use crate::damselfly::memory::log_lines::LogLines;
     use crate::damselfly::memory::memory_pool::MemoryPool;
-    use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
+    use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType};
+    use crate::damselfly::memory::systrace_layout::SysTraceLayout;
 
     #[test]
     fn is_line_useless_test() {
@@ -745,27 +1409,27 @@ mod tests {
         let log = [allocation_record, free_record, stacktrace_record, poolbounds_record, poolname_record, useless_record];
         let mut iter = log.iter().peekable();
         let mut mst_parser = MemorySysTraceParser::new();
-        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()));
+        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()).unwrap());
         iter.next();
-        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()));
+        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()).unwrap());
         iter.next();
-        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()));
+        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()).unwrap());
         iter.next();
-        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()));
+        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()).unwrap());
         iter.next();
-        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()));
+        assert!(!mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()).unwrap());
         iter.next();
-        assert!(mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()));
+        assert!(mst_parser.is_line_useless_and_load_pool(iter.peek().unwrap()).unwrap());
     }
 
     #[test]
     fn bake_memory_update_alloc_test() {
         let mut mst_parser = MemorySysTraceParser::new();
-        mst_parser.record_queue.push(RecordType::Allocation(0, 4, "".to_string(), "".to_string()));
+        mst_parser.record_queue.push(RecordType::Allocation(0, 4, "".to_string(), "".to_string(), None, None));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "1".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "2".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "3".to_string()));
-        if let MemoryUpdateType::Allocation(allocation) = mst_parser.bake_memory_update() {
+        if let MemoryUpdateType::Allocation(allocation) = mst_parser.bake_memory_update().unwrap() {
             assert_eq!(allocation.get_absolute_address(), 0);
             assert_eq!(allocation.get_absolute_size(), 4);
             assert_eq!(*allocation.get_callstack(), String::from("1\n2\n3\n"));
@@ -777,27 +1441,49 @@ mod tests {
     #[test]
     fn bake_memory_update_free_test() {
         let mut mst_parser = MemorySysTraceParser::new();
-        mst_parser.record_queue.push(RecordType::Free(0, "".to_string(), "".to_string()));
+        mst_parser.record_queue.push(RecordType::Free(0, "".to_string(), "".to_string(), None, None));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "1".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "2".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "3".to_string()));
-        let memory_update = mst_parser.bake_memory_update();
+        let memory_update = mst_parser.bake_memory_update().unwrap();
         if let MemoryUpdateType::Free(free) = memory_update {
             assert_eq!(free.get_absolute_address(), 0);
             assert_eq!(*free.get_callstack(), "1\n2\n3\n");
         }
     }
 
+    #[test]
+    fn bake_memory_update_realloc_test() {
+        let mut mst_parser = MemorySysTraceParser::new();
+        mst_parser.record_queue.push(RecordType::Allocation(0, 4, "".to_string(), "".to_string(), None, None));
+        mst_parser.bake_memory_update().unwrap();
+        mst_parser.record_queue.clear();
+        mst_parser.record_queue.push(RecordType::Reallocation(0, 8, 12, "".to_string(), "".to_string(), None, None));
+        mst_parser.record_queue.push(RecordType::StackTrace(0, "old".to_string()));
+        mst_parser.record_queue.push(RecordType::StackTrace(8, "new".to_string()));
+        if let MemoryUpdateType::Reallocation(reallocation) = mst_parser.bake_memory_update().unwrap() {
+            assert_eq!(reallocation.get_absolute_address(), 8);
+            assert_eq!(reallocation.get_absolute_size(), 12);
+            assert_eq!(reallocation.get_old_address(), 0);
+            assert_eq!(reallocation.get_old_size(), 4);
+            assert_eq!(*reallocation.get_callstack(), "new\n");
+        } else {
+            panic!();
+        }
+        assert_eq!(mst_parser.find_latest_allocation_size(0), 0);
+        assert_eq!(mst_parser.find_latest_allocation_size(8), 12);
+    }
+
     #[test]
     fn bake_memory_update_pool_test() {
         let mut mst_parser = MemorySysTraceParser::new();
-        mst_parser.record_queue.push(RecordType::Free(0, "".to_string(), "".to_string()));
+        mst_parser.record_queue.push(RecordType::Free(0, "".to_string(), "".to_string(), None, None));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "1".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "2".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "3".to_string()));
         mst_parser.record_queue.push(RecordType::PoolBounds(11, 17));
         mst_parser.record_queue.push(RecordType::PoolName("malloc_pool".to_string()));
-        let memory_update = mst_parser.bake_memory_update();
+        let memory_update = mst_parser.bake_memory_update().unwrap();
         if let MemoryUpdateType::Free(free) = memory_update {
             assert_eq!(free.get_absolute_address(), 0);
             assert_eq!(*free.get_callstack(), "1\n2\n3\n");
@@ -805,62 +1491,59 @@ mod tests {
         }
     }
     #[test]
-    #[should_panic]
     fn bake_memory_update_empty_test() {
         let mut mst_parser = MemorySysTraceParser::new();
-        mst_parser.bake_memory_update();
+        assert!(mst_parser.bake_memory_update().is_err());
     }
 
     #[test]
-    #[should_panic]
     fn bake_memory_update_invalid_queue_trace_only_test() {
         let mut mst_parser = MemorySysTraceParser::new();
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
-        mst_parser.bake_memory_update();
+        assert!(matches!(mst_parser.bake_memory_update(), Err(DamselflyError::OrphanStackTrace { .. })));
     }
 
     #[test]
-    #[should_panic]
     fn bake_memory_update_invalid_queue_trace_first_allocation_test() {
         let mut mst_parser = MemorySysTraceParser::new();
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
-        mst_parser.record_queue.push(RecordType::Allocation(0, 4, "callstack".to_string(), "".to_string()));
+        mst_parser.record_queue.push(RecordType::Allocation(0, 4, "callstack".to_string(), "".to_string(), None, None));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
-        mst_parser.bake_memory_update();
+        assert!(matches!(mst_parser.bake_memory_update(), Err(DamselflyError::OrphanStackTrace { .. })));
     }
 
     #[test]
-    #[should_panic]
     fn bake_memory_update_invalid_queue_trace_first_free_test() {
         let mut mst_parser = MemorySysTraceParser::new();
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
-        mst_parser.record_queue.push(RecordType::Free(0, "callstack".to_string(), "".to_string()));
+        mst_parser.record_queue.push(RecordType::Free(0, "callstack".to_string(), "".to_string(), None, None));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
         mst_parser.record_queue.push(RecordType::StackTrace(0, "callstack".to_string()));
-        mst_parser.bake_memory_update();
+        assert!(matches!(mst_parser.bake_memory_update(), Err(DamselflyError::OrphanStackTrace { .. })));
     }
 
     #[test]
     fn process_alloc_or_free_first_record_test(){
         let mut mst_parser = MemorySysTraceParser::new();
-        let record = RecordType::Allocation(0, 4, "callstack".to_string(), "".to_string());
-        let instruction = mst_parser.process_alloc_or_free(Some(record));
+        let record = RecordType::Allocation(0, 4, "callstack".to_string(), "".to_string(), None, None);
+        let instruction = mst_parser.process_alloc_or_free(Some(record)).unwrap();
         assert!(instruction.is_none());
         assert_eq!(mst_parser.record_queue.len(), 1);
         match mst_parser.record_queue.first().unwrap() {
-            RecordType::Allocation(address, size, callstack, _) => {
+            RecordType::Allocation(address, size, callstack, _, _, _) => {
                 assert_eq!(*address, 0);
                 assert_eq!(*size, 4);
                 assert_eq!(*callstack, "callstack".to_string());
             }
             RecordType::Free(..) => panic!("Wrong type: Free"),
+            RecordType::Reallocation(..) => panic!("Wrong type: Reallocation"),
             RecordType::StackTrace(..) => panic!("Wrong type: Stacktrace"),
             RecordType::PoolBounds(..) => panic!("Wrong type: PoolBounds"),
             RecordType::PoolName(..) => panic!("Wrong type: PoolName"),
@@ -870,23 +1553,23 @@ mod tests {
     #[test]
     fn process_alloc_or_free_existing_records_test() {
         let mut mst_parser = MemorySysTraceParser::new();
-        let alloc_record = RecordType::Allocation(0, 4, "".to_string(), "".to_string());
+        let alloc_record = RecordType::Allocation(0, 4, "".to_string(), "".to_string(), None, None);
         let records = vec![
             RecordType::StackTrace(0, "1".to_string()),
             RecordType::StackTrace(0, "2".to_string()),
             RecordType::StackTrace(0, "3".to_string()),
         ];
 
-        mst_parser.process_alloc_or_free(Some(alloc_record));
+        mst_parser.process_alloc_or_free(Some(alloc_record)).unwrap();
         for record in records {
-            mst_parser.process_stacktrace(record);
+            mst_parser.process_stacktrace(record).unwrap();
         }
 
         // Current queue status
         // | Alloc0 | Trace1 | Trace2 | Trace3 |
         let memory_update = mst_parser.process_alloc_or_free(
-            Some(RecordType::Allocation(4, 4, "".to_string(), "".to_string()))
-        ).unwrap();
+            Some(RecordType::Allocation(4, 4, "".to_string(), "".to_string(), None, None))
+        ).unwrap().unwrap();
         // | Alloc4 |
         // instruction = Alloc0 with Trace 1-3
 
@@ -897,6 +1580,7 @@ mod tests {
                 assert_eq!(*allocation.get_callstack(), "1\n2\n3\n");
             }
             MemoryUpdateType::Free(_) => panic!("Wrong type: Free"),
+            MemoryUpdateType::Reallocation(_) => panic!("Wrong type: Reallocation"),
         }
 
         let records = vec![
@@ -906,13 +1590,13 @@ mod tests {
         ];
 
         for record in records {
-            mst_parser.process_stacktrace(record);
+            mst_parser.process_stacktrace(record).unwrap();
         }
 
         // | Alloc4 | Trace4 | Trace5 | Trace6 |
         let memory_update = mst_parser.process_alloc_or_free(
-            Some(RecordType::Free(0, "callstack3".to_string(), "".to_string()))
-        ).unwrap();
+            Some(RecordType::Free(0, "callstack3".to_string(), "".to_string(), None, None))
+        ).unwrap().unwrap();
         // | Free0 |
         // instruction = Alloc4 with Trace 1-3
 
@@ -923,10 +1607,11 @@ mod tests {
                 assert_eq!(*allocation.get_callstack(), "4\n5\n6\n");
             }
             MemoryUpdateType::Free(_) => panic!("Wrong type: Free"),
+            MemoryUpdateType::Reallocation(_) => panic!("Wrong type: Reallocation"),
         }
 
         // EOF
-        let memory_update = mst_parser.process_alloc_or_free(None).unwrap();
+        let memory_update = mst_parser.process_alloc_or_free(None).unwrap().unwrap();
         // Empty
         // instruction = Free
 
@@ -936,16 +1621,17 @@ mod tests {
                 assert_eq!(free.get_absolute_address(), 0);
                 assert_eq!(*free.get_callstack(), "callstack3");
             }
+            MemoryUpdateType::Reallocation(_) => panic!("Wrong type: Reallocation"),
         }
     }
 
     #[test]
-    #[should_panic]
     fn process_stacktrace_empty_queue_test() {
         let mut mst_parser = MemorySysTraceParser::new();
-        mst_parser.process_stacktrace(
+        let result = mst_parser.process_stacktrace(
             RecordType::StackTrace(0, "1".to_string())
         );
+        assert!(matches!(result, Err(DamselflyError::OrphanStackTrace { .. })));
     }
 
     #[test]
@@ -954,13 +1640,15 @@ mod tests {
         let line = "00001444: 039e0edc |V|A|005|        0 us   0003.678 s    < DT:0xE1504C74> + e150206c 20";
         let record = mst_parser.line_to_record(line).unwrap();
         match record {
-            RecordType::Allocation(address, size, callstack, real_timestamp) => {
+            RecordType::Allocation(address, size, callstack, real_timestamp, thread_id, _) => {
                 assert_eq!(address, 3780124780);
                 assert_eq!(size, 32);
                 assert!(callstack.is_empty());
                 assert_eq!(real_timestamp, "0003.678 s");
+                assert_eq!(thread_id, Some(0xE1504C74));
             }
             RecordType::Free(..) => panic!("Wrong record type: Free"),
+            RecordType::Reallocation(..) => panic!("Wrong record type: Reallocation"),
             RecordType::StackTrace(..) => panic!("Wrong record type: Stacktrace"),
             RecordType::PoolBounds(..) => panic!("Wrong record type: PoolBounds"),
             RecordType::PoolName(..) => panic!("Wrong record type: PoolName"),
@@ -974,17 +1662,78 @@ mod tests {
         let record = mst_parser.line_to_record(line).unwrap();
         match record {
             RecordType::Allocation(..) => panic!("Wrong type: Allocation"),
-            RecordType::Free(address, callstack, real_timestamp) => {
+            RecordType::Free(address, callstack, real_timestamp, thread_id, _) => {
                 assert_eq!(address, 3780124716);
                 assert!(callstack.is_empty());
                 assert_eq!(real_timestamp, "0003.677 s");
+                assert_eq!(thread_id, Some(0xE1504B54));
             }
+            RecordType::Reallocation(..) => panic!("Wrong type: Reallocation"),
             RecordType::StackTrace(..) => panic!("Wrong type: Stacktrace"),
             RecordType::PoolBounds(..) => panic!("Wrong type: PoolBounds"),
             RecordType::PoolName(..) => panic!("Wrong type: PoolName"),
         }
     }
 
+    #[test]
+    fn line_to_record_realloc_test() {
+        let mst_parser = MemorySysTraceParser::new();
+        let line = "00001192: 039dd8f5 |V|A|005|        3 us   0003.677 s    < DT:0xE1504B54> ~ e150202c e150206c 40";
+        let record = mst_parser.line_to_record(line).unwrap();
+        match record {
+            RecordType::Reallocation(old_address, address, size, callstack, real_timestamp, _, _) => {
+                assert_eq!(old_address, 3780124716);
+                assert_eq!(address, 3780124780);
+                assert_eq!(size, 64);
+                assert!(callstack.is_empty());
+                assert_eq!(real_timestamp, "0003.677 s");
+            }
+            _ => panic!("Wrong type"),
+        }
+    }
+
+    #[test]
+    fn line_to_record_custom_layout_test() {
+        let layout = r#"{"payload_separator": "::", "timestamp_column": 1, "units_column": null, "task_id_marker": "tid="}"#;
+        let mst_parser = MemorySysTraceParser::new().with_layout(layout.parse().unwrap());
+        let line = "[cpu1] 3.678s tid=0xE1504C74 :: + e150206c 20";
+        match mst_parser.line_to_record(line).unwrap() {
+            RecordType::Allocation(address, size, _, real_timestamp, thread_id, _) => {
+                assert_eq!(address, 3780124780);
+                assert_eq!(size, 32);
+                assert_eq!(real_timestamp, "3.678 s");
+                assert_eq!(thread_id, Some(0xE1504C74));
+            }
+            _ => panic!("Wrong type"),
+        }
+        let default_line = "00001444: 039e0edc |V|A|005|        0 us   0003.678 s    < DT:0xE1504C74> + e150206c 20";
+        assert!(matches!(mst_parser.line_to_record(default_line), Err(DamselflyError::MalformedRecord { .. })));
+    }
+
+    #[test]
+    fn cycle_counter_timestamp_test() {
+        let layout = r#"{"counter_frequency_hz": 100000000}"#;
+        let mut mst_parser = MemorySysTraceParser::new().with_layout(layout.parse().unwrap());
+        let log = "00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14\n\
+                   00001069: 039dcc2c |V|A|005|        2 us   0003.677 s    < DT:0xE14DEEBC> - e150202c\n";
+        let updates = mst_parser.stream_memory_updates(LogLines::new(log.as_bytes(), "[log]"))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(*updates[0].get_real_timestamp(), "3677000.000 us");
+        // 250 ticks of a 100 MHz clock later
+        assert_eq!(*updates[1].get_real_timestamp(), "3677002.500 us");
+        assert_eq!(updates[1].get_cycle_counter(), Some(CycleCounterReading::new(0x039dcc2c, 3_677_002_500)));
+        assert_eq!(updates[1].get_real_timestamp_microseconds().unwrap(), 3_677_002.5);
+    }
+
+    #[test]
+    fn parse_thread_id_test() {
+        let layout = SysTraceLayout::default();
+        assert_eq!(layout.parse_task_id("00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC"), Some(0xE14DEEBC));
+        assert_eq!(layout.parse_task_id("00000039: 03c67959 |V|A|005|        0 us   0003.917 s    < DT:  unknown "), None);
+        assert_eq!(layout.parse_task_id("00000039: 03c67959 |V|A|005|        0 us   0003.917 s"), None);
+    }
+
     #[test]
     fn line_to_record_trace_test() {
         let mst_parser = MemorySysTraceParser::new();
@@ -993,6 +1742,7 @@ mod tests {
         match record {
             RecordType::Allocation(..) => panic!("Wrong type: Allocation"),
             RecordType::Free(..) => panic!("Wrong type: Free"),
+            RecordType::Reallocation(..) => panic!("Wrong type: Reallocation"),
             RecordType::StackTrace(address, _) => {
                 assert_eq!(address, 3780124716);
             }
@@ -1009,6 +1759,7 @@ mod tests {
         match record {
             RecordType::Allocation(..) => panic!("Wrong type: Allocation"),
             RecordType::Free(..) => panic!("Wrong type: Free"),
+            RecordType::Reallocation(..) => panic!("Wrong type: Reallocation"),
             RecordType::StackTrace(..) => panic!("Wrong type: StackTrace"),
             RecordType::PoolBounds(address, size) => {
                 assert_eq!(address, 3780124716);
@@ -1026,6 +1777,7 @@ mod tests {
         match record {
             RecordType::Allocation(..) => panic!("Wrong type: Allocation"),
             RecordType::Free(..) => panic!("Wrong type: Free"),
+            RecordType::Reallocation(..) => panic!("Wrong type: Reallocation"),
             RecordType::StackTrace(..) => panic!("Wrong type: StackTrace"),
             RecordType::PoolBounds(..) => panic!("Wrong type: StackTrace"),
             RecordType::PoolName(name) => {
@@ -1081,7 +1833,7 @@ mod tests {
 00001100: 039dce14 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> ^ e15020a4 [e04865ef]
  ";
 
-        let memory_updates = mst_parser.parse_log_directly(log, TEST_BINARY_PATH).memory_updates;
+        let memory_updates = mst_parser.parse_log_directly(log, TEST_BINARY_PATH).unwrap().memory_updates;
         let alloc = memory_updates.first().unwrap();
         if let MemoryUpdateType::Allocation(allocation) = alloc {
             assert_eq!(allocation.get_absolute_address(), 3780124716);
@@ -1113,7 +1865,7 @@ mod tests {
 00057608: 0b197a34 |V|B|002|        0 us   0011.712 s    < DT:0xE14DEEBC> sched_switch from pid <0xe14e6d94> (priority 235) to pid <0xe14deebc> (priority 235)
 00057609: 0b197a70 |V|B|002|        3 us   0011.712 s    < DT:0xE14E6D94> sched_switch from pid <0xe14deebc> (priority 255) to pid <0xe14e6d94> (priority 235)
  ";
-        let instructions = mst_parser.parse_log_directly(log, TEST_BINARY_PATH).memory_updates;
+        let instructions = mst_parser.parse_log_directly(log, TEST_BINARY_PATH).unwrap().memory_updates;
         assert!(matches!(instructions.first().unwrap(), MemoryUpdateType::Free(..)));
     }
 
@@ -1127,7 +1879,7 @@ mod tests {
 00000830: 039da3f2 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> - e150204c 14
 0 ";
         let mut mst_parser = MemorySysTraceParser::new();
-        let addresses = mst_parser.extract_addresses_from_log(log);
+        let addresses = mst_parser.extract_addresses_from_log(log.split('\n')).unwrap();
         assert_eq!(addresses, vec![0xe045d83b, 0xe04865ef]);
     }
 
@@ -1141,10 +1893,12 @@ mod tests {
 00000828: 039da2f5 |V|A|002|        0 us   0003.677 s    < DT:0xE14DEEBC> SSC::Received Activity Monitor State 2 Change Event
 00000830: 039da3f2 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> - e150204c 14
 0 ";
-        mst_parser.parse_symbols(log, TEST_BINARY_PATH);
+        mst_parser.parse_symbols(log.split('\n'), TEST_BINARY_PATH).unwrap();
 
-        assert_eq!(mst_parser.symbols.get(&usize::from_str_radix("e045d83b", 16).unwrap()).unwrap(),
-                   &String::from("/work/hpdev/dune/src/fw/print/engine/PageBasedEngine/Bratwurst/Remote/LibBratwurstProtobuf/src/FormatterRasterInterfaceMessages.pb-c.c:208"));
+        // Frames are `function (file:line)`, with the directories common to both addresses trimmed
+        let symbol = mst_parser.symbols.get(&usize::from_str_radix("e045d83b", 16).unwrap()).unwrap();
+        assert!(symbol.ends_with("FormatterRasterInterfaceMessages.pb-c.c:208)"));
+        assert!(!symbol.contains("/work/hpdev/dune/src/fw/"));
     }
 
     #[test]
@@ -1154,6 +1908,115 @@ mod tests {
         assert_eq!(MemorySysTraceParser::longest_common_prefix(&strings), String::from("/work/hpdev/dune/src/fw/"));
     }
 
+    #[test]
+    fn longest_common_prefix_no_paths_test() {
+        let strings = vec![String::from("[UNKNOWN SYMBOL]"), String::from("[UNKNOWN SYMBOL]")];
+        assert_eq!(MemorySysTraceParser::longest_common_prefix(&strings), String::new());
+    }
+
+    #[test]
+    fn longest_common_prefix_one_file_test() {
+        let strings = vec![String::from("/work/src/main.c:12"), String::from("/work/src/main.c:15")];
+        assert_eq!(MemorySysTraceParser::longest_common_prefix(&strings), String::from("/work/src/"));
+    }
+
+    #[test]
+    fn line_to_record_bad_address_test() {
+        let mut mst_parser = MemorySysTraceParser::new();
+        mst_parser.line_number = 12;
+        let line = "00001444: 039e0edc |V|A|005|        0 us   0003.678 s    < DT:0xE1504C74> + e15zz06c 20";
+        match mst_parser.line_to_record(line) {
+            Err(DamselflyError::BadAddress { line, text }) => {
+                assert_eq!(line, 12);
+                assert_eq!(text, "e15zz06c");
+            }
+            _ => panic!("Expected BadAddress"),
+        }
+    }
+
+    #[test]
+    fn line_to_record_bad_timestamp_test() {
+        let mst_parser = MemorySysTraceParser::new();
+        let line = "00001444: 039e0edc |V|A|005|        0 us   0003.678 h    < DT:0xE1504C74> + e150206c 20";
+        assert!(matches!(mst_parser.line_to_record(line), Err(DamselflyError::BadTimestamp { .. })));
+    }
+
+    #[test]
+    fn line_to_record_missing_size_test() {
+        let mst_parser = MemorySysTraceParser::new();
+        let line = "00001444: 039e0edc |V|A|005|        0 us   0003.678 s    < DT:0xE1504C74> + e150206c";
+        assert!(matches!(mst_parser.line_to_record(line), Err(DamselflyError::MalformedRecord { .. })));
+    }
+
+    #[test]
+    fn process_instruction_reports_line_number_test() {
+        let mut mst_parser = MemorySysTraceParser::new();
+        let log = "\
+00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14
+00001069: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> sched_switch
+00001070: 039dcb41 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e15020a4 xyz";
+        let mut log_iter = log.split('\n').peekable();
+        match mst_parser.process_instruction(&mut log_iter) {
+            Err(DamselflyError::BadSize { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("Expected BadSize"),
+        }
+    }
+
+    #[test]
+    fn stream_memory_updates_test() {
+        let mut mst_parser = MemorySysTraceParser::new();
+        let log = "\
+00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14
+00001069: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> ^ e150202c [e045d83b]
+00001070: 039dcb41 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e15020a4 6c
+00001071: 039dcb41 |V|A|005|       12 us   0003.678 s    < DT:0xE14DEEBC> - e150202c
+";
+        let mut memory_updates = mst_parser.stream_memory_updates(LogLines::new(log.as_bytes(), "log"));
+        let first = memory_updates.next().unwrap().unwrap();
+        assert!(matches!(first, MemoryUpdateType::Allocation(..)));
+        assert_eq!(first.get_absolute_address(), 0xe150202c);
+        let remaining: Vec<MemoryUpdateType> = memory_updates.map(Result::unwrap).collect();
+        assert_eq!(remaining.len(), 2);
+        match &remaining[1] {
+            MemoryUpdateType::Free(free) => assert_eq!(free.get_absolute_size(), 0x14),
+            MemoryUpdateType::Allocation(_) => panic!("Wrong type: Allocation"),
+            MemoryUpdateType::Reallocation(_) => panic!("Wrong type: Reallocation"),
+        }
+        assert_eq!(mst_parser.counter, 3);
+    }
+
+    #[test]
+    fn process_instruction_lenient_orphan_stacktrace_test() {
+        let mut mst_parser = MemorySysTraceParser::new_lenient();
+        let log = "\
+00001069: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> ^ e150202c [e045d83b]
+00001070: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14
+00001071: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> ^ e150202c [e045d83b]";
+        let mut log_iter = log.split('\n').peekable();
+        let memory_update = mst_parser.process_instruction(&mut log_iter).unwrap().unwrap();
+        assert_eq!(memory_update.get_absolute_address(), 0xe150202c);
+        assert_eq!(*memory_update.get_callstack(), "[INVALID_SYMBOL]\n");
+        assert_eq!(mst_parser.diagnostics.len(), 1);
+        assert_eq!(mst_parser.diagnostics[0].get_line(), 1);
+        assert!(mst_parser.diagnostics[0].get_text().contains("^ e150202c"));
+        assert_eq!(mst_parser.diagnostics[0].get_reason(), "stack trace does not follow an allocation or free");
+    }
+
+    #[test]
+    fn process_instruction_lenient_truncated_line_test() {
+        let mut mst_parser = MemorySysTraceParser::new_lenient();
+        let log = "\
+00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14
+00001070: 039dcb41 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e15020a4 xyz
+00001071: 039dcb41 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150";
+        let mut log_iter = log.split('\n').peekable();
+        let memory_update = mst_parser.process_instruction(&mut log_iter).unwrap().unwrap();
+        assert_eq!(memory_update.get_absolute_address(), 0xe150202c);
+        assert!(log_iter.peek().is_none());
+        let lines: Vec<usize> = mst_parser.diagnostics.iter().map(|diagnostic| diagnostic.get_line()).collect();
+        assert_eq!(lines, vec![2, 3]);
+    }
+
     #[test]
     fn get_pool_bounds_test() {
         let mst_parser = MemorySysTraceParser::new();
@@ -1173,7 +2036,7 @@ mod tests {
 00000163: 03c305a7 |V|A|005|        0 us   0003.937 s    < DT:  unknown > POOLBOUNDS e1676c94 1228800
 00000164: 03c305f0 |V|A|005|        4 us   0003.937 s    < DT:  unknown > POOLNAME cpp_pool
 00000165: 03c305f0 |V|A|080|        0 us   0003.937 s    < DT:  unknown > NvramAdapterNOS::DirectNosNvmObjs :: DirectNosNvmObjs is created.";
-        let parse_results = mst_parser.parse_log_directly(log, TEST_BINARY_PATH);
+        let parse_results = mst_parser.parse_log_directly(log, TEST_BINARY_PATH).unwrap();
         let pools = parse_results.pool_list.get_pools();
         let mut pools: Vec<MemoryPool> = Vec::from_iter(pools.clone());
         pools.sort();
@@ -1184,4 +2047,62 @@ mod tests {
         assert_eq!(pools[1].get_size(), 1228800);
         assert_eq!(pools[1].get_name(), "cpp_pool");
     }
+
+    #[test]
+    fn with_update_pools_test() {
+        let parse_results = || {
+            let mut pool_list = MemoryPoolList::default();
+            pool_list.add_pool(MemoryPool::new(0, 64, String::from("a")));
+            let updates = vec![
+                Allocation::new(0, 16, Arc::new(String::new()), 0, String::from("0 us")).wrap_in_enum(),
+                Free::new(0, 16, Arc::new(String::new()), 1, String::from("1 us")).wrap_in_enum(),
+            ];
+            ParseResults::new(updates, pool_list, 2, Vec::new())
+        };
+        let pool = Arc::new(String::from("a"));
+
+        let parse_results_with_pools = parse_results().with_update_pools(vec![Arc::clone(&pool), Arc::clone(&pool)]).unwrap();
+        assert_eq!(parse_results_with_pools.update_pools.len(), 2);
+        let error = parse_results().with_update_pools(vec![Arc::clone(&pool)]).err().unwrap();
+        assert!(matches!(error, DamselflyError::InconsistentPools { .. }));
+        let error = parse_results().with_update_pools(vec![pool, Arc::new(String::from("b"))]).err().unwrap();
+        assert!(matches!(error, DamselflyError::InconsistentPools { .. }));
+    }
+
+    #[test]
+    fn pool_lifecycle_test() {
+        let mut mst_parser = MemorySysTraceParser::new();
+        let log = "\
+00000150: 03c30500 |V|A|005|        0 us   0003.936 s    < DT:  unknown > POOLCREATE task_pool e1000000 64
+00000151: 03c30560 |V|A|005|        0 us   0003.937 s    < DT:  unknown > + e1000000 10
+00000152: 03c30560 |V|A|005|        0 us   0003.937 s    < DT:  unknown > ^ e1000000 [e03c2221]
+00000153: 03c30579 |V|A|005|        1 us   0003.937 s    < DT:  unknown > POOLRESIZE task_pool 128
+00000154: 03c30579 |V|A|005|        0 us   0003.937 s    < DT:  unknown > + e1000040 20
+00000155: 03c3058a |V|A|005|        1 us   0003.938 s    < DT:  unknown > POOLDELETE task_pool";
+        // Pool events do not depend on symbols, so the updates are streamed without a binary
+        let memory_updates = mst_parser.stream_memory_updates(LogLines::new(log.as_bytes(), "log"))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let parse_results = ParseResults::new(memory_updates, mst_parser.pool_list, mst_parser.counter, Vec::new());
+        let pools = parse_results.pool_list.get_pools();
+        assert_eq!(pools.len(), 1);
+        assert!(pools.contains(&MemoryPool::new(0xe1000000, 128, String::from("task_pool"))));
+        let lifetime = parse_results.pool_list.get_lifetime("task_pool").unwrap().clone();
+        let positions: Vec<usize> = lifetime.get_events().iter().map(|event| event.position).collect();
+        assert_eq!(positions, vec![0, 1, 2]);
+        assert_eq!(lifetime.get_events()[2].real_timestamp, "0003.938 s");
+        assert_eq!(lifetime.get_bounds_after(1), Some((0xe1000000, 0xe1000080)));
+
+        let split_results = parse_results.split_by_pools(0, 0).unwrap();
+        assert_eq!(split_results[0].memory_updates.len(), 2);
+        assert_eq!(split_results[0].pool_lifetime, Some(lifetime));
+
+        let log = "00000153: 03c30579 |V|A|005|        1 us   0003.937 s    < DT:  unknown > POOLRESIZE task_pool 128";
+        let mut mst_parser = MemorySysTraceParser::new();
+        let error = mst_parser.stream_memory_updates(LogLines::new(log.as_bytes(), "log"))
+            .collect::<Result<Vec<_>, _>>()
+            .err()
+            .unwrap();
+        assert!(matches!(error, DamselflyError::MalformedRecord { line: 1, .. }));
+    }
 }
@@ -69,10 +69,10 @@ impl MemoryPool {
     /// # Arguments
     ///
     /// * `start`: Range start.
-    /// * `end`: Range end.
+    /// * `end`: Range end (exclusive).
     ///
     /// returns: True if the range is inside the pool, False if otherwise.
     pub fn contains(&self, start: usize, end: usize) -> bool {
-        start >= self.start && end < (self.start + self.size)
+        start >= self.start && end <= (self.start + self.size)
     }
 }
\ No newline at end of file
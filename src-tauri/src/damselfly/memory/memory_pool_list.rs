@@ -1,24 +1,123 @@
 //! Holds a list of MemoryPools and lets you add to the list.
-use std::collections::HashSet;
+//! Pools that are created, resized or deleted during the trace also have a PoolLifetime. Such a
+//! pool is stored with the widest bounds it ever has, so that all of its operations fall inside it.
+use std::collections::{HashMap, HashSet};
 use crate::damselfly::memory::memory_pool::MemoryPool;
+use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind, PoolLifetime};
 
 #[derive(Default)]
 pub struct MemoryPoolList {
-    pools: HashSet<MemoryPool>
+    pools: HashSet<MemoryPool>,
+    lifetimes: HashMap<String, PoolLifetime>,
 }
 
 impl MemoryPoolList {
     pub fn new(pools: HashSet<MemoryPool>) -> Self {
         Self {
-            pools
+            pools,
+            lifetimes: HashMap::new(),
         }
     }
-    
+
     pub fn add_pool(&mut self, pool: MemoryPool) {
         self.pools.insert(pool);
     }
 
+    /// Adds a pool along with when it is created, resized and deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool`: The pool, with the widest bounds it ever has.
+    /// * `lifetime`: Lifetime of the pool.
+    ///
+    /// returns: ()
+    pub fn add_pool_with_lifetime(&mut self, pool: MemoryPool, lifetime: PoolLifetime) {
+        self.lifetimes.insert(pool.get_name().to_string(), lifetime);
+        self.pools.insert(pool);
+    }
+
+    /// Records that a pool has been created, resized or deleted. Creating a pool that is not in
+    /// the list adds it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: Name of the pool.
+    /// * `event`: The change to the pool.
+    ///
+    /// returns: false if the event resizes or deletes a pool that is not in the list
+    pub fn add_pool_event(&mut self, name: &str, event: PoolEvent) -> bool {
+        let existing_pool = self.pools.iter().find(|pool| pool.get_name() == name).cloned();
+        if existing_pool.is_none() && !matches!(event.kind, PoolEventKind::Create { .. }) {
+            return false;
+        }
+        let lifetime = self.lifetimes.entry(name.to_string()).or_insert_with(|| {
+            PoolLifetime::new(existing_pool.as_ref().map(|pool| (pool.get_start(), pool.get_size())))
+        });
+        lifetime.push_event(event);
+        let (start, end) = lifetime.get_extent()
+            .expect("[MemoryPoolList::add_pool_event]: Pool must exist at some point");
+        if let Some(existing_pool) = existing_pool {
+            self.pools.remove(&existing_pool);
+        }
+        self.pools.insert(MemoryPool::new(start, end - start, name.to_string()));
+        true
+    }
+
+    /// Removes every pool event, restoring pools to their bounds before the first event and
+    /// removing pools that were only created by events.
+    pub fn clear_pool_events(&mut self) {
+        for (name, lifetime) in self.lifetimes.drain() {
+            self.pools.retain(|pool| pool.get_name() != name);
+            if let Some((start, size)) = lifetime.get_initial_bounds() {
+                self.pools.insert(MemoryPool::new(start, size, name));
+            }
+        }
+    }
+
     pub fn get_pools(&self) -> &HashSet<MemoryPool> {
         &self.pools
     }
-}
\ No newline at end of file
+
+    /// Gets when a pool is created, resized and deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: Name of the pool.
+    ///
+    /// returns: The pool's lifetime, or None if it exists for the whole trace
+    pub fn get_lifetime(&self, name: &str) -> Option<&PoolLifetime> {
+        self.lifetimes.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::memory_pool::MemoryPool;
+    use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+    use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind};
+
+    fn event(kind: PoolEventKind, position: usize) -> PoolEvent {
+        PoolEvent { kind, position, real_timestamp: String::from("0 us") }
+    }
+
+    #[test]
+    fn add_pool_event_test() {
+        let mut pool_list = MemoryPoolList::default();
+        pool_list.add_pool(MemoryPool::new(0, 16, String::from("static")));
+        assert!(!pool_list.add_pool_event("dynamic", event(PoolEventKind::Delete, 0)));
+        assert!(pool_list.add_pool_event("dynamic", event(PoolEventKind::Create { start: 100, size: 50 }, 1)));
+        assert!(pool_list.add_pool_event("dynamic", event(PoolEventKind::Resize { size: 80 }, 2)));
+        assert!(pool_list.add_pool_event("static", event(PoolEventKind::Delete, 3)));
+
+        assert_eq!(pool_list.get_pools().len(), 2);
+        assert!(pool_list.get_pools().contains(&MemoryPool::new(100, 80, String::from("dynamic"))));
+        assert_eq!(pool_list.get_lifetime("dynamic").unwrap().get_bounds_after(0), None);
+        assert_eq!(pool_list.get_lifetime("static").unwrap().get_bounds_after(0), Some((0, 16)));
+        assert_eq!(pool_list.get_lifetime("static").unwrap().get_bounds_after(3), None);
+
+        pool_list.clear_pool_events();
+        assert_eq!(pool_list.get_pools().len(), 1);
+        assert!(pool_list.get_pools().contains(&MemoryPool::new(0, 16, String::from("static"))));
+        assert!(pool_list.get_lifetime("static").is_none());
+    }
+}
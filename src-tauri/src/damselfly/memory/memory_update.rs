@@ -1,14 +1,19 @@
-//! A memory update: Allocation or Free.
+//! A memory update: Allocation, Free or Reallocation.
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde::de::{MapAccess, SeqAccess};
 
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::cycle_counter_clock::CycleCounterReading;
+use crate::damselfly::memory::utility::Utility;
+
 #[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
 pub enum MemoryUpdateType {
     Allocation(Allocation),
-    Free(Free)
+    Free(Free),
+    Reallocation(Reallocation),
 }
 
 impl MemoryUpdateType {
@@ -16,6 +21,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.get_absolute_address(),
             MemoryUpdateType::Free(free) => free.get_absolute_address(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_absolute_address(),
         }
     }
 
@@ -23,6 +29,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.set_absolute_address(new_address),
             MemoryUpdateType::Free(free) => free.set_absolute_address(new_address),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.set_absolute_address(new_address),
         }
     }
     
@@ -30,6 +37,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.get_absolute_size(),
             MemoryUpdateType::Free(free) => free.get_absolute_size(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_absolute_size(),
         }
     }
     
@@ -37,6 +45,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.set_absolute_size(new_size),
             MemoryUpdateType::Free(free) => free.set_absolute_size(new_size),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.set_absolute_size(new_size),
         }
     }
 
@@ -44,6 +53,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.get_callstack(),
             MemoryUpdateType::Free(free) => free.get_callstack(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_callstack(),
         }
     }
 
@@ -51,6 +61,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.get_absolute_address(),
             MemoryUpdateType::Free(free) => free.get_absolute_address(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_span().0,
         }
     }
 
@@ -58,6 +69,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.get_absolute_address() + allocation.get_absolute_size(),
             MemoryUpdateType::Free(free) => free.get_absolute_address() + free.get_absolute_size(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_span().1,
         }
     }
     
@@ -65,6 +77,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.get_timestamp(),
             MemoryUpdateType::Free(free) => free.get_timestamp(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_timestamp(),
         }
     }
 
@@ -72,6 +85,7 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.set_timestamp(new_timestamp),
             MemoryUpdateType::Free(free) => free.set_timestamp(new_timestamp),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.set_timestamp(new_timestamp),
         }
     }
 
@@ -79,6 +93,50 @@ impl MemoryUpdateType {
         match self {
             MemoryUpdateType::Allocation(allocation) => allocation.get_real_timestamp(),
             MemoryUpdateType::Free(free) => free.get_real_timestamp(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_real_timestamp(),
+        }
+    }
+
+    pub fn get_thread_id(&self) -> Option<usize> {
+        match self {
+            MemoryUpdateType::Allocation(allocation) => allocation.get_thread_id(),
+            MemoryUpdateType::Free(free) => free.get_thread_id(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_thread_id(),
+        }
+    }
+
+    pub fn set_thread_id(&mut self, new_thread_id: Option<usize>) {
+        match self {
+            MemoryUpdateType::Allocation(allocation) => allocation.set_thread_id(new_thread_id),
+            MemoryUpdateType::Free(free) => free.set_thread_id(new_thread_id),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.set_thread_id(new_thread_id),
+        }
+    }
+
+    pub fn get_cycle_counter(&self) -> Option<CycleCounterReading> {
+        match self {
+            MemoryUpdateType::Allocation(allocation) => allocation.get_cycle_counter(),
+            MemoryUpdateType::Free(free) => free.get_cycle_counter(),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_cycle_counter(),
+        }
+    }
+
+    pub fn set_cycle_counter(&mut self, new_cycle_counter: Option<CycleCounterReading>) {
+        match self {
+            MemoryUpdateType::Allocation(allocation) => allocation.set_cycle_counter(new_cycle_counter),
+            MemoryUpdateType::Free(free) => free.set_cycle_counter(new_cycle_counter),
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.set_cycle_counter(new_cycle_counter),
+        }
+    }
+
+    /// Gets the realtime timestamp in microseconds. This comes from the cycle counter reading if
+    /// there is one, since it is more precise than the realtime timestamp string.
+    ///
+    /// returns: The realtime timestamp, or BadTimestamp if the string cannot be converted
+    pub fn get_real_timestamp_microseconds(&self) -> Result<f64, DamselflyError> {
+        match self.get_cycle_counter() {
+            Some(cycle_counter) => Ok(cycle_counter.get_microseconds()),
+            None => Utility::convert_to_microseconds(self.get_real_timestamp()).map(|microseconds| microseconds as f64),
         }
     }
 }
@@ -90,8 +148,13 @@ impl Display for MemoryUpdateType {
                 allocation.to_string(),
             MemoryUpdateType::Free(free) =>
                 free.to_string(),
+            MemoryUpdateType::Reallocation(reallocation) =>
+                reallocation.to_string(),
         };
-        write!(f, "{}", str)
+        match self.get_thread_id() {
+            Some(thread_id) => write!(f, "{} DT:0x{:X}", str, thread_id),
+            None => write!(f, "{}", str),
+        }
     }
 }
 
@@ -104,6 +167,10 @@ pub trait MemoryUpdate {
     fn get_timestamp(&self) -> usize;
     fn set_timestamp(&mut self, new_timestamp: usize);
     fn get_real_timestamp(&self) -> &String;
+    fn get_thread_id(&self) -> Option<usize>;
+    fn set_thread_id(&mut self, new_thread_id: Option<usize>);
+    fn get_cycle_counter(&self) -> Option<CycleCounterReading>;
+    fn set_cycle_counter(&mut self, new_cycle_counter: Option<CycleCounterReading>);
     fn wrap_in_enum(self) -> MemoryUpdateType;
 }
 
@@ -114,6 +181,8 @@ pub struct Allocation {
     callstack: Arc<String>,
     timestamp: usize,
     real_timestamp: String,
+    thread_id: Option<usize>,
+    cycle_counter: Option<CycleCounterReading>,
 }
 
 impl Allocation {
@@ -127,6 +196,9 @@ impl Allocation {
     /// * `timestamp`: Absolute operation timestamp of the allocation.
     /// * `real_timestamp`: String representing the real timestamp e.g. "0020.939 s"
     ///
+    /// The thread ID and cycle counter reading are unknown until set with set_thread_id and
+    /// set_cycle_counter.
+    ///
     /// returns: Allocation
     pub fn new(address: usize, size: usize, callstack: Arc<String>, timestamp: usize, real_timestamp: String) -> Allocation {
         Allocation {
@@ -135,6 +207,8 @@ impl Allocation {
             callstack,
             timestamp,
             real_timestamp,
+            thread_id: None,
+            cycle_counter: None,
         }
     }
 }
@@ -146,6 +220,8 @@ pub struct Free {
     callstack: Arc<String>,
     timestamp: usize,
     real_timestamp: String,
+    thread_id: Option<usize>,
+    cycle_counter: Option<CycleCounterReading>,
 }
 
 impl Free {
@@ -159,6 +235,9 @@ impl Free {
     /// * `timestamp`: Absolute operation timestamp of the free.
     /// * `real_timestamp`: String representing the real timestamp e.g. "0020.939 s"
     ///
+    /// The thread ID and cycle counter reading are unknown until set with set_thread_id and
+    /// set_cycle_counter.
+    ///
     /// returns: Free
     pub fn new(address: usize, size: usize, callstack: Arc<String>, timestamp: usize, real_timestamp: String) -> Free {
         Free {
@@ -167,6 +246,81 @@ impl Free {
             callstack,
             timestamp,
             real_timestamp,
+            thread_id: None,
+            cycle_counter: None,
+        }
+    }
+}
+
+/// A resize of an existing allocation, which may or may not have moved it.
+///
+/// The address and size are those of the allocation after the resize, so that a Reallocation can
+/// stand in for the Allocation it replaces. The old address and size describe what it replaced.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Reallocation {
+    address: usize,
+    size: usize,
+    old_address: usize,
+    old_size: usize,
+    callstack: Arc<String>,
+    timestamp: usize,
+    real_timestamp: String,
+    thread_id: Option<usize>,
+    cycle_counter: Option<CycleCounterReading>,
+}
+
+impl Reallocation {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `address`: Address where the allocation starts after the resize.
+    /// * `size`: Size of the allocation after the resize.
+    /// * `old_address`: Address where the allocation started before the resize.
+    /// * `old_size`: Size of the allocation before the resize.
+    /// * `callstack`: Callstack of the reallocation.
+    /// * `timestamp`: Absolute operation timestamp of the reallocation.
+    /// * `real_timestamp`: String representing the real timestamp e.g. "0020.939 s"
+    ///
+    /// The thread ID and cycle counter reading are unknown until set with set_thread_id and
+    /// set_cycle_counter.
+    ///
+    /// returns: Reallocation
+    pub fn new(address: usize, size: usize, old_address: usize, old_size: usize, callstack: Arc<String>, timestamp: usize, real_timestamp: String) -> Reallocation {
+        Reallocation {
+            address,
+            size,
+            old_address,
+            old_size,
+            callstack,
+            timestamp,
+            real_timestamp,
+            thread_id: None,
+            cycle_counter: None,
+        }
+    }
+
+    pub fn get_old_address(&self) -> usize {
+        self.old_address
+    }
+
+    pub fn get_old_size(&self) -> usize {
+        self.old_size
+    }
+
+    /// Gets the span of memory touched by the reallocation, covering both the old and the new
+    /// allocation. An empty old or new allocation does not contribute to the span.
+    ///
+    /// returns: (start, end) of the span.
+    pub fn get_span(&self) -> (usize, usize) {
+        let new_span = (self.address, self.address + self.size);
+        let old_span = (self.old_address, self.old_address + self.old_size);
+        if self.old_size == 0 {
+            new_span
+        } else if self.size == 0 {
+            old_span
+        } else {
+            (new_span.0.min(old_span.0), new_span.1.max(old_span.1))
         }
     }
 }
@@ -204,6 +358,22 @@ impl MemoryUpdate for Allocation {
         &self.real_timestamp
     }
 
+    fn get_thread_id(&self) -> Option<usize> {
+        self.thread_id
+    }
+
+    fn set_thread_id(&mut self, new_thread_id: Option<usize>) {
+        self.thread_id = new_thread_id;
+    }
+
+    fn get_cycle_counter(&self) -> Option<CycleCounterReading> {
+        self.cycle_counter
+    }
+
+    fn set_cycle_counter(&mut self, new_cycle_counter: Option<CycleCounterReading>) {
+        self.cycle_counter = new_cycle_counter;
+    }
+
     fn wrap_in_enum(self) -> MemoryUpdateType {
         MemoryUpdateType::Allocation(self)
     }
@@ -242,11 +412,87 @@ impl MemoryUpdate for Free {
         &self.real_timestamp
     }
 
+    fn get_thread_id(&self) -> Option<usize> {
+        self.thread_id
+    }
+
+    fn set_thread_id(&mut self, new_thread_id: Option<usize>) {
+        self.thread_id = new_thread_id;
+    }
+
+    fn get_cycle_counter(&self) -> Option<CycleCounterReading> {
+        self.cycle_counter
+    }
+
+    fn set_cycle_counter(&mut self, new_cycle_counter: Option<CycleCounterReading>) {
+        self.cycle_counter = new_cycle_counter;
+    }
+
     fn wrap_in_enum(self) -> MemoryUpdateType {
         MemoryUpdateType::Free(self)
     }
 }
 
+impl MemoryUpdate for Reallocation {
+    fn get_absolute_address(&self) -> usize {
+        self.address
+    }
+
+    /// Moves the old address by the same amount, so that both allocations stay in the same frame.
+    fn set_absolute_address(&mut self, new_address: usize) {
+        self.old_address = (self.old_address + new_address).saturating_sub(self.address);
+        self.address = new_address
+    }
+
+    fn get_absolute_size(&self) -> usize {
+        self.size
+    }
+
+    /// Resizes the old allocation by the same amount, so that padding applies to both allocations.
+    fn set_absolute_size(&mut self, new_size: usize) {
+        if self.old_size != 0 {
+            self.old_size = (self.old_size + new_size).saturating_sub(self.size);
+        }
+        self.size = new_size;
+    }
+
+    fn get_callstack(&self) -> Arc<String> {
+        Arc::clone(&(self.callstack))
+    }
+
+    fn get_timestamp(&self) -> usize {
+        self.timestamp
+    }
+
+    fn set_timestamp(&mut self, new_timestamp: usize) {
+        self.timestamp = new_timestamp;
+    }
+
+    fn get_real_timestamp(&self) -> &String {
+        &self.real_timestamp
+    }
+
+    fn get_thread_id(&self) -> Option<usize> {
+        self.thread_id
+    }
+
+    fn set_thread_id(&mut self, new_thread_id: Option<usize>) {
+        self.thread_id = new_thread_id;
+    }
+
+    fn get_cycle_counter(&self) -> Option<CycleCounterReading> {
+        self.cycle_counter
+    }
+
+    fn set_cycle_counter(&mut self, new_cycle_counter: Option<CycleCounterReading>) {
+        self.cycle_counter = new_cycle_counter;
+    }
+
+    fn wrap_in_enum(self) -> MemoryUpdateType {
+        MemoryUpdateType::Reallocation(self)
+    }
+}
+
 impl Display for Allocation {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let str = format!("[{} {}] ALLOC: 0x{:x} {}B",
@@ -269,15 +515,30 @@ impl Display for Free {
     }
 }
 
+impl Display for Reallocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let str = format!("[{} {}] REALLOC: 0x{:x} {}B -> 0x{:x} {}B",
+                          self.get_timestamp(),
+                          self.get_real_timestamp(),
+                          self.get_old_address(),
+                          self.get_old_size(),
+                          self.get_absolute_address(),
+                          self.get_absolute_size());
+        write!(f, "{}", str)
+    }
+}
+
 /// Serialize implementations for IPC to the frontend via Tauri
 impl Serialize for Allocation {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        let mut state = serializer.serialize_struct("Allocation", 5)?;
+        let mut state = serializer.serialize_struct("Allocation", 7)?;
         state.serialize_field("address", &self.address)?;
         state.serialize_field("size", &self.size)?;
         state.serialize_field("callstack", &*self.callstack)?;
         state.serialize_field("timestamp", &self.timestamp)?;
         state.serialize_field("real_timestamp", &self.real_timestamp)?;
+        state.serialize_field("thread_id", &self.thread_id)?;
+        state.serialize_field("cycle_counter", &self.cycle_counter)?;
         state.end()
     }
 }
@@ -285,7 +546,7 @@ impl Serialize for Allocation {
 impl<'de> Deserialize<'de> for Allocation {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where D: Deserializer<'de> {
-        enum Field { Address, Size, Callstack, Timestamp, RealTimestamp }
+        enum Field { Address, Size, Callstack, Timestamp, RealTimestamp, ThreadId, CycleCounter }
 
         impl<'de> Deserialize<'de> for Field {
             fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -296,7 +557,7 @@ impl<'de> Deserialize<'de> for Allocation {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
-                        formatter.write_str("Address, Size, Callstack, Timestamp, RealTimestamp")
+                        formatter.write_str("Address, Size, Callstack, Timestamp, RealTimestamp, ThreadId, CycleCounter")
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -307,6 +568,8 @@ impl<'de> Deserialize<'de> for Allocation {
                             "callstack" => Ok(Field::Callstack),
                             "timestamp" => Ok(Field::Timestamp),
                             "real_timestamp" => Ok(Field::RealTimestamp),
+                            "thread_id" => Ok(Field::ThreadId),
+                            "cycle_counter" => Ok(Field::CycleCounter),
                             _ => Err(serde::de::Error::unknown_field(value, FIELDS)),
                         }
                     }
@@ -335,7 +598,13 @@ impl<'de> Deserialize<'de> for Allocation {
                     .ok_or_else(|| serde::de::Error::invalid_length(3, &self))?;
                 let real_timestamp = seq.next_element()?
                     .ok_or_else(|| serde::de::Error::invalid_length(4, &self))?;
-                Ok(Allocation::new(address, size, Arc::new(callstack), timestamp, real_timestamp))
+                // Older serialised updates do not have a thread ID or cycle counter reading
+                let thread_id = seq.next_element()?.unwrap_or(None);
+                let cycle_counter = seq.next_element()?.unwrap_or(None);
+                let mut update = Allocation::new(address, size, Arc::new(callstack), timestamp, real_timestamp);
+                update.set_thread_id(thread_id);
+                update.set_cycle_counter(cycle_counter);
+                Ok(update)
             }
 
             fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error> where A: MapAccess<'de> {
@@ -344,6 +613,8 @@ impl<'de> Deserialize<'de> for Allocation {
                 let mut callstack = None;
                 let mut timestamp = None;
                 let mut real_timestamp = None;
+                let mut thread_id = None;
+                let mut cycle_counter = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -377,6 +648,18 @@ impl<'de> Deserialize<'de> for Allocation {
                             }
                             real_timestamp = Some(map.next_value()?);
                         }
+                        Field::ThreadId => {
+                            if thread_id.is_some() {
+                                return Err(serde::de::Error::duplicate_field("thread_id"));
+                            }
+                            thread_id = Some(map.next_value()?);
+                        }
+                        Field::CycleCounter => {
+                            if cycle_counter.is_some() {
+                                return Err(serde::de::Error::duplicate_field("cycle_counter"));
+                            }
+                            cycle_counter = Some(map.next_value()?);
+                        }
                     }
                 }
                 let address = address.ok_or_else(|| serde::de::Error::missing_field("address"))?;
@@ -384,23 +667,28 @@ impl<'de> Deserialize<'de> for Allocation {
                 let callstack = callstack.ok_or_else(|| serde::de::Error::missing_field("callstack"))?;
                 let timestamp = timestamp.ok_or_else(|| serde::de::Error::missing_field("timestamp"))?;
                 let real_timestamp = real_timestamp.ok_or_else(|| serde::de::Error::missing_field("real_timestamp"))?;
-                Ok(Allocation::new(address, size, Arc::new(callstack), timestamp, real_timestamp))
+                let mut update = Allocation::new(address, size, Arc::new(callstack), timestamp, real_timestamp);
+                update.set_thread_id(thread_id.unwrap_or(None));
+                update.set_cycle_counter(cycle_counter.unwrap_or(None));
+                Ok(update)
             }
         }
 
-        const FIELDS: &[&str] = &["address", "size", "callstack", "timestamp", "real_timestamp"];
+        const FIELDS: &[&str] = &["address", "size", "callstack", "timestamp", "real_timestamp", "thread_id", "cycle_counter"];
         deserializer.deserialize_struct("Allocation", FIELDS, AllocationVisitor)
     }
 }
 
 impl Serialize for Free {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        let mut state = serializer.serialize_struct("Free", 5)?;
+        let mut state = serializer.serialize_struct("Free", 7)?;
         state.serialize_field("address", &self.address)?;
         state.serialize_field("size", &self.size)?;
         state.serialize_field("callstack", &*self.callstack)?;
         state.serialize_field("timestamp", &self.timestamp)?;
         state.serialize_field("real_timestamp", &self.real_timestamp)?;
+        state.serialize_field("thread_id", &self.thread_id)?;
+        state.serialize_field("cycle_counter", &self.cycle_counter)?;
         state.end()
     }
 }
@@ -408,7 +696,7 @@ impl Serialize for Free {
 impl<'de> Deserialize<'de> for Free {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where D: Deserializer<'de> {
-        enum Field { Address, Size, Callstack, Timestamp, RealTimestamp }
+        enum Field { Address, Size, Callstack, Timestamp, RealTimestamp, ThreadId, CycleCounter }
 
         impl<'de> Deserialize<'de> for Field {
             fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -419,7 +707,7 @@ impl<'de> Deserialize<'de> for Free {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
-                        formatter.write_str("Address, Size, Callstack, Timestamp, RealTimestamp")
+                        formatter.write_str("Address, Size, Callstack, Timestamp, RealTimestamp, ThreadId, CycleCounter")
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -430,6 +718,8 @@ impl<'de> Deserialize<'de> for Free {
                             "callstack" => Ok(Field::Callstack),
                             "timestamp" => Ok(Field::Timestamp),
                             "real_timestamp" => Ok(Field::RealTimestamp),
+                            "thread_id" => Ok(Field::ThreadId),
+                            "cycle_counter" => Ok(Field::CycleCounter),
                             _ => Err(serde::de::Error::unknown_field(value, FIELDS)),
                         }
                     }
@@ -458,7 +748,13 @@ impl<'de> Deserialize<'de> for Free {
                     .ok_or_else(|| serde::de::Error::invalid_length(3, &self))?;
                 let real_timestamp = seq.next_element()?
                     .ok_or_else(|| serde::de::Error::invalid_length(4, &self))?;
-                Ok(Free::new(address, size, Arc::new(callstack), timestamp, real_timestamp))
+                // Older serialised updates do not have a thread ID or cycle counter reading
+                let thread_id = seq.next_element()?.unwrap_or(None);
+                let cycle_counter = seq.next_element()?.unwrap_or(None);
+                let mut update = Free::new(address, size, Arc::new(callstack), timestamp, real_timestamp);
+                update.set_thread_id(thread_id);
+                update.set_cycle_counter(cycle_counter);
+                Ok(update)
             }
 
             fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error> where A: MapAccess<'de> {
@@ -467,6 +763,8 @@ impl<'de> Deserialize<'de> for Free {
                 let mut callstack = None;
                 let mut timestamp = None;
                 let mut real_timestamp = None;
+                let mut thread_id = None;
+                let mut cycle_counter = None;
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -500,6 +798,18 @@ impl<'de> Deserialize<'de> for Free {
                             }
                             real_timestamp = Some(map.next_value()?);
                         }
+                        Field::ThreadId => {
+                            if thread_id.is_some() {
+                                return Err(serde::de::Error::duplicate_field("thread_id"));
+                            }
+                            thread_id = Some(map.next_value()?);
+                        }
+                        Field::CycleCounter => {
+                            if cycle_counter.is_some() {
+                                return Err(serde::de::Error::duplicate_field("cycle_counter"));
+                            }
+                            cycle_counter = Some(map.next_value()?);
+                        }
                     }
                 }
                 let address = address.ok_or_else(|| serde::de::Error::missing_field("address"))?;
@@ -507,11 +817,188 @@ impl<'de> Deserialize<'de> for Free {
                 let callstack = callstack.ok_or_else(|| serde::de::Error::missing_field("callstack"))?;
                 let timestamp = timestamp.ok_or_else(|| serde::de::Error::missing_field("timestamp"))?;
                 let real_timestamp = real_timestamp.ok_or_else(|| serde::de::Error::missing_field("real_timestamp"))?;
-                Ok(Free::new(address, size, Arc::new(callstack), timestamp, real_timestamp))
+                let mut update = Free::new(address, size, Arc::new(callstack), timestamp, real_timestamp);
+                update.set_thread_id(thread_id.unwrap_or(None));
+                update.set_cycle_counter(cycle_counter.unwrap_or(None));
+                Ok(update)
             }
         }
 
-        const FIELDS: &[&str] = &["address", "size", "callstack", "timestamp", "real_timestamp"];
+        const FIELDS: &[&str] = &["address", "size", "callstack", "timestamp", "real_timestamp", "thread_id", "cycle_counter"];
         deserializer.deserialize_struct("Free", FIELDS, FreeVisitor)
     }
-}
\ No newline at end of file
+}
+
+impl Serialize for Reallocation {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut state = serializer.serialize_struct("Reallocation", 9)?;
+        state.serialize_field("address", &self.address)?;
+        state.serialize_field("size", &self.size)?;
+        state.serialize_field("old_address", &self.old_address)?;
+        state.serialize_field("old_size", &self.old_size)?;
+        state.serialize_field("callstack", &*self.callstack)?;
+        state.serialize_field("timestamp", &self.timestamp)?;
+        state.serialize_field("real_timestamp", &self.real_timestamp)?;
+        state.serialize_field("thread_id", &self.thread_id)?;
+        state.serialize_field("cycle_counter", &self.cycle_counter)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Reallocation {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de> {
+        enum Field { Address, Size, OldAddress, OldSize, Callstack, Timestamp, RealTimestamp, ThreadId, CycleCounter }
+
+        impl<'de> Deserialize<'de> for Field {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where D: Deserializer<'de> {
+                struct FieldVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for FieldVisitor {
+                    type Value = Field;
+
+                    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                        formatter.write_str("Address, Size, OldAddress, OldSize, Callstack, Timestamp, RealTimestamp, ThreadId, CycleCounter")
+                    }
+
+                    fn visit_str<E>(self, value: &str) -> Result<Field, E>
+                        where E: serde::de::Error, {
+                        match value {
+                            "address" => Ok(Field::Address),
+                            "size" => Ok(Field::Size),
+                            "old_address" => Ok(Field::OldAddress),
+                            "old_size" => Ok(Field::OldSize),
+                            "callstack" => Ok(Field::Callstack),
+                            "timestamp" => Ok(Field::Timestamp),
+                            "real_timestamp" => Ok(Field::RealTimestamp),
+                            "thread_id" => Ok(Field::ThreadId),
+                            "cycle_counter" => Ok(Field::CycleCounter),
+                            _ => Err(serde::de::Error::unknown_field(value, FIELDS)),
+                        }
+                    }
+                }
+                deserializer.deserialize_identifier(FieldVisitor)
+            }
+        }
+
+        struct ReallocationVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ReallocationVisitor {
+            type Value = Reallocation;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("struct Reallocation")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error> where A: SeqAccess<'de> {
+                let address = seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let size = seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                let old_address = seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(2, &self))?;
+                let old_size = seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(3, &self))?;
+                let callstack = seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(4, &self))?;
+                let timestamp = seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(5, &self))?;
+                let real_timestamp = seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(6, &self))?;
+                // Older serialised updates do not have a thread ID or cycle counter reading
+                let thread_id = seq.next_element()?.unwrap_or(None);
+                let cycle_counter = seq.next_element()?.unwrap_or(None);
+                let mut update = Reallocation::new(address, size, old_address, old_size, Arc::new(callstack), timestamp, real_timestamp);
+                update.set_thread_id(thread_id);
+                update.set_cycle_counter(cycle_counter);
+                Ok(update)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error> where A: MapAccess<'de> {
+                let mut address = None;
+                let mut size = None;
+                let mut old_address = None;
+                let mut old_size = None;
+                let mut callstack = None;
+                let mut timestamp = None;
+                let mut real_timestamp = None;
+                let mut thread_id = None;
+                let mut cycle_counter = None;
+
+                while let Some(key) = map.next_key()? {
+                    match key {
+                        Field::Address => {
+                            if address.is_some() {
+                                return Err(serde::de::Error::duplicate_field("address"));
+                            }
+                            address = Some(map.next_value()?);
+                        }
+                        Field::Size => {
+                            if size.is_some() {
+                                return Err(serde::de::Error::duplicate_field("size"));
+                            }
+                            size = Some(map.next_value()?);
+                        }
+                        Field::OldAddress => {
+                            if old_address.is_some() {
+                                return Err(serde::de::Error::duplicate_field("old_address"));
+                            }
+                            old_address = Some(map.next_value()?);
+                        }
+                        Field::OldSize => {
+                            if old_size.is_some() {
+                                return Err(serde::de::Error::duplicate_field("old_size"));
+                            }
+                            old_size = Some(map.next_value()?);
+                        }
+                        Field::Callstack => {
+                            if callstack.is_some() {
+                                return Err(serde::de::Error::duplicate_field("callstack"));
+                            }
+                            callstack = Some(map.next_value()?);
+                        }
+                        Field::Timestamp => {
+                            if timestamp.is_some() {
+                                return Err(serde::de::Error::duplicate_field("timestamp"));
+                            }
+                            timestamp = Some(map.next_value()?);
+                        }
+                        Field::RealTimestamp => {
+                            if real_timestamp.is_some() {
+                                return Err(serde::de::Error::duplicate_field("real_timestamp"));
+                            }
+                            real_timestamp = Some(map.next_value()?);
+                        }
+                        Field::ThreadId => {
+                            if thread_id.is_some() {
+                                return Err(serde::de::Error::duplicate_field("thread_id"));
+                            }
+                            thread_id = Some(map.next_value()?);
+                        }
+                        Field::CycleCounter => {
+                            if cycle_counter.is_some() {
+                                return Err(serde::de::Error::duplicate_field("cycle_counter"));
+                            }
+                            cycle_counter = Some(map.next_value()?);
+                        }
+                    }
+                }
+                let address = address.ok_or_else(|| serde::de::Error::missing_field("address"))?;
+                let size = size.ok_or_else(|| serde::de::Error::missing_field("size"))?;
+                let old_address = old_address.ok_or_else(|| serde::de::Error::missing_field("old_address"))?;
+                let old_size = old_size.ok_or_else(|| serde::de::Error::missing_field("old_size"))?;
+                let callstack = callstack.ok_or_else(|| serde::de::Error::missing_field("callstack"))?;
+                let timestamp = timestamp.ok_or_else(|| serde::de::Error::missing_field("timestamp"))?;
+                let real_timestamp = real_timestamp.ok_or_else(|| serde::de::Error::missing_field("real_timestamp"))?;
+                let mut update = Reallocation::new(address, size, old_address, old_size, Arc::new(callstack), timestamp, real_timestamp);
+                update.set_thread_id(thread_id.unwrap_or(None));
+                update.set_cycle_counter(cycle_counter.unwrap_or(None));
+                Ok(update)
+            }
+        }
+
+        const FIELDS: &[&str] = &["address", "size", "old_address", "old_size", "callstack", "timestamp", "real_timestamp", "thread_id", "cycle_counter"];
+        deserializer.deserialize_struct("Reallocation", FIELDS, ReallocationVisitor)
+    }
+}
@@ -10,13 +10,13 @@ pub struct MemoryUsage {
     free_blocks: usize,
     free_segment_fragmentation: u128,
     latest_operation: usize,
-    timestamp_microseconds: u64,
+    timestamp_microseconds: f64,
     timestamp: u64
 }
 
 impl MemoryUsage {
     pub fn new(memory_used_absolute: i128, distinct_blocks: u128, largest_free_block: (usize, usize, usize), 
-               free_blocks: usize, free_segment_fragmentation: u128, latest_operation: usize, timestamp_microseconds: u64, 
+               free_blocks: usize, free_segment_fragmentation: u128, latest_operation: usize, timestamp_microseconds: f64, 
                timestamp: u64) -> MemoryUsage {
         MemoryUsage {
             memory_used_absolute,
@@ -81,9 +81,9 @@ impl MemoryUsage {
         self.free_segment_fragmentation = free_segment_fragmentation
     }
     
-    pub fn get_timestamp_microseconds(&self) -> u64 { self.timestamp_microseconds }
+    pub fn get_timestamp_microseconds(&self) -> f64 { self.timestamp_microseconds }
     
-    pub fn set_timestamp_microseconds(&mut self, timestamp_microseconds: u64) {
+    pub fn set_timestamp_microseconds(&mut self, timestamp_microseconds: f64) {
         self.timestamp_microseconds = timestamp_microseconds;
     }
 }
@@ -114,9 +114,9 @@ mod tests {
 
     #[test]
     fn ordering_test() {
-        let base = MemoryUsage::new(128, 4, (0, 0, 0), 0, 0, 4, 0, 0);
-        let larger = MemoryUsage::new(256, 3, (0, 0, 0), 0, 0, 3, 0, 0);
-        let equal = MemoryUsage::new(128, 32, (0, 0, 0), 0, 0, 32, 0, 0);
+        let base = MemoryUsage::new(128, 4, (0, 0, 0), 0, 0, 4, 0.0, 0);
+        let larger = MemoryUsage::new(256, 3, (0, 0, 0), 0, 0, 3, 0.0, 0);
+        let equal = MemoryUsage::new(128, 32, (0, 0, 0), 0, 0, 32, 0.0, 0);
         assert_eq!(base, equal);
         assert!(base < larger);
         assert!(equal < larger);
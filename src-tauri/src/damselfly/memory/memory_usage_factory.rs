@@ -1,10 +1,10 @@
 //! Generates MemoryUsages.
 use std::cmp::{max};
 use owo_colors::OwoColorize;
+use crate::damselfly::damselfly_error::DamselflyError;
 use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
 use crate::damselfly::memory::memory_usage::MemoryUsage;
 use crate::damselfly::memory::memory_usage_stats::MemoryUsageStats;
-use crate::damselfly::memory::utility::Utility;
 use crate::damselfly::update_interval::distinct_block_counter::DistinctBlockCounter;
 
 pub struct MemoryUsageFactory {
@@ -45,7 +45,10 @@ impl MemoryUsageFactory {
         self.memory_updates = updates;
     }
 
-    pub fn calculate_usage_stats(&mut self) -> MemoryUsageStats {
+    /// Computes the usage stats after each update.
+    ///
+    /// returns: MemoryUsageStats, or BadTimestamp if an update's realtime timestamp is invalid
+    pub fn calculate_usage_stats(&mut self) -> Result<MemoryUsageStats, DamselflyError> {
         let mut current_usage = 0;
         let mut max_usage = 0;
         let mut max_free_blocks: u128 = 0;
@@ -57,7 +60,7 @@ impl MemoryUsageFactory {
         let mut max_distinct_blocks: u128 = 0;
 
         for (index, update) in self.memory_updates.iter().enumerate() {
-            println!("Processing usage stats: {}", update.cyan());
+            eprintln!("Processing usage stats: {}", update.cyan());
             current_usage += Self::get_total_usage_delta(update);
             max_usage = max(max_usage, current_usage);
             distinct_block_counter.push_update(update);
@@ -65,7 +68,7 @@ impl MemoryUsageFactory {
             let free_blocks = distinct_block_counter.get_free_blocks();
             let largest_free_block = distinct_block_counter.get_largest_free_block();
             let free_segment_fragmentation = distinct_block_counter.get_free_segment_fragmentation();
-            let real_timestamp_microseconds = Utility::convert_to_microseconds(update.get_real_timestamp());
+            let real_timestamp_microseconds = update.get_real_timestamp_microseconds()?;
             max_distinct_blocks = max(max_distinct_blocks, distinct_blocks);
             max_free_blocks = max(max_free_blocks, free_blocks.len() as u128);
             max_free_segment_fragmentation = max(max_free_segment_fragmentation, free_segment_fragmentation);
@@ -74,8 +77,8 @@ impl MemoryUsageFactory {
             memory_usages.push(MemoryUsage::new(current_usage, distinct_blocks, largest_free_block, free_blocks.len(), free_segment_fragmentation, index, real_timestamp_microseconds, self.counter));
             self.counter += 1;
         }
-        MemoryUsageStats::new(memory_usages, max_usage, max_free_blocks, max_distinct_blocks,
-                              max_free_segment_fragmentation, max_largest_free_block as u128)
+        Ok(MemoryUsageStats::new(memory_usages, max_usage, max_free_blocks, max_distinct_blocks,
+                                 max_free_segment_fragmentation, max_largest_free_block as u128))
     }
 
     fn get_total_usage_delta(memory_update: &MemoryUpdateType) -> i128 {
@@ -86,6 +89,9 @@ impl MemoryUsageFactory {
             MemoryUpdateType::Free(free) => {
                 -(free.get_absolute_size() as i128)
             }
+            MemoryUpdateType::Reallocation(reallocation) => {
+                reallocation.get_absolute_size() as i128 - reallocation.get_old_size() as i128
+            }
         }
     }
 }
@@ -95,15 +101,16 @@ mod tests {
     use std::sync::Arc;
     use crate::damselfly::memory::memory_parsers::{MemoryParser, MemorySysTraceParser};
     use crate::damselfly::consts::{TEST_BINARY_PATH, TEST_LOG};
-    use crate::damselfly::memory::memory_update::{Allocation, MemoryUpdateType};
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::memory_update::{Allocation, MemoryUpdateType, Reallocation};
     use crate::damselfly::memory::memory_usage_factory::MemoryUsageFactory;
     use crate::damselfly::memory::memory_usage_stats::MemoryUsageStats;
 
     fn initialise_test_log() -> MemoryUsageStats {
         let mst_parser = MemorySysTraceParser::new();
-        let updates = mst_parser.parse_log_directly(TEST_LOG, TEST_BINARY_PATH).memory_updates;
+        let updates = mst_parser.parse_log_directly(TEST_LOG, TEST_BINARY_PATH).unwrap().memory_updates;
         let mut memory_usage_factory = MemoryUsageFactory::new(updates, 0, 0, usize::MIN, usize::MAX);
-        memory_usage_factory.calculate_usage_stats()
+        memory_usage_factory.calculate_usage_stats().unwrap()
     }
 
     #[test]
@@ -149,7 +156,7 @@ mod tests {
         let second_update = MemoryUpdateType::Allocation(Allocation::new(12, 8, Arc::new(String::new()), 0, String::from("0001.677 s")));
         let usage_stats =
             MemoryUsageFactory::new(vec![first_update, second_update], 0, 0, usize::MIN, usize::MAX)
-                .calculate_usage_stats();
+                .calculate_usage_stats().unwrap();
         assert_eq!(usage_stats.get_max_distinct_blocks(), 2);
     }
 
@@ -159,7 +166,7 @@ mod tests {
         let second_update = MemoryUpdateType::Allocation(Allocation::new(12, 8, Arc::new(String::new()), 0, String::from("0001.677 s")));
         let usage_stats =
             MemoryUsageFactory::new(vec![first_update, second_update], 0, 4, usize::MIN, usize::MAX)
-                .calculate_usage_stats();
+                .calculate_usage_stats().unwrap();
         assert_eq!(usage_stats.get_max_distinct_blocks(), 1);
     }
 
@@ -169,7 +176,7 @@ mod tests {
         let second_update = MemoryUpdateType::Allocation(Allocation::new(12, 8, Arc::new(String::new()), 0, String::from("0001.677 s")));
         let usage_stats =
             MemoryUsageFactory::new(vec![first_update, second_update], 0, 8, usize::MIN, usize::MAX)
-                .calculate_usage_stats();
+                .calculate_usage_stats().unwrap();
         assert_eq!(usage_stats.get_max_distinct_blocks(), 2);
     }
 
@@ -180,12 +187,37 @@ mod tests {
         let third_update = MemoryUpdateType::Allocation(Allocation::new(32, 8, Arc::new(String::new()), 2, String::from("0001.678 s")));
         let usage_stats =
             MemoryUsageFactory::new(vec![first_update.clone(), second_update.clone(), third_update.clone()], 2, 2, usize::MIN, usize::MAX)
-                .calculate_usage_stats();
+                .calculate_usage_stats().unwrap();
         assert_eq!(usage_stats.get_max_distinct_blocks(), 1);
 
         let usage_stats =
             MemoryUsageFactory::new(vec![first_update, second_update, third_update], 4, 0, usize::MIN, usize::MAX)
-                .calculate_usage_stats();
+                .calculate_usage_stats().unwrap();
         assert_eq!(usage_stats.get_max_distinct_blocks(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn calculate_usage_with_realloc_test() {
+        let first_update = MemoryUpdateType::Allocation(Allocation::new(0, 8, Arc::new(String::new()), 0, String::from("0001.676 s")));
+        let second_update = MemoryUpdateType::Reallocation(Reallocation::new(0, 20, 0, 8, Arc::new(String::new()), 1, String::from("0001.677 s")));
+        let third_update = MemoryUpdateType::Reallocation(Reallocation::new(32, 4, 0, 20, Arc::new(String::new()), 2, String::from("0001.678 s")));
+        let usage_stats =
+            MemoryUsageFactory::new(vec![first_update, second_update, third_update], 0, 0, usize::MIN, usize::MAX)
+                .calculate_usage_stats().unwrap();
+        let memory_usages = usage_stats.get_memory_usages();
+        assert_eq!(usage_stats.get_max_usage(), 20);
+        assert_eq!(memory_usages[1].get_memory_used_absolute(), 20);
+        assert_eq!(memory_usages[2].get_memory_used_absolute(), 4);
+        assert_eq!(memory_usages[2].get_distinct_blocks(), 1);
+    }
+
+    #[test]
+    fn calculate_usage_bad_timestamp_test() {
+        let first_update = MemoryUpdateType::Allocation(Allocation::new(0, 8, Arc::new(String::new()), 0, String::from("0001.676 s")));
+        let second_update = MemoryUpdateType::Allocation(Allocation::new(12, 8, Arc::new(String::new()), 1, String::from("not a time")));
+        let result =
+            MemoryUsageFactory::new(vec![first_update, second_update], 0, 0, usize::MIN, usize::MAX)
+                .calculate_usage_stats();
+        assert!(matches!(result, Err(DamselflyError::BadTimestamp { .. })));
+    }
+}
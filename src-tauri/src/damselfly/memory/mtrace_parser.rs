@@ -0,0 +1,469 @@
+//! Parser for logs written by glibc's mtrace (set MALLOC_TRACE to the log path and call mtrace()
+//! in the program), so that host builds can be inspected too.
+//!
+//! Each line records one operation, optionally preceded by the caller:
+//!
+//! @ ./prog:[0x4005b6] + 0x1c9f460 0x64     malloc of 0x64 bytes
+//! @ ./prog:(main+0x3c)[0x4005c4] - 0x1c9f460     free
+//! @ ./prog:[0x4005d2] < 0x1c9f4d0     realloc, old address...
+//! @ ./prog:[0x4005d2] > 0x1c9f540 0xc8     ...followed by the new address and size
+//!
+//! Callers in the traced program are symbolised with its debuginfo, just like SysTrace stack
+//! traces. Newer versions of glibc log callers as offsets from where the program was loaded, so
+//! these are converted back to addresses. Callers in other objects (e.g. libc) are shown by the
+//! function name and offset logged by mtrace.
+//! mtrace logs have no timestamps or thread IDs, so each operation's realtime timestamp is its
+//! operation number in microseconds.
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::log_lines::LogLines;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::memory_parsers::{LenientParser, MemoryParser, MemorySysTraceParser, ParseResults};
+use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+use crate::damselfly::memory::symbol_options::SymbolOptions;
+
+/// Parser for glibc mtrace logs.
+#[derive(Default)]
+pub struct MemoryMtraceParser {
+    time: usize,
+    allocation_sizes: HashMap<usize, usize>,
+    // (old_address, callstack) of a realloc whose new address has not been logged yet
+    pending_reallocation: Option<(usize, String)>,
+    // Caller address, as logged -> symbol
+    symbols: HashMap<usize, String>,
+    binary_name: String,
+    line_number: usize,
+    lenient: bool,
+    diagnostics: Vec<ParseDiagnostic>,
+    symbol_options: SymbolOptions,
+}
+
+impl MemoryParser for MemoryMtraceParser {
+    /// Parses an mtrace log into a Vec of MemoryUpdateTypes.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw log file.
+    /// * `binary_path`: File path to the traced program for debuginfo.
+    ///
+    /// returns: ParseResults
+    fn parse_log_directly(self, log: &str, binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_log_contents(|| Ok(LogLines::new(log.as_bytes(), "[log]")), binary_path)
+    }
+
+    /// Parses an mtrace log using its file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: File path to log
+    /// * `binary_path`: File path to the traced program for debuginfo.
+    ///
+    /// returns: ParseResults
+    fn parse_log(self, log_path: &str, binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemoryMtraceParser::parse_log]: log: {log_path} binary: {binary_path}");
+        let open_log = || {
+            Ok(LogLines::new(LogReader::open(log_path)?, log_path))
+        };
+        self.parse_log_contents(open_log, binary_path)
+    }
+}
+
+impl LenientParser for MemoryMtraceParser {
+    fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>> {
+        self.lenient.then_some(&mut self.diagnostics)
+    }
+}
+
+impl MemoryMtraceParser {
+    pub fn new() -> MemoryMtraceParser {
+        MemoryMtraceParser::default()
+    }
+
+    /// Constructs a parser that skips malformed records instead of failing. Each skipped record is
+    /// reported in the diagnostics of the ParseResults.
+    pub fn new_lenient() -> MemoryMtraceParser {
+        MemoryMtraceParser {
+            lenient: true,
+            ..Self::new()
+        }
+    }
+
+    /// Sets where separate debuginfo files are searched for and how source paths are remapped
+    /// when symbolising callers.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol_options`: Debuginfo search directories and source path remappings.
+    ///
+    /// returns: MemoryMtraceParser
+    pub fn with_symbol_options(mut self, symbol_options: SymbolOptions) -> MemoryMtraceParser {
+        self.symbol_options = symbol_options;
+        self
+    }
+
+    /// Parses a raw log, consuming itself and returning parse results.
+    /// The log is read twice: once to collect the caller addresses that need symbolising, then
+    /// again to parse memory operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `open_log`: Opens a new reader over the log. Called once per pass.
+    /// * `binary_path`: File path to the traced program for debuginfo.
+    ///
+    /// returns: ParseResults
+    fn parse_log_contents<R: BufRead>(
+        mut self,
+        open_log: impl Fn() -> Result<LogLines<R>, DamselflyError>,
+        binary_path: &str
+    ) -> Result<ParseResults, DamselflyError> {
+        let mut log_lines = open_log()?;
+        self.parse_symbols(log_lines.by_ref(), binary_path)?;
+        log_lines.check_error()?;
+
+        let mut log_lines = open_log()?;
+        let memory_updates = self.parse_memory_updates(log_lines.by_ref())?;
+        log_lines.check_error()?;
+        Ok(ParseResults::from_parsed_log(memory_updates, MemoryPoolList::default(), self.diagnostics))
+    }
+
+    /// Finds the symbols of every caller address in the log.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the log.
+    /// * `binary_path`: File path to the traced program for debuginfo.
+    ///
+    /// returns: nothing, as the symbols are stored as a struct field.
+    pub fn parse_symbols<S: AsRef<str>>(&mut self, log_lines: impl Iterator<Item = S>, binary_path: &str) -> Result<(), DamselflyError> {
+        self.binary_name = Self::get_file_name(binary_path).to_string();
+        let logged_addresses: Vec<usize> = log_lines
+            .filter_map(|line| {
+                let (caller, _) = Self::split_caller(line.as_ref());
+                caller
+                    .filter(|caller| self.is_caller_in_binary(caller))
+                    .and_then(Self::parse_caller_address)
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        // Logs without callers in the program do not need the binary
+        if logged_addresses.is_empty() {
+            return Ok(());
+        }
+        let binary_file = MemorySysTraceParser::open_binary(binary_path)?;
+        let object = MemorySysTraceParser::parse_binary(&binary_file, binary_path)?;
+        // The image base is 0 for position independent executables
+        let image_base = MemorySysTraceParser::get_image_base(&object);
+        let addresses: Vec<usize> = logged_addresses
+            .iter()
+            .map(|&address| if address >= image_base { address } else { address + image_base })
+            .collect();
+        let symbols = MemorySysTraceParser::symbolise_in_binary(&addresses, binary_path, &object, &self.symbol_options)?;
+        self.symbols = logged_addresses.into_iter().zip(symbols).collect();
+        Ok(())
+    }
+
+    fn get_file_name(path: &str) -> &str {
+        Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path)
+    }
+
+    /// Checks if a caller is in the traced program rather than a shared library. Callers logged
+    /// without an object are assumed to be in the program.
+    ///
+    /// # Arguments
+    ///
+    /// * `caller`: Caller from the log, e.g. ./prog:(main+0x3c)[0x4005c4].
+    ///
+    /// returns: bool
+    fn is_caller_in_binary(&self, caller: &str) -> bool {
+        let object_end = caller.find(":(").or_else(|| caller.rfind(":["));
+        match object_end {
+            Some(object_end) => Self::get_file_name(&caller[..object_end]) == self.binary_name,
+            None => true,
+        }
+    }
+
+    /// Parses the memory operations in a log. Symbols should already have been loaded with
+    /// parse_symbols, or callers will be shown by their function name only.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the log.
+    ///
+    /// returns: The memory operations in the log, in order.
+    pub fn parse_memory_updates<S: AsRef<str>>(&mut self, log_lines: impl Iterator<Item = S>) -> Result<Vec<MemoryUpdateType>, DamselflyError> {
+        let mut memory_updates = Vec::new();
+        for line in log_lines {
+            let line = line.as_ref();
+            self.line_number += 1;
+            match self.line_to_memory_update(line) {
+                Ok(Some(memory_update)) => memory_updates.push(memory_update),
+                Ok(None) => {},
+                Err(error) => self.skip_or_fail(line, error)?,
+            }
+        }
+        if self.pending_reallocation.is_some() {
+            let error = self.malformed_record("log ends between the < and > records of a realloc");
+            self.skip_or_fail("", error)?;
+        }
+        Ok(memory_updates)
+    }
+
+    /// Parses a line of the log into a memory update.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the log.
+    ///
+    /// returns: The memory update, or None if the line does not change memory (a comment, a
+    /// failed realloc, or the first half of a realloc).
+    fn line_to_memory_update(&mut self, line: &str) -> Result<Option<MemoryUpdateType>, DamselflyError> {
+        let line = line.trim();
+        // "= Start" and "= End" mark where tracing was turned on and off
+        if line.is_empty() || line.starts_with('=') {
+            return Ok(None);
+        }
+        let (caller, operation) = Self::split_caller(line);
+        let callstack = caller.map(|caller| self.symbolise_caller(caller)).unwrap_or_default();
+        let split_operation = operation.split_whitespace().collect::<Vec<_>>();
+        let line_number = self.line_number;
+        let field = |index: usize, name: &str| {
+            split_operation.get(index)
+                .copied()
+                .ok_or_else(|| DamselflyError::MalformedRecord {
+                    line: line_number,
+                    reason: format!("{} record is missing its {name}", split_operation[0]),
+                })
+        };
+
+        let operation_type = *split_operation.first()
+            .ok_or_else(|| self.malformed_record("line has no operation"))?;
+        if let Some((old_address, _)) = &self.pending_reallocation {
+            if operation_type != ">" {
+                let old_address = *old_address;
+                self.pending_reallocation = None;
+                // Only the < record is dropped, this line is still parsed
+                let error = self.malformed_record(&format!("realloc of {old_address:#x} is missing its > record"));
+                self.skip_or_fail("", error)?;
+            }
+        }
+
+        let memory_update = match operation_type {
+            "+" => {
+                let address = self.parse_address(field(1, "address")?)?;
+                let size = self.parse_size(field(2, "size")?)?;
+                // A failed malloc is logged with a null address
+                if address == 0 {
+                    return Ok(None);
+                }
+                self.allocation_sizes.insert(address, size);
+                Allocation::new(address, size, Arc::new(callstack), self.time, self.real_timestamp()).wrap_in_enum()
+            }
+            "-" => {
+                let address = self.parse_address(field(1, "address")?)?;
+                let size = self.allocation_sizes.remove(&address).unwrap_or(0);
+                Free::new(address, size, Arc::new(callstack), self.time, self.real_timestamp()).wrap_in_enum()
+            }
+            "<" => {
+                let old_address = self.parse_address(field(1, "old address")?)?;
+                self.pending_reallocation = Some((old_address, callstack));
+                return Ok(None);
+            }
+            ">" => {
+                let (old_address, callstack) = self.pending_reallocation.take()
+                    .ok_or_else(|| self.malformed_record("> record is not preceded by a < record"))?;
+                let address = self.parse_address(field(1, "new address")?)?;
+                let size = self.parse_size(field(2, "size")?)?;
+                // As with frees, the log does not say how big the old allocation was
+                let old_size = self.allocation_sizes.remove(&old_address).unwrap_or(0);
+                self.allocation_sizes.insert(address, size);
+                Reallocation::new(address, size, old_address, old_size, Arc::new(callstack), self.time, self.real_timestamp()).wrap_in_enum()
+            }
+            // A failed realloc leaves the old allocation untouched
+            "!" => return Ok(None),
+            unknown => return Err(self.malformed_record(&format!("invalid operation type {unknown}"))),
+        };
+        self.time += 1;
+        Ok(Some(memory_update))
+    }
+
+    /// Splits the caller (e.g. ./prog:(main+0x3c)[0x4005c4]) from the operation in a line.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the log.
+    ///
+    /// returns: (caller, if the line has one, operation)
+    fn split_caller(line: &str) -> (Option<&str>, &str) {
+        let line = line.trim();
+        let Some(rest) = line.strip_prefix("@ ") else {
+            return (None, line);
+        };
+        match rest.find("] ") {
+            Some(end) => (Some(&rest[..=end]), &rest[end + 2..]),
+            None => (None, line),
+        }
+    }
+
+    /// Parses the return address of a caller, e.g. 0x4005c4 in ./prog:(main+0x3c)[0x4005c4].
+    fn parse_caller_address(caller: &str) -> Option<usize> {
+        let open_bracket_pos = caller.rfind('[')?;
+        let address = caller[open_bracket_pos + 1..].strip_suffix(']')?;
+        usize::from_str_radix(address.strip_prefix("0x")?, 16).ok()
+    }
+
+    /// Gets the symbol of a caller, falling back to the function name and offset logged by mtrace
+    /// if the debuginfo does not cover the caller (e.g. it is in a shared library).
+    ///
+    /// # Arguments
+    ///
+    /// * `caller`: Caller from the log, e.g. ./prog:(main+0x3c)[0x4005c4].
+    ///
+    /// returns: The caller's frames, one per line. Inlined functions have frames of their own.
+    fn symbolise_caller(&self, caller: &str) -> String {
+        let symbol = Some(caller)
+            .filter(|caller| self.is_caller_in_binary(caller))
+            .and_then(Self::parse_caller_address)
+            .and_then(|address| self.symbols.get(&address))
+            .filter(|symbol| *symbol != "[UNKNOWN SYMBOL]")
+            .cloned()
+            .or_else(|| {
+                let open_bracket_pos = caller.find('(')?;
+                let close_bracket_pos = caller.rfind(')')?;
+                caller.get(open_bracket_pos + 1..close_bracket_pos).map(str::to_string)
+            })
+            .unwrap_or_else(|| caller.to_string());
+        format!("{symbol}\n")
+    }
+
+    /// mtrace does not log times, so operations are spaced 1us apart.
+    fn real_timestamp(&self) -> String {
+        format!("{} us", self.time)
+    }
+
+    /// Parses a hex address, with a 0x prefix, from the line currently being parsed. Null
+    /// addresses are logged as (nil).
+    fn parse_address(&self, text: &str) -> Result<usize, DamselflyError> {
+        if text == "(nil)" {
+            return Ok(0);
+        }
+        text.strip_prefix("0x")
+            .and_then(|address| usize::from_str_radix(address, 16).ok())
+            .ok_or_else(|| DamselflyError::BadAddress { line: self.line_number, text: text.to_string() })
+    }
+
+    /// Parses a hex size, with a 0x prefix, from the line currently being parsed.
+    fn parse_size(&self, text: &str) -> Result<usize, DamselflyError> {
+        text.strip_prefix("0x")
+            .and_then(|size| usize::from_str_radix(size, 16).ok())
+            .ok_or_else(|| DamselflyError::BadSize { line: self.line_number, text: text.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::memory_parsers::MemoryParser;
+    use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
+    use crate::damselfly::memory::mtrace_parser::MemoryMtraceParser;
+
+    const TEST_LOG: &str = "= Start
+@ ./prog:[0x4005b6] + 0x1c9f460 0x64
+@ ./prog:(main+0x3c)[0x4005c4] + 0x1c9f4d0 0x10
+@ ./prog:[0x4005d2] < 0x1c9f4d0
+@ ./prog:[0x4005d2] > 0x1c9f540 0xc8
+@ /lib/x86_64-linux-gnu/libc.so.6:(clone+0x3f)[0x7f1c2a3b4c5d] - 0x1c9f460
+= End";
+
+    #[test]
+    fn parse_memory_updates_test() {
+        let mut parser = MemoryMtraceParser::new();
+        let updates = parser.parse_memory_updates(TEST_LOG.lines()).unwrap();
+        assert_eq!(updates.len(), 4);
+
+        let MemoryUpdateType::Allocation(allocation) = &updates[0] else { panic!("expected an allocation") };
+        assert_eq!(allocation.get_absolute_address(), 0x1c9f460);
+        assert_eq!(allocation.get_absolute_size(), 0x64);
+        assert_eq!(allocation.get_timestamp(), 0);
+        assert_eq!(allocation.get_real_timestamp(), "0 us");
+
+        assert_eq!(*updates[1].get_callstack(), "main+0x3c\n");
+
+        let MemoryUpdateType::Reallocation(reallocation) = &updates[2] else { panic!("expected a reallocation") };
+        assert_eq!(reallocation.get_old_address(), 0x1c9f4d0);
+        assert_eq!(reallocation.get_old_size(), 0x10);
+        assert_eq!(reallocation.get_absolute_address(), 0x1c9f540);
+        assert_eq!(reallocation.get_absolute_size(), 0xc8);
+        assert_eq!(reallocation.get_timestamp(), 2);
+
+        let MemoryUpdateType::Free(free) = &updates[3] else { panic!("expected a free") };
+        assert_eq!(free.get_absolute_address(), 0x1c9f460);
+        assert_eq!(free.get_absolute_size(), 0x64);
+        assert_eq!(*free.get_callstack(), "clone+0x3f\n");
+    }
+
+    #[test]
+    fn parse_memory_updates_symbols_test() {
+        let mut parser = MemoryMtraceParser::new();
+        parser.binary_name = String::from("prog");
+        parser.symbols.insert(0x4005b6, String::from("helper (util.h:4)\nmain (main.c:12)"));
+        // Callers in other objects at the same offset are not the same caller
+        parser.symbols.insert(0x7f1c2a3b4c5d, String::from("main (main.c:20)"));
+        let updates = parser.parse_memory_updates(TEST_LOG.lines()).unwrap();
+        assert_eq!(*updates[0].get_callstack(), "helper (util.h:4)\nmain (main.c:12)\n");
+        assert_eq!(*updates[2].get_callstack(), "./prog:[0x4005d2]\n");
+        assert_eq!(*updates[3].get_callstack(), "clone+0x3f\n");
+    }
+
+    #[test]
+    fn is_caller_in_binary_test() {
+        let mut parser = MemoryMtraceParser::new();
+        parser.binary_name = String::from("prog");
+        assert!(parser.is_caller_in_binary("./prog:[0x4005b6]"));
+        assert!(parser.is_caller_in_binary("/home/work/prog:(main+0x3c)[0x4005c4]"));
+        assert!(parser.is_caller_in_binary("[0x4005b6]"));
+        assert!(!parser.is_caller_in_binary("/lib/x86_64-linux-gnu/libc.so.6:(clone+0x3f)[0x7f1c2a3b4c5d]"));
+    }
+
+    #[test]
+    fn parse_memory_updates_no_caller_test() {
+        let log = "+ 0x1000 0x8\n@ ./prog:[0x4005b6] + (nil) 0x10\n! 0x1000 0x20\n- 0x1000";
+        let updates = MemoryMtraceParser::new().parse_memory_updates(log.lines()).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(*updates[0].get_callstack(), "");
+        assert_eq!(updates[1].get_absolute_size(), 8);
+
+        // The binary is not read if no callers are in it
+        let log = "@ /lib/x86_64-linux-gnu/libc.so.6:(malloc+0x1a)[0x7f1c2a3b4c5d] + 0x1000 0x8\n- 0x1000";
+        let parse_results = MemoryMtraceParser::new().parse_log_directly(log, "/nonexistent/prog").unwrap();
+        assert_eq!(parse_results.memory_updates.len(), 2);
+        assert_eq!(*parse_results.memory_updates[0].get_callstack(), "malloc+0x1a\n");
+    }
+
+    #[test]
+    fn parse_memory_updates_unpaired_realloc_test() {
+        let log = "< 0x1000\n+ 0x2000 0x8";
+        let error = MemoryMtraceParser::new().parse_memory_updates(log.lines()).unwrap_err();
+        assert!(matches!(error, DamselflyError::MalformedRecord { line: 2, .. }));
+
+        let mut parser = MemoryMtraceParser::new_lenient();
+        let memory_updates = parser.parse_memory_updates(log.lines()).unwrap();
+        assert_eq!(memory_updates.len(), 1);
+        assert_eq!(memory_updates[0].get_absolute_address(), 0x2000);
+        assert_eq!(parser.diagnostics.len(), 1);
+        assert_eq!(parser.diagnostics[0].get_line(), 2);
+
+        let log = "> 0x1000 0x8";
+        assert!(MemoryMtraceParser::new().parse_memory_updates(log.lines()).is_err());
+    }
+}
@@ -0,0 +1,379 @@
+//! Damselfly's own trace format, for other tools to write traces in and for saving traces parsed
+//! from any other format. It is JSON lines: a header on the first line, then one memory update
+//! per line, in the same JSON that is sent to the frontend.
+//!
+//! ```text
+//! {"format":"damselfly","version":1,"max_timestamp":3,"pools":[{"name":"heap","start":4096,"size":65536}],"metadata":{"source":"systrace"}}
+//! {"Allocation":{"address":4096,"size":64,"callstack":"main.c:12\n","timestamp":0,"real_timestamp":"1.500 s","thread_id":null}}
+//! {"Reallocation":{"address":8192,"size":128,"old_address":4096,"old_size":64,"callstack":"main.c:20\n","timestamp":1,"real_timestamp":"1.750 s","thread_id":7}}
+//! {"Free":{"address":8192,"size":128,"callstack":"main.c:31\n","timestamp":2,"real_timestamp":"2 s","thread_id":7,"pool":"heap"}}
+//! ```
+//!
+//! See the README for the meaning of each field. Blank lines are ignored.
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::log_lines::LogLines;
+use crate::damselfly::memory::log_reader::LogReader;
+use crate::damselfly::memory::memory_parsers::{LenientParser, MemoryParser, ParseResults};
+use crate::damselfly::memory::memory_pool::MemoryPool;
+use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+use crate::damselfly::memory::memory_update::MemoryUpdateType;
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+use crate::damselfly::memory::pool_lifetime::PoolLifetime;
+use crate::damselfly::memory::utility::Utility;
+
+/// Value of the header's format field, which identifies the file as a Damselfly trace.
+pub const NATIVE_FORMAT_NAME: &str = "damselfly";
+/// Version of the format written by NativeTraceWriter. Readers reject newer versions.
+pub const NATIVE_FORMAT_VERSION: u32 = 1;
+
+/// The first line of a trace.
+#[derive(Serialize, Deserialize)]
+struct NativeHeader {
+    format: String,
+    version: u32,
+    // Defaults to the number of updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_timestamp: Option<u64>,
+    #[serde(default)]
+    pools: Vec<NativePool>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NativePool {
+    name: String,
+    start: usize,
+    size: usize,
+    // Only for pools that are created, resized or deleted during the trace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lifetime: Option<PoolLifetime>,
+}
+
+/// A memory update, and optionally the pool it belongs to.
+#[derive(Serialize, Deserialize)]
+struct NativeUpdate {
+    #[serde(flatten)]
+    update: MemoryUpdateType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool: Option<String>,
+}
+
+/// Parser for traces in Damselfly's own format.
+#[derive(Default)]
+pub struct MemoryNativeParser {
+    line_number: usize,
+    lenient: bool,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl MemoryParser for MemoryNativeParser {
+    /// Parses a trace into a Vec of MemoryUpdateTypes.
+    ///
+    /// # Arguments
+    ///
+    /// * `log`: Raw trace.
+    /// * `_binary_path`: Unused, as callstacks are stored already symbolised.
+    ///
+    /// returns: ParseResults
+    fn parse_log_directly(self, log: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        self.parse_log_contents(LogLines::new(log.as_bytes(), "[log]"))
+    }
+
+    /// Parses a trace using its file path.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_path`: File path to the trace.
+    /// * `_binary_path`: Unused, as callstacks are stored already symbolised.
+    ///
+    /// returns: ParseResults
+    fn parse_log(self, log_path: &str, _binary_path: &str) -> Result<ParseResults, DamselflyError> {
+        eprintln!("[MemoryNativeParser::parse_log]: log: {log_path}");
+        self.parse_log_contents(LogLines::new(LogReader::open(log_path)?, log_path))
+    }
+}
+
+impl LenientParser for MemoryNativeParser {
+    fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
+    fn get_lenient_diagnostics(&mut self) -> Option<&mut Vec<ParseDiagnostic>> {
+        self.lenient.then_some(&mut self.diagnostics)
+    }
+}
+
+impl MemoryNativeParser {
+    pub fn new() -> MemoryNativeParser {
+        MemoryNativeParser::default()
+    }
+
+    /// Constructs a parser that skips malformed updates instead of failing. Each skipped update is
+    /// reported in the diagnostics of the ParseResults. The header must still be valid.
+    pub fn new_lenient() -> MemoryNativeParser {
+        MemoryNativeParser {
+            lenient: true,
+            ..Self::new()
+        }
+    }
+
+    /// Checks if a line is the header of a trace in Damselfly's own format.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: First line of a log.
+    ///
+    /// returns: bool
+    pub fn is_header(line: &str) -> bool {
+        line.trim_start().starts_with('{')
+            && serde_json::from_str::<NativeHeader>(line).is_ok_and(|header| header.format == NATIVE_FORMAT_NAME)
+    }
+
+    /// Parses a whole trace, consuming itself and returning parse results.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_lines`: Lines of the trace.
+    ///
+    /// returns: ParseResults
+    fn parse_log_contents<R: BufRead>(mut self, mut log_lines: LogLines<R>) -> Result<ParseResults, DamselflyError> {
+        let header = self.parse_header(log_lines.by_ref())?;
+        let mut memory_updates = Vec::new();
+        let mut update_pools = Vec::new();
+        for line in log_lines.by_ref() {
+            self.line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.parse_update(&line) {
+                Ok((update, pool)) => {
+                    memory_updates.push(update);
+                    update_pools.push(pool);
+                }
+                Err(error) => self.skip_or_fail(&line, error)?,
+            }
+        }
+        log_lines.check_error()?;
+        let mut pool_list = MemoryPoolList::default();
+        for pool in header.pools {
+            let memory_pool = MemoryPool::new(pool.start, pool.size, pool.name);
+            match pool.lifetime {
+                Some(lifetime) => pool_list.add_pool_with_lifetime(memory_pool, lifetime),
+                None => pool_list.add_pool(memory_pool),
+            }
+        }
+        let mut parse_results = ParseResults::from_parsed_log(memory_updates, pool_list, self.diagnostics);
+        if let Some(max_timestamp) = header.max_timestamp {
+            parse_results = parse_results.with_max_timestamp(max_timestamp);
+        }
+        // Updates are only sorted into pools by name if every update names its pool
+        match update_pools.into_iter().collect::<Option<Vec<_>>>() {
+            Some(update_pools) if !update_pools.is_empty() => {
+                for pool in &update_pools {
+                    if !parse_results.pool_list.get_pools().iter().any(|listed_pool| listed_pool.get_name() == pool.as_str()) {
+                        return Err(DamselflyError::MalformedRecord { line: 1, reason: format!("pool {pool} is not listed in the header") });
+                    }
+                }
+                parse_results.with_update_pools(update_pools)
+            }
+            _ => Ok(parse_results),
+        }
+    }
+
+    /// Parses the header on the first line of a trace.
+    fn parse_header(&mut self, log_lines: &mut impl Iterator<Item = String>) -> Result<NativeHeader, DamselflyError> {
+        self.line_number += 1;
+        let line = log_lines.next()
+            .ok_or_else(|| self.malformed_record("trace is empty"))?;
+        let header: NativeHeader = serde_json::from_str(&line)
+            .map_err(|error| self.malformed_record(&format!("invalid header: {error}")))?;
+        if header.format != NATIVE_FORMAT_NAME {
+            return Err(self.malformed_record(&format!("header format is {}, expected {NATIVE_FORMAT_NAME}", header.format)));
+        }
+        if header.version > NATIVE_FORMAT_VERSION {
+            return Err(self.malformed_record(&format!(
+                "trace is version {}, but only versions up to {NATIVE_FORMAT_VERSION} can be read", header.version
+            )));
+        }
+        Ok(header)
+    }
+
+    /// Parses a memory update line.
+    ///
+    /// returns: The memory update, and the pool it belongs to if it names one.
+    fn parse_update(&self, line: &str) -> Result<(MemoryUpdateType, Option<Arc<String>>), DamselflyError> {
+        let native_update: NativeUpdate = serde_json::from_str(line)
+            .map_err(|error| self.malformed_record(&format!("invalid memory update: {error}")))?;
+        let real_timestamp = native_update.update.get_real_timestamp();
+        Utility::convert_to_microseconds(real_timestamp)
+            .map_err(|_| DamselflyError::BadTimestamp { line: self.line_number, text: real_timestamp.clone() })?;
+        Ok((native_update.update, native_update.pool.map(Arc::new)))
+    }
+}
+
+/// Writes parse results in Damselfly's own format, so that traces in any format can be saved and
+/// reopened without the original log or binary.
+pub struct NativeTraceWriter;
+
+impl NativeTraceWriter {
+    /// Writes a trace to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `parse_results`: The parsed trace.
+    /// * `metadata`: Free-form information about the trace, e.g. the log it was parsed from.
+    /// * `path`: Path to write the trace to. Any existing file is overwritten.
+    ///
+    /// returns: nothing, or the IO error that stopped the trace from being written
+    pub fn write_to_file(parse_results: &ParseResults, metadata: &BTreeMap<String, String>, path: &str) -> Result<(), DamselflyError> {
+        let io_error = |source| DamselflyError::Io { path: path.to_string(), source };
+        let file = File::create(path).map_err(io_error)?;
+        let mut writer = BufWriter::new(file);
+        Self::write(parse_results, metadata, &mut writer).map_err(io_error)?;
+        writer.flush().map_err(io_error)
+    }
+
+    /// Writes a trace to a writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `parse_results`: The parsed trace.
+    /// * `metadata`: Free-form information about the trace, e.g. the log it was parsed from.
+    /// * `writer`: Where to write the trace.
+    ///
+    /// returns: nothing, or the IO error that stopped the trace from being written
+    pub fn write(parse_results: &ParseResults, metadata: &BTreeMap<String, String>, mut writer: impl Write) -> std::io::Result<()> {
+        let mut pools: Vec<&MemoryPool> = parse_results.pool_list.get_pools().iter().collect();
+        pools.sort();
+        let header = NativeHeader {
+            format: NATIVE_FORMAT_NAME.to_string(),
+            version: NATIVE_FORMAT_VERSION,
+            max_timestamp: Some(parse_results.max_timestamp),
+            pools: pools.into_iter()
+                .map(|pool| NativePool {
+                    name: pool.get_name().to_string(),
+                    start: pool.get_start(),
+                    size: pool.get_size(),
+                    lifetime: parse_results.pool_list.get_lifetime(pool.get_name()).cloned(),
+                })
+                .collect(),
+            metadata: metadata.clone(),
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writeln!(writer)?;
+
+        for (index, update) in parse_results.memory_updates.iter().enumerate() {
+            let native_update = NativeUpdate {
+                update: update.clone(),
+                pool: parse_results.update_pools.get(index).map(|pool| pool.to_string()),
+            };
+            serde_json::to_writer(&mut writer, &native_update)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use crate::damselfly::damselfly_error::DamselflyError;
+    use crate::damselfly::memory::cycle_counter_clock::CycleCounterReading;
+    use crate::damselfly::memory::memory_parsers::{MemoryParser, ParseResults};
+    use crate::damselfly::memory::memory_pool::MemoryPool;
+    use crate::damselfly::memory::memory_pool_list::MemoryPoolList;
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+    use crate::damselfly::memory::native_format::{MemoryNativeParser, NativeTraceWriter};
+    use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind};
+
+    const TEST_TRACE: &str = r#"{"format":"damselfly","version":1,"pools":[{"name":"heap","start":4096,"size":65536}],"metadata":{"source":"test"}}
+{"Allocation":{"address":4096,"size":64,"callstack":"main.c:12\n","timestamp":0,"real_timestamp":"1.500 s","thread_id":null}}
+
+{"Reallocation":{"address":8192,"size":128,"old_address":4096,"old_size":64,"callstack":"main.c:20\n","timestamp":1,"real_timestamp":"1.750 s","thread_id":7}}
+{"Free":{"address":8192,"size":128,"callstack":"main.c:31\n","timestamp":2,"real_timestamp":"2 s","thread_id":7}}
+"#;
+
+    fn initialise_test_parse_results() -> ParseResults {
+        let mut pool_list = MemoryPoolList::default();
+        pool_list.add_pool(MemoryPool::new(0, 64, String::from("a")));
+        pool_list.add_pool(MemoryPool::new(0, 64, String::from("b")));
+        pool_list.add_pool_event("a", PoolEvent { kind: PoolEventKind::Delete, position: 1, real_timestamp: String::from("20 us") });
+        let mut free = Free::new(0, 16, Arc::new(String::from("f\n")), 1, String::from("20.500 us"));
+        free.set_thread_id(Some(3));
+        free.set_cycle_counter(Some(CycleCounterReading::new(2050, 20_500)));
+        let updates = vec![
+            Allocation::new(0, 16, Arc::new(String::from("a\n")), 0, String::from("10 us")).wrap_in_enum(),
+            free.wrap_in_enum(),
+            Reallocation::new(32, 32, 0, 0, Arc::new(String::new()), 2, String::from("30 us")).wrap_in_enum(),
+        ];
+        ParseResults::new(updates, pool_list, 3, Vec::new())
+            .with_update_pools(vec![Arc::new(String::from("a")), Arc::new(String::from("b")), Arc::new(String::from("b"))])
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_trace_test() {
+        let parse_results = MemoryNativeParser::new().parse_log_directly(TEST_TRACE, "").unwrap();
+        assert_eq!(parse_results.memory_updates.len(), 3);
+        assert_eq!(parse_results.max_timestamp, 3);
+        assert_eq!(parse_results.pool_list.get_pools().len(), 1);
+        assert!(parse_results.update_pools.is_empty());
+        assert!(matches!(parse_results.memory_updates[1], MemoryUpdateType::Reallocation(_)));
+        assert_eq!(parse_results.memory_updates[2].get_thread_id(), Some(7));
+        assert_eq!(parse_results.memory_updates[2].get_cycle_counter(), None);
+        assert_eq!(*parse_results.memory_updates[0].get_callstack(), "main.c:12\n");
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let parse_results = initialise_test_parse_results();
+        let metadata = BTreeMap::from([(String::from("source"), String::from("test"))]);
+        let mut trace = Vec::new();
+        NativeTraceWriter::write(&parse_results, &metadata, &mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        assert_eq!(trace.lines().count(), 4);
+
+        let reread_results = MemoryNativeParser::new().parse_log_directly(&trace, "").unwrap();
+        assert_eq!(reread_results.memory_updates, parse_results.memory_updates);
+        assert_eq!(reread_results.update_pools, parse_results.update_pools);
+        assert_eq!(reread_results.max_timestamp, 3);
+        assert!(reread_results.pool_list.get_lifetime("a").is_some());
+        assert_eq!(reread_results.pool_list.get_lifetime("a"), parse_results.pool_list.get_lifetime("a"));
+        assert!(reread_results.pool_list.get_lifetime("b").is_none());
+
+        let pools = reread_results.split_by_pools(0, 0).unwrap();
+        for pool in pools {
+            let expected_updates = if pool.pool.get_name() == "a" { 1 } else { 2 };
+            assert_eq!(pool.memory_updates.len(), expected_updates);
+        }
+    }
+
+    #[test]
+    fn bad_header_test() {
+        let result = MemoryNativeParser::new().parse_log_directly("{\"format\":\"damselfly\",\"version\":99}\n", "");
+        assert!(matches!(result, Err(DamselflyError::MalformedRecord { line: 1, .. })));
+        let result = MemoryNativeParser::new().parse_log_directly("{\"format\":\"other\",\"version\":1}\n", "");
+        assert!(matches!(result, Err(DamselflyError::MalformedRecord { line: 1, .. })));
+    }
+
+    #[test]
+    fn bad_update_test() {
+        let trace = TEST_TRACE.replace("\"2 s\"", "\"2 minutes\"");
+        let result = MemoryNativeParser::new().parse_log_directly(&trace, "");
+        assert!(matches!(result, Err(DamselflyError::BadTimestamp { line: 5, .. })));
+        for timestamp in ["\"-1 us\"", "\"99999999999999999999999 s\""] {
+            let trace = TEST_TRACE.replace("\"2 s\"", timestamp);
+            let result = MemoryNativeParser::new().parse_log_directly(&trace, "");
+            assert!(matches!(result, Err(DamselflyError::BadTimestamp { line: 5, .. })), "{timestamp}");
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! A record that was skipped while parsing a log in lenient mode.
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ParseDiagnostic {
+    line: usize,
+    text: String,
+    reason: String,
+}
+
+impl ParseDiagnostic {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Line number in the log, starting from 1.
+    /// * `text`: Raw text of the skipped line.
+    /// * `reason`: Why the line was skipped.
+    ///
+    /// returns: ParseDiagnostic
+    pub fn new(line: usize, text: String, reason: String) -> Self {
+        Self {
+            line,
+            text,
+            reason,
+        }
+    }
+
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+}
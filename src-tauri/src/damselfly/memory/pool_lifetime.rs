@@ -0,0 +1,205 @@
+//! Tracks when a pool is created, resized and deleted during a trace, for RTOSes that manage pools
+//! at runtime. Pools without any lifecycle events exist for the whole trace.
+//!
+//! Each event is placed by its position: the number of the pool's memory operations that came
+//! before it.
+use serde::{Deserialize, Serialize};
+
+/// A change to a pool's bounds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PoolEventKind {
+    /// The pool is created (or recreated) with these bounds.
+    Create { start: usize, size: usize },
+    /// The pool keeps its start address but changes size.
+    Resize { size: usize },
+    /// The pool is deleted. Its addresses are unused until it is created again.
+    Delete,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolEvent {
+    #[serde(flatten)]
+    pub kind: PoolEventKind,
+    /// Number of the pool's memory operations that happened before this event.
+    pub position: usize,
+    /// Realtime timestamp of the event, e.g. "0003.677 s".
+    pub real_timestamp: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolLifetime {
+    /// Bounds of the pool before the first event, or None if it did not exist yet.
+    initial_bounds: Option<(usize, usize)>,
+    events: Vec<PoolEvent>,
+}
+
+impl PoolLifetime {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_bounds`: (start, size) of the pool at the start of the trace, or None if the pool
+    ///   is created during the trace.
+    ///
+    /// returns: PoolLifetime
+    pub fn new(initial_bounds: Option<(usize, usize)>) -> PoolLifetime {
+        PoolLifetime {
+            initial_bounds,
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds an event. Events must be added in the order they happened.
+    pub fn push_event(&mut self, event: PoolEvent) {
+        self.events.push(event);
+    }
+
+    pub fn get_events(&self) -> &[PoolEvent] {
+        &self.events
+    }
+
+    pub fn get_initial_bounds(&self) -> Option<(usize, usize)> {
+        self.initial_bounds
+    }
+
+    /// Gets the bounds of the pool once a number of its operations have happened. Events that
+    /// happened between the last of these operations and the next are included.
+    ///
+    /// # Arguments
+    ///
+    /// * `operations`: Number of the pool's operations that have happened.
+    ///
+    /// returns: (start, end) of the pool, or None if it does not exist at that point
+    pub fn get_bounds_after(&self, operations: usize) -> Option<(usize, usize)> {
+        let mut bounds = self.initial_bounds;
+        for event in self.events.iter().take_while(|event| event.position <= operations) {
+            bounds = match event.kind {
+                PoolEventKind::Create { start, size } => Some((start, size)),
+                PoolEventKind::Resize { size } => bounds.map(|(start, _)| (start, size)),
+                PoolEventKind::Delete => None,
+            };
+        }
+        bounds.map(|(start, size)| (start, start + size))
+    }
+
+    /// Gets the lowest and highest address the pool ever covers.
+    ///
+    /// returns: (start, end), or None if the pool never exists
+    pub fn get_extent(&self) -> Option<(usize, usize)> {
+        let mut extent: Option<(usize, usize)> = None;
+        let mut bounds = self.initial_bounds;
+        let mut include = |bounds: Option<(usize, usize)>| {
+            if let Some((start, size)) = bounds {
+                extent = Some(match extent {
+                    Some((lowest, highest)) => (lowest.min(start), highest.max(start + size)),
+                    None => (start, start + size),
+                });
+            }
+        };
+        include(bounds);
+        for event in &self.events {
+            bounds = match event.kind {
+                PoolEventKind::Create { start, size } => Some((start, size)),
+                PoolEventKind::Resize { size } => bounds.map(|(start, _)| (start, size)),
+                PoolEventKind::Delete => None,
+            };
+            include(bounds);
+        }
+        extent
+    }
+
+    /// Renumbers event positions after some operations have been removed, e.g. to count only the
+    /// operations in this pool, or only those made by certain threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `kept_positions`: Positions (in the current numbering) of the operations that are kept,
+    ///   in ascending order.
+    ///
+    /// returns: PoolLifetime with positions counting only the kept operations
+    pub fn renumber(&self, kept_positions: &[usize]) -> PoolLifetime {
+        let mut lifetime = self.clone();
+        for event in &mut lifetime.events {
+            event.position = kept_positions.partition_point(|&position| position < event.position);
+        }
+        lifetime
+    }
+
+    /// Shifts the pool's bounds by the padding applied to each operation, in the same way as the
+    /// pool itself is shifted when operations are split into pools.
+    ///
+    /// # Arguments
+    ///
+    /// * `left_padding`: Padding added to the left of each operation.
+    /// * `right_padding`: Padding added to the right of each operation.
+    ///
+    /// returns: PoolLifetime
+    pub fn pad(&self, left_padding: usize, right_padding: usize) -> PoolLifetime {
+        let mut lifetime = self.clone();
+        lifetime.initial_bounds = lifetime.initial_bounds
+            .map(|(start, size)| (start.saturating_sub(left_padding), size + right_padding));
+        for event in &mut lifetime.events {
+            match &mut event.kind {
+                PoolEventKind::Create { start, size } => {
+                    *start = start.saturating_sub(left_padding);
+                    *size += right_padding;
+                }
+                PoolEventKind::Resize { size } => *size += right_padding,
+                PoolEventKind::Delete => {}
+            }
+        }
+        lifetime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind, PoolLifetime};
+
+    fn initialise_test_lifetime() -> PoolLifetime {
+        let mut lifetime = PoolLifetime::new(None);
+        let events = [
+            (PoolEventKind::Create { start: 100, size: 50 }, 0),
+            (PoolEventKind::Resize { size: 80 }, 2),
+            (PoolEventKind::Delete, 4),
+            (PoolEventKind::Create { start: 60, size: 20 }, 6),
+        ];
+        for (kind, position) in events {
+            lifetime.push_event(PoolEvent { kind, position, real_timestamp: String::from("0 us") });
+        }
+        lifetime
+    }
+
+    #[test]
+    fn get_bounds_after_test() {
+        let lifetime = initialise_test_lifetime();
+        assert_eq!(lifetime.get_bounds_after(0), Some((100, 150)));
+        assert_eq!(lifetime.get_bounds_after(1), Some((100, 150)));
+        assert_eq!(lifetime.get_bounds_after(2), Some((100, 180)));
+        assert_eq!(lifetime.get_bounds_after(4), None);
+        assert_eq!(lifetime.get_bounds_after(5), None);
+        assert_eq!(lifetime.get_bounds_after(6), Some((60, 80)));
+        assert_eq!(lifetime.get_extent(), Some((60, 180)));
+
+        let static_lifetime = PoolLifetime::new(Some((10, 10)));
+        assert_eq!(static_lifetime.get_bounds_after(100), Some((10, 20)));
+        assert_eq!(PoolLifetime::new(None).get_extent(), None);
+    }
+
+    #[test]
+    fn renumber_test() {
+        // Only the operations at positions 1 and 5 are kept
+        let lifetime = initialise_test_lifetime().renumber(&[1, 5]);
+        let positions: Vec<usize> = lifetime.get_events().iter().map(|event| event.position).collect();
+        assert_eq!(positions, vec![0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn pad_test() {
+        let lifetime = initialise_test_lifetime().pad(8, 4);
+        assert_eq!(lifetime.get_bounds_after(0), Some((92, 146)));
+        assert_eq!(lifetime.get_bounds_after(2), Some((92, 176)));
+        assert_eq!(lifetime.get_bounds_after(6), Some((52, 76)));
+    }
+}
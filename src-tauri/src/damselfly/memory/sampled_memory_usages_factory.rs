@@ -35,7 +35,7 @@ impl SampledMemoryUsagesFactory {
         let mut bucket_keys: Vec<u64> = buckets.keys().cloned().collect();
         bucket_keys.sort();
         let last_key = *bucket_keys.last().unwrap_or(&0);
-        let mut previous_averaged_usage = MemoryUsage::new(0, 0, (0, 0, 0), 0, 0, 0, 0, 0);
+        let mut previous_averaged_usage = MemoryUsage::new(0, 0, (0, 0, 0), 0, 0, 0, 0.0, 0);
         let mut previous_first_last_operations = (u64::MAX, u64::MIN);
         for key in (0..=last_key).step_by(self.sample_interval as usize) {
             match buckets.get(&key) {
@@ -51,7 +51,7 @@ impl SampledMemoryUsagesFactory {
                             0,
                             0,
                             key as usize,
-                            0,
+                            key as f64,
                             0,
                         );
                     let mut bucket_memory_used = 0;
@@ -109,7 +109,7 @@ mod tests {
     #[test]
     fn sample_one_update() {
         let memory_usages = vec![
-            MemoryUsage::new(1, 1, (0, 0, 0), 1, 0, 1, 0, 0),
+            MemoryUsage::new(1, 1, (0, 0, 0), 1, 0, 1, 0.0, 0),
         ];
         let memory_usage_sampler = SampledMemoryUsagesFactory::new(1, memory_usages);
         assert_eq!(memory_usage_sampler.divide_usages_into_buckets().first().unwrap().get_first(), 1);
@@ -120,15 +120,15 @@ mod tests {
         assert_eq!(memory_usage_sampler.divide_usages_into_buckets().first().unwrap().get_sampled_usage().get_largest_free_block(), (0, 0, 0));
         assert_eq!(memory_usage_sampler.divide_usages_into_buckets().first().unwrap().get_sampled_usage().get_free_segment_fragmentation(), 0);
         assert_eq!(memory_usage_sampler.divide_usages_into_buckets().first().unwrap().get_sampled_usage().get_latest_operation(), 1);
-        assert_eq!(memory_usage_sampler.divide_usages_into_buckets().first().unwrap().get_sampled_usage().get_timestamp_microseconds(), 0);
+        assert_eq!(memory_usage_sampler.divide_usages_into_buckets().first().unwrap().get_sampled_usage().get_timestamp_microseconds(), 0.0);
     }
 
     #[test]
     fn sample_multiple_updates_no_overlap() {
         let memory_usages = vec![
-            MemoryUsage::new(1, 1, (1, 2, 1), 1, 0, 1, 1, 0),
-            MemoryUsage::new(2, 2, (2, 4, 2), 2, 0, 2, 2, 0),
-            MemoryUsage::new(3, 3, (3, 6, 3), 3, 0, 3, 3, 0),
+            MemoryUsage::new(1, 1, (1, 2, 1), 1, 0, 1, 1.0, 0),
+            MemoryUsage::new(2, 2, (2, 4, 2), 2, 0, 2, 2.0, 0),
+            MemoryUsage::new(3, 3, (3, 6, 3), 3, 0, 3, 3.0, 0),
         ];
         let memory_usage_sampler = SampledMemoryUsagesFactory::new(1, memory_usages);
         let buckets = memory_usage_sampler.divide_usages_into_buckets();
@@ -137,41 +137,41 @@ mod tests {
         assert_eq!(buckets[0].get_sampled_usage().get_free_blocks(), 0);
         assert_eq!(buckets[0].get_sampled_usage().get_largest_free_block(), (0, 0, 0));
         assert_eq!(buckets[0].get_sampled_usage().get_latest_operation(), 0);
-        assert_eq!(buckets[0].get_sampled_usage().get_timestamp_microseconds(), 0);
+        assert_eq!(buckets[0].get_sampled_usage().get_timestamp_microseconds(), 0.0);
         assert_eq!(buckets[1].get_sampled_usage().get_memory_used_absolute(), 1);
         assert_eq!(buckets[1].get_sampled_usage().get_distinct_blocks(), 1);
         assert_eq!(buckets[1].get_sampled_usage().get_free_blocks(), 1);
         assert_eq!(buckets[1].get_sampled_usage().get_largest_free_block(), (1, 2, 1));
         assert_eq!(buckets[1].get_sampled_usage().get_latest_operation(), 1);
-        assert_eq!(buckets[1].get_sampled_usage().get_timestamp_microseconds(), 1);
+        assert_eq!(buckets[1].get_sampled_usage().get_timestamp_microseconds(), 1.0);
         assert_eq!(buckets[2].get_sampled_usage().get_memory_used_absolute(), 2);
         assert_eq!(buckets[2].get_sampled_usage().get_distinct_blocks(), 2);
         assert_eq!(buckets[2].get_sampled_usage().get_free_blocks(), 2);
         assert_eq!(buckets[2].get_sampled_usage().get_largest_free_block(), (2, 4, 2));
         assert_eq!(buckets[2].get_sampled_usage().get_latest_operation(), 2);
-        assert_eq!(buckets[2].get_sampled_usage().get_timestamp_microseconds(), 2);
+        assert_eq!(buckets[2].get_sampled_usage().get_timestamp_microseconds(), 2.0);
         assert_eq!(buckets[3].get_sampled_usage().get_memory_used_absolute(), 3);
         assert_eq!(buckets[3].get_sampled_usage().get_distinct_blocks(), 3);
         assert_eq!(buckets[3].get_sampled_usage().get_free_blocks(), 3);
         assert_eq!(buckets[3].get_sampled_usage().get_largest_free_block(), (3, 6, 3));
         assert_eq!(buckets[3].get_sampled_usage().get_latest_operation(), 3);
-        assert_eq!(buckets[3].get_sampled_usage().get_timestamp_microseconds(), 3);
+        assert_eq!(buckets[3].get_sampled_usage().get_timestamp_microseconds(), 3.0);
     }
     
     #[test]
     fn sample_multiple_updates_overlap() {
         let memory_usages = vec![
             // timestamp = 1
-            MemoryUsage::new(1, 1, (1, 2, 1), 1, 0, 1, 1, 0),
-            MemoryUsage::new(2, 2, (2, 4, 2), 2, 0, 2, 1, 0),
-            MemoryUsage::new(3, 3, (3, 6, 3), 3, 0, 3, 1, 0),
+            MemoryUsage::new(1, 1, (1, 2, 1), 1, 0, 1, 1.0, 0),
+            MemoryUsage::new(2, 2, (2, 4, 2), 2, 0, 2, 1.0, 0),
+            MemoryUsage::new(3, 3, (3, 6, 3), 3, 0, 3, 1.0, 0),
 
             // timestamp = 2
-            MemoryUsage::new(4, 4, (4, 8, 4), 4, 0,4, 2, 0),
-            MemoryUsage::new(5, 5, (5, 10, 5), 5, 0,5, 2, 0),
+            MemoryUsage::new(4, 4, (4, 8, 4), 4, 0,4, 2.0, 0),
+            MemoryUsage::new(5, 5, (5, 10, 5), 5, 0,5, 2.0, 0),
 
             // timestamp = 3
-            MemoryUsage::new(6, 6, (6, 12, 6), 6, 0, 6, 3, 0),
+            MemoryUsage::new(6, 6, (6, 12, 6), 6, 0, 6, 3.0, 0),
         ];
         
         let memory_usage_sampler = SampledMemoryUsagesFactory::new(1, memory_usages);
@@ -182,59 +182,59 @@ mod tests {
         assert_eq!(buckets[0].get_sampled_usage().get_largest_free_block(), (0, 0, 0));
         assert_eq!(buckets[0].get_sampled_usage().get_free_blocks(), 0);
         assert_eq!(buckets[0].get_sampled_usage().get_latest_operation(), 0);
-        assert_eq!(buckets[0].get_sampled_usage().get_timestamp_microseconds(), 0);
+        assert_eq!(buckets[0].get_sampled_usage().get_timestamp_microseconds(), 0.0);
         assert_eq!(buckets[1].get_sampled_usage().get_memory_used_absolute(), 2);
         assert_eq!(buckets[1].get_sampled_usage().get_distinct_blocks(), 2);
         assert_eq!(buckets[1].get_sampled_usage().get_largest_free_block(), (3, 6, 3));
         assert_eq!(buckets[1].get_sampled_usage().get_free_blocks(), 2);
         assert_eq!(buckets[1].get_sampled_usage().get_latest_operation(), 3);
-        assert_eq!(buckets[1].get_sampled_usage().get_timestamp_microseconds(), 1);
+        assert_eq!(buckets[1].get_sampled_usage().get_timestamp_microseconds(), 1.0);
         assert_eq!(buckets[2].get_sampled_usage().get_memory_used_absolute(), 4);
         assert_eq!(buckets[2].get_sampled_usage().get_distinct_blocks(), 4);
         assert_eq!(buckets[2].get_sampled_usage().get_largest_free_block(), (5, 10, 5));
         assert_eq!(buckets[2].get_sampled_usage().get_free_blocks(), 4);
         assert_eq!(buckets[2].get_sampled_usage().get_latest_operation(), 5);
-        assert_eq!(buckets[2].get_sampled_usage().get_timestamp_microseconds(), 2);
+        assert_eq!(buckets[2].get_sampled_usage().get_timestamp_microseconds(), 2.0);
         assert_eq!(buckets[3].get_sampled_usage().get_memory_used_absolute(), 6);
         assert_eq!(buckets[3].get_sampled_usage().get_distinct_blocks(), 6);
         assert_eq!(buckets[3].get_sampled_usage().get_largest_free_block(), (6, 12, 6));
         assert_eq!(buckets[3].get_sampled_usage().get_free_blocks(), 6);
         assert_eq!(buckets[3].get_sampled_usage().get_latest_operation(), 6);
-        assert_eq!(buckets[3].get_sampled_usage().get_timestamp_microseconds(), 3);
+        assert_eq!(buckets[3].get_sampled_usage().get_timestamp_microseconds(), 3.0);
     }
     
     #[test]
     fn sample_multiple_updates_overlap_sample_interval_two() {
         let memory_usages = vec![
             // timestamp = 1
-            MemoryUsage::new(1, 1, (1, 2, 1), 1, 0, 1, 1, 0),
-            MemoryUsage::new(2, 2, (2, 4, 2), 2, 0, 2, 1, 0),
-            MemoryUsage::new(3, 3, (3, 6, 3), 3, 0, 3, 1, 0),
+            MemoryUsage::new(1, 1, (1, 2, 1), 1, 0, 1, 1.0, 0),
+            MemoryUsage::new(2, 2, (2, 4, 2), 2, 0, 2, 1.0, 0),
+            MemoryUsage::new(3, 3, (3, 6, 3), 3, 0, 3, 1.0, 0),
 
             // timestamp = 2
-            MemoryUsage::new(4, 4, (4, 8, 4), 4, 0, 4, 2, 0),
-            MemoryUsage::new(5, 5, (5, 10, 5), 5, 0, 5, 2, 0),
-            MemoryUsage::new(6, 6, (6, 12, 6), 6, 0, 6, 2, 0),
+            MemoryUsage::new(4, 4, (4, 8, 4), 4, 0, 4, 2.0, 0),
+            MemoryUsage::new(5, 5, (5, 10, 5), 5, 0, 5, 2.0, 0),
+            MemoryUsage::new(6, 6, (6, 12, 6), 6, 0, 6, 2.0, 0),
 
             // timestamp = 3
-            MemoryUsage::new(7, 7, (7, 14, 7), 7, 0, 7, 3, 0),
-            MemoryUsage::new(8, 8, (8, 16, 8), 8, 0, 8, 3, 0),
-            MemoryUsage::new(9, 9, (9, 18, 9), 9, 0, 9, 3, 0),
+            MemoryUsage::new(7, 7, (7, 14, 7), 7, 0, 7, 3.0, 0),
+            MemoryUsage::new(8, 8, (8, 16, 8), 8, 0, 8, 3.0, 0),
+            MemoryUsage::new(9, 9, (9, 18, 9), 9, 0, 9, 3.0, 0),
 
             // timestamp = 4
-            MemoryUsage::new(10, 10, (10, 20, 10), 10, 0, 10, 4, 0),
-            MemoryUsage::new(11, 11, (11, 22, 11), 11, 0, 11, 4, 0),
-            MemoryUsage::new(12, 12, (12, 24, 12), 12, 0, 12, 4, 0),
+            MemoryUsage::new(10, 10, (10, 20, 10), 10, 0, 10, 4.0, 0),
+            MemoryUsage::new(11, 11, (11, 22, 11), 11, 0, 11, 4.0, 0),
+            MemoryUsage::new(12, 12, (12, 24, 12), 12, 0, 12, 4.0, 0),
 
             // timestamp = 5
-            MemoryUsage::new(13, 13, (13, 26, 13), 13, 0, 13, 5, 0),
-            MemoryUsage::new(14, 14, (14, 28, 14), 14, 0, 14, 5, 0),
-            MemoryUsage::new(15, 15, (15, 30, 15), 15, 0, 15, 5, 0),
+            MemoryUsage::new(13, 13, (13, 26, 13), 13, 0, 13, 5.0, 0),
+            MemoryUsage::new(14, 14, (14, 28, 14), 14, 0, 14, 5.0, 0),
+            MemoryUsage::new(15, 15, (15, 30, 15), 15, 0, 15, 5.0, 0),
 
             // timestamp = 6
-            MemoryUsage::new(16, 16, (16, 32, 16), 16, 0, 16, 6, 0),
-            MemoryUsage::new(17, 17, (17, 34, 17), 17, 0, 17, 6, 0),
-            MemoryUsage::new(18, 18, (18, 36, 18), 18, 0, 18, 6, 0),
+            MemoryUsage::new(16, 16, (16, 32, 16), 16, 0, 16, 6.0, 0),
+            MemoryUsage::new(17, 17, (17, 34, 17), 17, 0, 17, 6.0, 0),
+            MemoryUsage::new(18, 18, (18, 36, 18), 18, 0, 18, 6.0, 0),
         ];
 
         let memory_usage_sampler = SampledMemoryUsagesFactory::new(2, memory_usages);
@@ -247,7 +247,7 @@ mod tests {
         assert_eq!(buckets[0].get_sampled_usage().get_largest_free_block(), (0, 0, 0));
         assert_eq!(buckets[0].get_sampled_usage().get_free_blocks(), 0);
         assert_eq!(buckets[0].get_sampled_usage().get_latest_operation(), 0);
-        assert_eq!(buckets[0].get_sampled_usage().get_timestamp_microseconds(), 0);
+        assert_eq!(buckets[0].get_sampled_usage().get_timestamp_microseconds(), 0.0);
         assert_eq!(buckets[1].get_first(), 3);
         assert_eq!(buckets[1].get_last(), 4);
         assert_eq!(buckets[1].get_sampled_usage().get_memory_used_absolute(), 3);
@@ -255,7 +255,7 @@ mod tests {
         assert_eq!(buckets[1].get_sampled_usage().get_largest_free_block(), (6, 12, 6));
         assert_eq!(buckets[1].get_sampled_usage().get_free_blocks(), 3);
         assert_eq!(buckets[1].get_sampled_usage().get_latest_operation(), 6);
-        assert_eq!(buckets[1].get_sampled_usage().get_timestamp_microseconds(), 2);
+        assert_eq!(buckets[1].get_sampled_usage().get_timestamp_microseconds(), 2.0);
         assert_eq!(buckets[2].get_first(), 4);
         assert_eq!(buckets[2].get_last(), 5);
         assert_eq!(buckets[2].get_sampled_usage().get_memory_used_absolute(), 9);
@@ -263,7 +263,7 @@ mod tests {
         assert_eq!(buckets[2].get_sampled_usage().get_largest_free_block(), (12, 24, 12));
         assert_eq!(buckets[2].get_sampled_usage().get_free_blocks(), 9);
         assert_eq!(buckets[2].get_sampled_usage().get_latest_operation(), 12);
-        assert_eq!(buckets[2].get_sampled_usage().get_timestamp_microseconds(), 4);
+        assert_eq!(buckets[2].get_sampled_usage().get_timestamp_microseconds(), 4.0);
         assert_eq!(buckets[3].get_first(), 6);
         assert_eq!(buckets[3].get_last(), 7);
         assert_eq!(buckets[3].get_sampled_usage().get_memory_used_absolute(), 15);
@@ -271,7 +271,7 @@ mod tests {
         assert_eq!(buckets[3].get_sampled_usage().get_largest_free_block(), (18, 36, 18));
         assert_eq!(buckets[3].get_sampled_usage().get_free_blocks(), 15);
         assert_eq!(buckets[3].get_sampled_usage().get_latest_operation(), 18);
-        assert_eq!(buckets[3].get_sampled_usage().get_timestamp_microseconds(), 6);
+        assert_eq!(buckets[3].get_sampled_usage().get_timestamp_microseconds(), 6.0);
     }
 }
 
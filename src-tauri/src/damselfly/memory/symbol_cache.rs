@@ -0,0 +1,260 @@
+//! Persists symbolised frames to disk, so that opening another trace of the same firmware build
+//! skips the DWARF lookups. There is one cache file per binary, named after the binary's build ID,
+//! or the CRC of its separate debuginfo in .gnu_debuglink if it has no build ID, so a rebuilt
+//! binary never uses a stale cache. Binaries with neither are identified by their file name, size
+//! and modification time. The cache can be looked up without reading the binary's debuginfo.
+//! The cache also records which separate debuginfo file (if any) the frames came from, and is
+//! discarded if a different one is found, e.g. after adding a debug directory.
+//!
+//! Frames are stored by the address in the binary (i.e. after relocating position independent
+//! binaries) and before path remapping, so the cache can be shared by traces with different
+//! symbol options.
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use object::{Object, ReadRef};
+use serde::{Deserialize, Serialize};
+
+use crate::damselfly::memory::symbol_frame::SymbolFrame;
+
+/// Bump this whenever the cache contents change meaning, so that old caches are discarded.
+pub const SYMBOL_CACHE_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SymbolCache {
+    version: u32,
+    /// Identifies the separate debuginfo file the frames came from, if any.
+    debuginfo_id: Option<String>,
+    frames: HashMap<usize, Vec<SymbolFrame>>,
+}
+
+impl SymbolCache {
+    /// Constructs an empty cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `debuginfo_id`: Identifies the separate debuginfo file used to symbolise the binary, if
+    ///   any, as given by file_id.
+    ///
+    /// returns: SymbolCache
+    pub fn new(debuginfo_id: Option<String>) -> SymbolCache {
+        SymbolCache {
+            version: SYMBOL_CACHE_VERSION,
+            debuginfo_id,
+            frames: HashMap::new(),
+        }
+    }
+
+    /// Identifies a binary by its build ID, or the CRC in its .gnu_debuglink section if it has no
+    /// build ID, or failing both, by file_id. Only these small sections are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_path`: Path to the binary.
+    /// * `object`: The parsed binary.
+    ///
+    /// returns: String that is safe to use as a file name, or None if the binary's file cannot be
+    /// identified
+    pub fn binary_id<'data, R: ReadRef<'data>>(binary_path: &str, object: &object::File<'data, R>) -> Option<String> {
+        if let Ok(Some(build_id)) = object.build_id() {
+            if !build_id.is_empty() {
+                let build_id: String = build_id.iter().map(|byte| format!("{byte:02x}")).collect();
+                return Some(format!("build-id-{build_id}"));
+            }
+        }
+        if let Ok(Some((_, crc))) = object.gnu_debuglink() {
+            return Some(format!("debuglink-{crc:08x}"));
+        }
+        Self::file_id(Path::new(binary_path))
+    }
+
+    /// Identifies a file by its name, size and modification time, without reading it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to the file.
+    ///
+    /// returns: String that is safe to use as a file name, or None if the file's metadata cannot
+    /// be read
+    pub fn file_id(path: &Path) -> Option<String> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        let name = path.file_name()?.to_string_lossy();
+        Some(format!("file-{name}-{}-{}", metadata.len(), modified.as_nanos()))
+    }
+
+    /// Gets the default directory for symbol caches: $XDG_CACHE_HOME/damselfly/symbols, falling back
+    /// to ~/.cache/damselfly/symbols, or %LOCALAPPDATA%\damselfly\symbols on Windows.
+    ///
+    /// returns: The directory, or None if there is no home or cache directory
+    pub fn default_dir() -> Option<String> {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+        Some(cache_home.join("damselfly").join("symbols").to_string_lossy().into_owned())
+    }
+
+    fn cache_path(cache_dir: &str, binary_id: &str) -> PathBuf {
+        Path::new(cache_dir).join(format!("{binary_id}.json"))
+    }
+
+    /// Loads the cache of a binary. A cache that is missing, unreadable, from another version of
+    /// Damselfly or made with different debuginfo is treated as empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_dir`: Directory holding the caches.
+    /// * `binary_id`: Identifies the binary, as given by binary_id.
+    /// * `debuginfo_id`: Identifies the separate debuginfo file used, if any, as given by file_id.
+    ///
+    /// returns: SymbolCache
+    pub fn load(cache_dir: &str, binary_id: &str, debuginfo_id: Option<String>) -> SymbolCache {
+        let path = Self::cache_path(cache_dir, binary_id);
+        fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| Self::from_json(&contents, debuginfo_id.as_deref()))
+            .unwrap_or_else(|| SymbolCache::new(debuginfo_id))
+    }
+
+    /// Parses a cache, checking that it can be used.
+    ///
+    /// # Arguments
+    ///
+    /// * `json`: The cache file.
+    /// * `debuginfo_id`: Identifies the separate debuginfo file used, if any.
+    ///
+    /// returns: SymbolCache, or None if it is invalid or stale
+    pub fn from_json(json: &str, debuginfo_id: Option<&str>) -> Option<SymbolCache> {
+        let cache: SymbolCache = serde_json::from_str(json).ok()?;
+        if cache.version != SYMBOL_CACHE_VERSION || cache.debuginfo_id.as_deref() != debuginfo_id {
+            return None;
+        }
+        Some(cache)
+    }
+
+    /// Writes the cache of a binary. The cache is written to a temporary file first, so that a
+    /// trace opened at the same time never reads half a cache. Failures are only logged, as the
+    /// frames can always be found again.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_dir`: Directory holding the caches. Created if it does not exist.
+    /// * `binary_id`: Identifies the binary, as given by binary_id.
+    ///
+    /// returns: ()
+    pub fn save(&self, cache_dir: &str, binary_id: &str) {
+        let path = Self::cache_path(cache_dir, binary_id);
+        let temporary_path = path.with_extension(format!("json.{}", std::process::id()));
+        let result = fs::create_dir_all(cache_dir)
+            .and_then(|_| serde_json::to_vec(self).map_err(std::io::Error::from))
+            .and_then(|json| fs::write(&temporary_path, json))
+            .and_then(|_| fs::rename(&temporary_path, &path));
+        if let Err(error) = result {
+            eprintln!("[SymbolCache::save]: Failed to write symbol cache {}: {error}", path.display());
+            let _ = fs::remove_file(&temporary_path);
+        }
+    }
+
+    /// Gets the cached frames of an address.
+    ///
+    /// # Arguments
+    ///
+    /// * `address`: Address in the binary.
+    ///
+    /// returns: The frames, or None if the address is not cached
+    pub fn get(&self, address: usize) -> Option<&Vec<SymbolFrame>> {
+        self.frames.get(&address)
+    }
+
+    pub fn get_debuginfo_id(&self) -> Option<&str> {
+        self.debuginfo_id.as_deref()
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        self.frames.contains_key(&address)
+    }
+
+    pub fn insert(&mut self, address: usize, frames: Vec<SymbolFrame>) {
+        self.frames.insert(address, frames);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use crate::damselfly::memory::symbol_cache::{SYMBOL_CACHE_VERSION, SymbolCache};
+    use crate::damselfly::memory::symbol_frame::SymbolFrame;
+
+    #[test]
+    fn from_json_test() {
+        let mut cache = SymbolCache::new(Some(String::from("build-id-abcd")));
+        let frames = vec![
+            SymbolFrame::new(Some(String::from("inlined")), Some(String::from("/src/a.h")), Some(3)),
+            SymbolFrame::new(Some(String::from("main")), Some(String::from("/src/main.c")), Some(10)),
+        ];
+        cache.insert(0x1000, frames.clone());
+        cache.insert(0x2000, vec![SymbolFrame::from_symbol_table(String::from("helper"), 0x8)]);
+        cache.insert(0x3000, Vec::new());
+        let json = serde_json::to_string(&cache).unwrap();
+
+        let loaded = SymbolCache::from_json(&json, Some("build-id-abcd")).unwrap();
+        assert_eq!(loaded, cache);
+        assert_eq!(loaded.get(0x1000), Some(&frames));
+        assert!(loaded.contains(0x3000));
+        assert!(!loaded.contains(0x4000));
+
+        // Made with different (or no) separate debuginfo
+        assert!(SymbolCache::from_json(&json, None).is_none());
+        assert!(SymbolCache::from_json(&json, Some("build-id-ef01")).is_none());
+        // From another version, or corrupt
+        let old_json = json.replacen(&format!("\"version\":{SYMBOL_CACHE_VERSION}"), "\"version\":0", 1);
+        assert!(SymbolCache::from_json(&old_json, Some("build-id-abcd")).is_none());
+        assert!(SymbolCache::from_json(&json[..json.len() / 2], Some("build-id-abcd")).is_none());
+    }
+
+    #[test]
+    fn save_load_test() {
+        let cache_dir = env::temp_dir().join(format!("damselfly-symbol-cache-test-{}", std::process::id()));
+        let cache_dir_str = cache_dir.to_string_lossy().into_owned();
+        let debuginfo_id = Some(String::from("file-app.debug-100-1"));
+        let mut cache = SymbolCache::new(debuginfo_id.clone());
+        cache.insert(0x1000, vec![SymbolFrame::new(Some(String::from("main")), Some(String::from("/src/main.c")), Some(10))]);
+        // The directory is created when the cache is first saved
+        cache.save(&cache_dir_str, "build-id-abcd");
+
+        let loaded = SymbolCache::load(&cache_dir_str, "build-id-abcd", debuginfo_id.clone());
+        assert_eq!(loaded, cache);
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1, "Temporary file was left behind");
+        // Another binary, or the same binary with different debuginfo
+        assert!(SymbolCache::load(&cache_dir_str, "build-id-ef01", debuginfo_id.clone()).is_empty());
+        let stale = SymbolCache::load(&cache_dir_str, "build-id-abcd", None);
+        assert!(stale.is_empty());
+        assert_eq!(stale.get_debuginfo_id(), None);
+
+        // A file's ID changes with its size
+        let file_path = cache_dir.join("build-id-abcd.json");
+        let file_id = SymbolCache::file_id(&file_path).unwrap();
+        assert!(file_id.starts_with("file-build-id-abcd.json-"));
+        fs::write(&file_path, "{}").unwrap();
+        assert_ne!(SymbolCache::file_id(&file_path).unwrap(), file_id);
+        assert!(SymbolCache::load(&cache_dir_str, "build-id-abcd", debuginfo_id).is_empty());
+        assert_eq!(SymbolCache::file_id(Path::new("/nonexistent/app.debug")), None);
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+//! A single frame of a symbolised return address. An address inside an inlined function resolves
+//! to several frames: the inlined functions, innermost first, then the function they were inlined
+//! into.
+//!
+//! Frames come from the DWARF debuginfo where it covers the address, otherwise from the ELF symbol
+//! table, which only gives the function and an offset into it.
+//!
+//! If the program loads several binaries, each frame is labelled with the binary it came from, as
+//! `module!function (file:line)`.
+use serde::{Deserialize, Serialize};
+
+/// Where a frame was found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolSource {
+    #[default]
+    Dwarf,
+    /// The ELF symbol table, at an offset from the start of the function.
+    SymbolTable { offset: u64 },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolFrame {
+    function: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    source: SymbolSource,
+    module: Option<String>,
+}
+
+impl SymbolFrame {
+    /// Constructs a frame found in the DWARF debuginfo.
+    ///
+    /// # Arguments
+    ///
+    /// * `function`: Demangled name of the function, if known.
+    /// * `file`: Source file, if known.
+    /// * `line`: Line in the source file, if known.
+    ///
+    /// returns: SymbolFrame
+    pub fn new(function: Option<String>, file: Option<String>, line: Option<u32>) -> SymbolFrame {
+        SymbolFrame {
+            function,
+            file,
+            line,
+            source: SymbolSource::Dwarf,
+            module: None,
+        }
+    }
+
+    /// Constructs a frame found in the ELF symbol table.
+    ///
+    /// # Arguments
+    ///
+    /// * `function`: Demangled name of the function.
+    /// * `offset`: Offset of the address from the start of the function.
+    ///
+    /// returns: SymbolFrame
+    pub fn from_symbol_table(function: String, offset: u64) -> SymbolFrame {
+        SymbolFrame {
+            function: Some(function),
+            file: None,
+            line: None,
+            source: SymbolSource::SymbolTable { offset },
+            module: None,
+        }
+    }
+
+    pub fn get_function(&self) -> Option<&str> {
+        self.function.as_deref()
+    }
+
+    pub fn get_file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Replaces the source file, e.g. after remapping its path.
+    pub fn set_file(&mut self, file: String) {
+        self.file = Some(file);
+    }
+
+    pub fn get_line(&self) -> Option<u32> {
+        self.line
+    }
+
+    pub fn get_source(&self) -> SymbolSource {
+        self.source
+    }
+
+    /// Labels the frame with the binary it came from.
+    pub fn set_module(&mut self, module: String) {
+        self.module = Some(module);
+    }
+
+    pub fn get_module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    /// Formats the frame as `function (file:line)`. Frames without a function are formatted as
+    /// `file:line`, and frames without a location as `function`. Frames from the symbol table are
+    /// formatted as `function+0xoffset [symtab]`. Frames labelled with a module are prefixed by
+    /// `module!`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`: Prefix to trim from the file, e.g. the directory the binary was built in.
+    ///
+    /// returns: String
+    pub fn format(&self, prefix: &str) -> String {
+        match &self.module {
+            Some(module) => format!("{module}!{}", self.format_symbol(prefix)),
+            None => self.format_symbol(prefix),
+        }
+    }
+
+    fn format_symbol(&self, prefix: &str) -> String {
+        if let SymbolSource::SymbolTable { offset } = self.source {
+            let function = self.function.as_deref().unwrap_or("[UNKNOWN FUNCTION]");
+            return format!("{function}+{offset:#x} [symtab]");
+        }
+        let location = match (&self.file, self.line) {
+            (None, None) => None,
+            (file, line) => {
+                let file = file.as_deref().map_or("[UNKNOWN FILE]", |file| file.trim_start_matches(prefix));
+                Some(format!("{file}:{}", line.unwrap_or(0)))
+            }
+        };
+        match (&self.function, location) {
+            (Some(function), Some(location)) => format!("{function} ({location})"),
+            (Some(function), None) => function.clone(),
+            (None, Some(location)) => location,
+            (None, None) => String::from("[UNKNOWN SYMBOL]"),
+        }
+    }
+
+    /// Formats the frames of an address, one per line, innermost first.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames`: Frames of the address.
+    /// * `prefix`: Prefix to trim from each file.
+    ///
+    /// returns: The frames, or [UNKNOWN SYMBOL] if there are none
+    pub fn format_frames(frames: &[SymbolFrame], prefix: &str) -> String {
+        if frames.is_empty() {
+            return String::from("[UNKNOWN SYMBOL]");
+        }
+        frames.iter()
+            .map(|frame| frame.format(prefix))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::symbol_frame::SymbolFrame;
+
+    #[test]
+    fn format_test() {
+        let inlined = SymbolFrame::new(Some(String::from("std::vector<int>::push_back")), Some(String::from("/src/include/vector")), Some(12));
+        let caller = SymbolFrame::new(Some(String::from("main")), Some(String::from("/src/main.cpp")), Some(30));
+        assert_eq!(caller.format("/src/"), "main (main.cpp:30)");
+        assert_eq!(SymbolFrame::format_frames(&[inlined, caller], "/src/"),
+                   "std::vector<int>::push_back (include/vector:12)\nmain (main.cpp:30)");
+
+        assert_eq!(SymbolFrame::new(Some(String::from("main")), None, None).format(""), "main");
+        assert_eq!(SymbolFrame::new(None, Some(String::from("main.c")), None).format(""), "main.c:0");
+        assert_eq!(SymbolFrame::new(None, None, Some(4)).format(""), "[UNKNOWN FILE]:4");
+        assert_eq!(SymbolFrame::format_frames(&[], ""), "[UNKNOWN SYMBOL]");
+        assert_eq!(SymbolFrame::from_symbol_table(String::from("main"), 0x1c).format(""), "main+0x1c [symtab]");
+
+        let mut module_frame = SymbolFrame::new(Some(String::from("dsp_main")), Some(String::from("/src/dsp.c")), Some(7));
+        module_frame.set_module(String::from("dsp.elf"));
+        assert_eq!(module_frame.format("/src/"), "dsp.elf!dsp_main (dsp.c:7)");
+        let mut module_frame = SymbolFrame::from_symbol_table(String::from("boot"), 0x4);
+        module_frame.set_module(String::from("boot.elf"));
+        assert_eq!(module_frame.format(""), "boot.elf!boot+0x4 [symtab]");
+    }
+}
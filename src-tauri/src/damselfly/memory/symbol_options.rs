@@ -0,0 +1,309 @@
+//! Where to look for debuginfo when symbolising a binary, and how to rewrite the source paths it
+//! contains.
+//!
+//! Release binaries are often stripped, with their debuginfo in a separate file that the binary
+//! points to by build ID (`<debug dir>/.build-id/ab/cdef....debug`) or by .gnu_debuglink (a file
+//! name and CRC). The file is searched for in the same places as GDB: next to the binary, in its
+//! .debug subdirectory, then in each configured debug directory.
+//!
+//! Source paths in debuginfo are those of the machine that built the binary, e.g.
+//! /work/hpdev/dune/src/main.c. Path remappings replace such a prefix with a local directory. If
+//! none are given, the directories common to every source file are trimmed instead.
+//!
+//! Programs that load other binaries (bootloaders, overlays, DSP firmware) can list each as a
+//! module. Addresses inside a module are symbolised with its debuginfo, and every frame is then
+//! labelled with the binary it came from.
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use object::{Object, ReadRef};
+
+use crate::damselfly::memory::binary_module::BinaryModule;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolOptions {
+    debug_dirs: Vec<String>,
+    // (from, to)
+    path_remaps: Vec<(String, String)>,
+    modules: Vec<BinaryModule>,
+    cache_dir: Option<String>,
+}
+
+impl SymbolOptions {
+    pub fn new() -> SymbolOptions {
+        SymbolOptions::default()
+    }
+
+    /// Adds a directory to search for separate debuginfo files.
+    ///
+    /// # Arguments
+    ///
+    /// * `debug_dir`: The directory, e.g. /usr/lib/debug.
+    ///
+    /// returns: SymbolOptions
+    pub fn with_debug_dir(mut self, debug_dir: String) -> SymbolOptions {
+        self.debug_dirs.push(debug_dir);
+        self
+    }
+
+    /// Adds a source path remapping. Remappings are tried in the order they were added.
+    ///
+    /// # Arguments
+    ///
+    /// * `from`: Prefix of source paths in the debuginfo, e.g. /work/hpdev/dune.
+    /// * `to`: What to replace the prefix with. May be empty to make the paths relative.
+    ///
+    /// returns: SymbolOptions
+    pub fn with_path_remap(mut self, from: String, to: String) -> SymbolOptions {
+        self.path_remaps.push((from, to));
+        self
+    }
+
+    /// Adds a binary loaded alongside the main binary. If modules overlap, e.g. overlays, an
+    /// address is symbolised with the first module added that contains it.
+    ///
+    /// # Arguments
+    ///
+    /// * `module`: The binary and the addresses it is loaded at.
+    ///
+    /// returns: SymbolOptions
+    pub fn with_module(mut self, module: BinaryModule) -> SymbolOptions {
+        self.modules.push(module);
+        self
+    }
+
+    /// Caches symbolised frames in a directory, so that traces of the same binary are symbolised
+    /// without reading its debuginfo again.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_dir`: The directory, e.g. SymbolCache::default_dir().
+    ///
+    /// returns: SymbolOptions
+    pub fn with_cache_dir(mut self, cache_dir: String) -> SymbolOptions {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    /// Parses a source path remapping given as FROM=TO, e.g. on the command line.
+    ///
+    /// # Arguments
+    ///
+    /// * `remap`: The remapping.
+    ///
+    /// returns: (from, to), or an error if there is no = or FROM is empty
+    pub fn parse_path_remap(remap: &str) -> Result<(String, String), String> {
+        match remap.split_once('=') {
+            Some((from, to)) if !from.is_empty() => Ok((from.to_string(), to.to_string())),
+            _ => Err(format!("{remap} is not a path remapping of the form FROM=TO")),
+        }
+    }
+
+    pub fn get_debug_dirs(&self) -> &[String] {
+        &self.debug_dirs
+    }
+
+    pub fn get_path_remaps(&self) -> &[(String, String)] {
+        &self.path_remaps
+    }
+
+    pub fn get_modules(&self) -> &[BinaryModule] {
+        &self.modules
+    }
+
+    pub fn get_cache_dir(&self) -> Option<&str> {
+        self.cache_dir.as_deref()
+    }
+
+    /// Finds the module an address was loaded from.
+    ///
+    /// # Arguments
+    ///
+    /// * `address`: The address.
+    ///
+    /// returns: Index of the first module containing the address, or None if it is in the main
+    /// binary
+    pub fn find_module(&self, address: usize) -> Option<usize> {
+        self.modules.iter().position(|module| module.contains(address))
+    }
+
+    /// Rewrites a source path using the first remapping whose prefix matches it. Prefixes match
+    /// whole path components, so /work/dune does not match /work/dune2/main.c.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Source path from the debuginfo.
+    ///
+    /// returns: The remapped path, or None if no remapping matches
+    pub fn remap_path(&self, path: &str) -> Option<String> {
+        self.path_remaps.iter().find_map(|(from, to)| {
+            let rest = Path::new(path).strip_prefix(from).ok()?;
+            Some(Path::new(to).join(rest).to_string_lossy().into_owned())
+        })
+    }
+
+    /// Gets the paths a debuginfo file could have from the binary's build ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `build_id`: The build ID.
+    ///
+    /// returns: <debug dir>/.build-id/<first byte>/<remaining bytes>.debug for each debug directory
+    pub fn build_id_paths(&self, build_id: &[u8]) -> Vec<PathBuf> {
+        let Some((first, rest)) = build_id.split_first() else {
+            return Vec::new();
+        };
+        let rest: String = rest.iter().map(|byte| format!("{byte:02x}")).collect();
+        self.debug_dirs.iter()
+            .map(|debug_dir| Path::new(debug_dir).join(".build-id").join(format!("{first:02x}")).join(format!("{rest}.debug")))
+            .collect()
+    }
+
+    /// Gets the paths a debuginfo file could have from the file name in the binary's
+    /// .gnu_debuglink section.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_path`: Path to the binary.
+    /// * `debuglink`: File name of the debuginfo file.
+    ///
+    /// returns: The file next to the binary, in the binary's .debug directory, then in each debug
+    /// directory
+    pub fn debuglink_paths(&self, binary_path: &str, debuglink: &str) -> Vec<PathBuf> {
+        let binary_dir = Path::new(binary_path).parent().unwrap_or(Path::new(""));
+        let mut paths = vec![binary_dir.join(debuglink), binary_dir.join(".debug").join(debuglink)];
+        paths.extend(self.debug_dirs.iter().map(|debug_dir| Path::new(debug_dir).join(debuglink)));
+        paths.retain(|path| path != Path::new(binary_path));
+        paths
+    }
+
+    /// Gets the paths the separate debuginfo file of a binary could have, by build ID first, then
+    /// by .gnu_debuglink.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_path`: Path to the binary.
+    /// * `object`: The parsed binary.
+    ///
+    /// returns: (path, CRC the file must have) of each candidate. Files found by build ID have no CRC.
+    fn debug_file_candidates<'data, R: ReadRef<'data>>(&self, binary_path: &str, object: &object::File<'data, R>) -> Vec<(PathBuf, Option<u32>)> {
+        let mut candidates: Vec<(PathBuf, Option<u32>)> = match object.build_id() {
+            Ok(Some(build_id)) => self.build_id_paths(build_id).into_iter().map(|path| (path, None)).collect(),
+            _ => Vec::new(),
+        };
+        if let Ok(Some((debuglink, crc))) = object.gnu_debuglink() {
+            let debuglink = String::from_utf8_lossy(debuglink);
+            candidates.extend(self.debuglink_paths(binary_path, &debuglink).into_iter().map(|path| (path, Some(crc))));
+        }
+        candidates
+    }
+
+    /// Finds the separate debuginfo file of a binary without reading it, so that it can be
+    /// identified cheaply. The CRC of files found by .gnu_debuglink is not checked, so
+    /// open_debug_file may use a later file instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_path`: Path to the binary.
+    /// * `object`: The parsed binary.
+    ///
+    /// returns: Path of the debuginfo file, or None if there is none
+    pub fn find_debug_file<'data, R: ReadRef<'data>>(&self, binary_path: &str, object: &object::File<'data, R>) -> Option<PathBuf> {
+        self.debug_file_candidates(binary_path, object)
+            .into_iter()
+            .map(|(path, _)| path)
+            .find(|path| path.is_file())
+    }
+
+    /// Opens the separate debuginfo file of a binary, by build ID first, then by .gnu_debuglink.
+    /// Files found by .gnu_debuglink are read once to check their CRC, and only used if it matches.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_path`: Path to the binary.
+    /// * `object`: The parsed binary.
+    ///
+    /// returns: (path, file) of the debuginfo file, or None if it cannot be found
+    pub fn open_debug_file<'data, R: ReadRef<'data>>(&self, binary_path: &str, object: &object::File<'data, R>) -> Option<(PathBuf, File)> {
+        self.debug_file_candidates(binary_path, object)
+            .into_iter()
+            .find_map(|(path, crc)| {
+                let file = File::open(&path).ok()?;
+                match crc {
+                    Some(crc) if Self::file_crc(&file).ok()? != crc => None,
+                    _ => Some((path, file)),
+                }
+            })
+    }
+
+    /// Computes the CRC of a file as used by .gnu_debuglink, without holding the file in memory.
+    fn file_crc(mut file: &File) -> std::io::Result<u32> {
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buffer = vec![0; 1 << 16];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => return Ok(hasher.finalize()),
+                length => hasher.update(&buffer[..length]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::damselfly::memory::binary_module::BinaryModule;
+    use crate::damselfly::memory::symbol_options::SymbolOptions;
+
+    #[test]
+    fn remap_path_test() {
+        let options = SymbolOptions::new()
+            .with_path_remap(String::from("/work/hpdev/dune/src"), String::from(""))
+            .with_path_remap(String::from("/work/hpdev/dune"), String::from("/home/me/dune"));
+        assert_eq!(options.remap_path("/work/hpdev/dune/src/main.c"), Some(String::from("main.c")));
+        assert_eq!(options.remap_path("/work/hpdev/dune/lib/pool.c"), Some(String::from("/home/me/dune/lib/pool.c")));
+        assert_eq!(options.remap_path("/work/hpdev/dune2/main.c"), None);
+        assert_eq!(options.remap_path("main.c"), None);
+        assert_eq!(SymbolOptions::new().remap_path("/work/hpdev/dune/src/main.c"), None);
+
+        assert_eq!(SymbolOptions::parse_path_remap("/work=/home/me=1"), Ok((String::from("/work"), String::from("/home/me=1"))));
+        assert_eq!(SymbolOptions::parse_path_remap("/work="), Ok((String::from("/work"), String::new())));
+        assert!(SymbolOptions::parse_path_remap("/work").is_err());
+        assert!(SymbolOptions::parse_path_remap("=/home/me").is_err());
+    }
+
+    #[test]
+    fn find_module_test() {
+        let options = SymbolOptions::new()
+            .with_module(BinaryModule::new(String::from("boot.elf"), 0x0, 0x1000))
+            .with_module(BinaryModule::new(String::from("overlay_a.elf"), 0x8000, 0x9000))
+            .with_module(BinaryModule::new(String::from("overlay_b.elf"), 0x8000, 0xa000));
+        assert_eq!(options.find_module(0x10), Some(0));
+        assert_eq!(options.find_module(0x8800), Some(1));
+        assert_eq!(options.find_module(0x9800), Some(2));
+        assert_eq!(options.find_module(0x4000), None);
+        assert_eq!(SymbolOptions::new().find_module(0x10), None);
+    }
+
+    #[test]
+    fn debug_file_paths_test() {
+        let options = SymbolOptions::new()
+            .with_debug_dir(String::from("/usr/lib/debug"))
+            .with_debug_dir(String::from("symbols"));
+        assert_eq!(options.build_id_paths(&[0xab, 0xcd, 0x01]), vec![
+            PathBuf::from("/usr/lib/debug/.build-id/ab/cd01.debug"),
+            PathBuf::from("symbols/.build-id/ab/cd01.debug"),
+        ]);
+        assert!(options.build_id_paths(&[]).is_empty());
+        assert_eq!(options.debuglink_paths("/opt/app/threadxApp", "threadxApp.debug"), vec![
+            PathBuf::from("/opt/app/threadxApp.debug"),
+            PathBuf::from("/opt/app/.debug/threadxApp.debug"),
+            PathBuf::from("/usr/lib/debug/threadxApp.debug"),
+            PathBuf::from("symbols/threadxApp.debug"),
+        ]);
+        // A binary that links to a file with its own name does not find itself
+        assert_eq!(options.debuglink_paths("app", "app").len(), 3);
+    }
+}
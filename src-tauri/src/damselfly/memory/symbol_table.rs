@@ -0,0 +1,96 @@
+//! Looks up the function containing an address using a binary's ELF symbol table (.symtab, or
+//! .dynsym if the binary has been stripped). Used to symbolise addresses that the DWARF debuginfo
+//! does not cover, or binaries without any debuginfo, as `function+offset`.
+use std::borrow::Cow;
+
+use object::{Object, ObjectSymbol, ObjectSymbolTable, ReadRef, SymbolKind};
+
+#[derive(Default)]
+pub struct SymbolTable {
+    // (address, size, demangled name), sorted by address
+    symbols: Vec<(u64, u64, String)>,
+}
+
+impl SymbolTable {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols`: (address, size, name) of each function. Names are demangled.
+    ///
+    /// returns: SymbolTable
+    pub fn new(mut symbols: Vec<(u64, u64, String)>) -> SymbolTable {
+        for (_, _, name) in symbols.iter_mut() {
+            *name = addr2line::demangle_auto(Cow::from(name.as_str()), None).into_owned();
+        }
+        symbols.sort_by_key(|(address, _, _)| *address);
+        SymbolTable {
+            symbols,
+        }
+    }
+
+    /// Reads the function symbols of a binary, from .symtab if it has one, otherwise .dynsym.
+    ///
+    /// # Arguments
+    ///
+    /// * `object`: The binary.
+    ///
+    /// returns: SymbolTable, which is empty if the binary has no function symbols
+    pub fn from_object<'data, R: ReadRef<'data>>(object: &object::File<'data, R>) -> SymbolTable {
+        let Some(table) = object.symbol_table().or_else(|| object.dynamic_symbol_table()) else {
+            return SymbolTable::default();
+        };
+        let symbols = table.symbols()
+            .filter(|symbol| symbol.is_definition() && symbol.kind() == SymbolKind::Text)
+            .filter_map(|symbol| {
+                let name = symbol.name().ok().filter(|name| !name.is_empty())?;
+                Some((symbol.address(), symbol.size(), name.to_string()))
+            })
+            .collect();
+        SymbolTable::new(symbols)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Finds the function containing an address. Functions without a size are assumed to extend to
+    /// the next function.
+    ///
+    /// # Arguments
+    ///
+    /// * `address`: Address to look up.
+    ///
+    /// returns: (function, offset of the address from the start of the function), or None if no
+    /// function contains the address
+    pub fn lookup(&self, address: u64) -> Option<(&str, u64)> {
+        let index = self.symbols.partition_point(|(start, _, _)| *start <= address).checked_sub(1)?;
+        let (start, size, name) = &self.symbols[index];
+        let offset = address - start;
+        if *size > 0 && offset >= *size {
+            return None;
+        }
+        Some((name, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::symbol_table::SymbolTable;
+
+    #[test]
+    fn lookup_test() {
+        let symbol_table = SymbolTable::new(vec![
+            (0x1200, 0, String::from("_start")),
+            (0x1000, 0x40, String::from("_ZN5store6Buffer4grabEm")),
+            (0x1100, 0x10, String::from("main")),
+        ]);
+        assert_eq!(symbol_table.lookup(0x1000), Some(("store::Buffer::grab(unsigned long)", 0)));
+        assert_eq!(symbol_table.lookup(0x103f), Some(("store::Buffer::grab(unsigned long)", 0x3f)));
+        assert_eq!(symbol_table.lookup(0x1040), None);
+        assert_eq!(symbol_table.lookup(0x110c), Some(("main", 0xc)));
+        assert_eq!(symbol_table.lookup(0x1300), Some(("_start", 0x100)));
+        assert_eq!(symbol_table.lookup(0xfff), None);
+        assert!(SymbolTable::default().lookup(0x1000).is_none());
+    }
+}
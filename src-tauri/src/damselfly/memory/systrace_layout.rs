@@ -0,0 +1,220 @@
+//! Describes where MemorySysTraceParser finds the fields of each SysTrace line, so that logs from
+//! firmware with slightly different line headers can be parsed.
+//!
+//! A line is a header, a separator, then the payload (the memory operation). The header is split
+//! into columns by whitespace, counting from 0. With the default layout:
+//!
+//! ```text
+//! 00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14
+//! 0         1        2                3 4    5        6    7 8            ^ payload
+//! ```
+//!
+//! Column 1 is a free-running hardware cycle counter. If its clock rate is given, realtime
+//! timestamps are derived from the counter instead of the millisecond-resolution timestamp.
+//!
+//! Layouts are loaded from JSON, or TOML if the file name ends in .toml. Fields that are left out
+//! keep their default value:
+//!
+//! ```text
+//! {"payload_separator": ">", "timestamp_column": 5, "units_column": 6, "task_id_marker": "DT:",
+//!  "counter_column": 1, "counter_frequency_hz": null, "counter_bits": 32}
+//! ```
+use std::fs;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::damselfly::damselfly_error::DamselflyError;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SysTraceLayout {
+    /// Text that separates the header from the payload. The header ends at its first occurrence.
+    pub payload_separator: String,
+    /// Column of the header holding the realtime timestamp.
+    pub timestamp_column: usize,
+    /// Column of the header holding the timestamp's unit (us, ms or s). If None, the unit directly
+    /// follows the timestamp in its column, e.g. 3.677s.
+    pub units_column: Option<usize>,
+    /// Text in the header that is followed by the hex ID of the task that logged the line, e.g.
+    /// DT:0xE14DEEBC. If empty, lines have no task ID.
+    pub task_id_marker: String,
+    /// Column of the header holding the hex cycle counter.
+    pub counter_column: usize,
+    /// Rate at which the cycle counter ticks. If None, the counter is ignored.
+    pub counter_frequency_hz: Option<f64>,
+    /// Width of the cycle counter, after which it wraps to 0.
+    pub counter_bits: u32,
+}
+
+impl Default for SysTraceLayout {
+    fn default() -> Self {
+        Self {
+            payload_separator: String::from(">"),
+            timestamp_column: 5,
+            units_column: Some(6),
+            task_id_marker: String::from("DT:"),
+            counter_column: 1,
+            counter_frequency_hz: None,
+            counter_bits: 32,
+        }
+    }
+}
+
+impl SysTraceLayout {
+    /// Loads a layout from a JSON or TOML file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to the layout. Files ending in .toml are parsed as TOML, others as JSON.
+    ///
+    /// returns: SysTraceLayout, or an error if the file cannot be read or is not a valid layout
+    pub fn load(path: &str) -> Result<SysTraceLayout, DamselflyError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|source| DamselflyError::Io { path: path.to_string(), source })?;
+        let bad_layout = |reason: String| DamselflyError::BadLayout { path: path.to_string(), reason };
+        let layout: SysTraceLayout = if path.to_lowercase().ends_with(".toml") {
+            toml::from_str(&contents).map_err(|error| bad_layout(error.to_string()))?
+        } else {
+            serde_json::from_str(&contents).map_err(|error| bad_layout(error.to_string()))?
+        };
+        layout.validate().map_err(bad_layout)?;
+        Ok(layout)
+    }
+
+    /// Checks that the layout can locate every field.
+    ///
+    /// returns: nothing, or the reason the layout is invalid
+    pub fn validate(&self) -> Result<(), String> {
+        if self.payload_separator.is_empty() {
+            return Err(String::from("payload_separator must not be empty"));
+        }
+        if self.units_column == Some(self.timestamp_column) {
+            return Err(String::from("units_column must differ from timestamp_column"));
+        }
+        if self.counter_frequency_hz.is_some_and(|frequency| !frequency.is_finite() || frequency <= 0.0) {
+            return Err(String::from("counter_frequency_hz must be positive"));
+        }
+        if !(1..=64).contains(&self.counter_bits) {
+            return Err(String::from("counter_bits must be between 1 and 64"));
+        }
+        Ok(())
+    }
+
+    /// Splits a line into its header and payload. Anything after a second separator is not part of
+    /// the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `line`: Raw line from the log.
+    ///
+    /// returns: (header, payload), or None if the line has no separator
+    pub fn split_line<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        let mut parts = line.split(self.payload_separator.as_str());
+        let header = parts.next()?;
+        let payload = parts.next()?;
+        Some((header, payload))
+    }
+
+    /// Gets the realtime timestamp from a line's header.
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: The part of the line before the separator.
+    ///
+    /// returns: (timestamp, units), or why they could not be found
+    pub fn parse_timestamp<'a>(&self, header: &'a str) -> Result<(&'a str, &'a str), &'static str> {
+        let columns = header.split_whitespace().collect::<Vec<_>>();
+        let timestamp = columns.get(self.timestamp_column)
+            .ok_or("failed to get timestamp")?;
+        match self.units_column {
+            Some(units_column) => {
+                let units = columns.get(units_column).ok_or("failed to get timestamp units")?;
+                Ok((timestamp, units))
+            }
+            None => {
+                let units_start = timestamp.find(char::is_alphabetic).ok_or("failed to get timestamp units")?;
+                Ok(timestamp.split_at(units_start))
+            }
+        }
+    }
+
+    /// Gets the cycle counter from a line's header.
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: The part of the line before the separator.
+    ///
+    /// returns: The counter, or the text that could not be parsed as one
+    pub fn parse_counter(&self, header: &str) -> Result<u64, String> {
+        let counter = header.split_whitespace().nth(self.counter_column).unwrap_or("");
+        u64::from_str_radix(counter, 16).map_err(|_| counter.to_string())
+    }
+
+    /// Parses the ID of the task (thread) that logged a line from its header. Some lines are logged
+    /// outside of any task, e.g. DT:  unknown, so these have no ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: The part of the line before the separator.
+    ///
+    /// returns: The task ID, if there is one.
+    pub fn parse_task_id(&self, header: &str) -> Option<usize> {
+        if self.task_id_marker.is_empty() {
+            return None;
+        }
+        let (_, task) = header.rsplit_once(self.task_id_marker.as_str())?;
+        let task = task.split_whitespace().next()?;
+        let task = task.strip_prefix("0x").or_else(|| task.strip_prefix("0X"))?;
+        usize::from_str_radix(task, 16).ok()
+    }
+}
+
+impl FromStr for SysTraceLayout {
+    type Err = String;
+
+    /// Parses a layout from JSON.
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        let layout: SysTraceLayout = serde_json::from_str(json).map_err(|error| error.to_string())?;
+        layout.validate()?;
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damselfly::memory::systrace_layout::SysTraceLayout;
+
+    #[test]
+    fn default_layout_test() {
+        let layout = SysTraceLayout::default();
+        let line = "00001068: 039dcb32 |V|A|005|        0 us   0003.677 s    < DT:0xE14DEEBC> + e150202c 14";
+        let (header, payload) = layout.split_line(line).unwrap();
+        assert_eq!(payload, " + e150202c 14");
+        assert_eq!(layout.parse_timestamp(header), Ok(("0003.677", "s")));
+        assert_eq!(layout.parse_task_id(header), Some(0xE14DEEBC));
+        assert_eq!(layout.parse_counter(header), Ok(0x039dcb32));
+        assert!(layout.split_line("no separator here").is_none());
+    }
+
+    #[test]
+    fn custom_layout_test() {
+        let layout: SysTraceLayout = r#"{"payload_separator": "|", "timestamp_column": 1, "units_column": null, "task_id_marker": "task="}"#
+            .parse()
+            .unwrap();
+        let (header, payload) = layout.split_line("[cpu0] 12.5ms task=0x1f | + e150202c 14").unwrap();
+        assert_eq!(payload, " + e150202c 14");
+        assert_eq!(layout.parse_timestamp(header), Ok(("12.5", "ms")));
+        assert_eq!(layout.parse_task_id(header), Some(0x1f));
+
+        let layout: SysTraceLayout = r#"{"timestamp_column": 2}"#.parse().unwrap();
+        assert_eq!(layout.units_column, Some(6));
+        assert_eq!(layout.parse_timestamp("a b c"), Err("failed to get timestamp units"));
+
+        assert!(r#"{"timestamp_column": 6}"#.parse::<SysTraceLayout>().is_err());
+        assert!(r#"{"timestamp_colum": 5}"#.parse::<SysTraceLayout>().is_err());
+        assert!(r#"{"payload_separator": ""}"#.parse::<SysTraceLayout>().is_err());
+        assert!(r#"{"counter_frequency_hz": 0}"#.parse::<SysTraceLayout>().is_err());
+        assert!(r#"{"counter_bits": 65}"#.parse::<SysTraceLayout>().is_err());
+    }
+}
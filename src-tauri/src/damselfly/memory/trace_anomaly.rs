@@ -0,0 +1,74 @@
+//! A memory operation that a correct allocator and program should never produce, found by
+//! TraceValidator.
+use std::fmt::{Display, Formatter};
+use serde::Serialize;
+use crate::damselfly::memory::memory_update::MemoryUpdateType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum AnomalyKind {
+    /// An address was freed (or reallocated) after it had already been freed.
+    DoubleFree,
+    /// An address was freed (or reallocated) that was never allocated.
+    UnknownFree,
+    /// An allocation overlaps an allocation that is still live.
+    OverlappingAllocation,
+}
+
+impl Display for AnomalyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            AnomalyKind::DoubleFree => "DOUBLE FREE",
+            AnomalyKind::UnknownFree => "UNKNOWN FREE",
+            AnomalyKind::OverlappingAllocation => "OVERLAPPING ALLOCATION",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TraceAnomaly {
+    kind: AnomalyKind,
+    update: MemoryUpdateType,
+    related_update: Option<MemoryUpdateType>,
+}
+
+impl TraceAnomaly {
+    /// Constructor.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind`: What is wrong with the update.
+    /// * `update`: The offending update.
+    /// * `related_update`: The earlier update it conflicts with: the first free for a double free,
+    ///   or the live allocation for an overlapping allocation.
+    ///
+    /// returns: TraceAnomaly
+    pub fn new(kind: AnomalyKind, update: MemoryUpdateType, related_update: Option<MemoryUpdateType>) -> Self {
+        Self {
+            kind,
+            update,
+            related_update,
+        }
+    }
+
+    pub fn get_kind(&self) -> AnomalyKind {
+        self.kind
+    }
+
+    pub fn get_update(&self) -> &MemoryUpdateType {
+        &self.update
+    }
+
+    pub fn get_related_update(&self) -> Option<&MemoryUpdateType> {
+        self.related_update.as_ref()
+    }
+}
+
+impl Display for TraceAnomaly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.related_update {
+            Some(related_update) => write!(f, "{}: {} (conflicts with {})", self.kind, self.update, related_update),
+            None => write!(f, "{}: {}", self.kind, self.update),
+        }
+    }
+}
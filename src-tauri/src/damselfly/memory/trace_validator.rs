@@ -0,0 +1,185 @@
+//! Checks a trace for operations that point to bugs in the allocator or the program using it:
+//! double frees, frees of addresses that were never allocated, and allocations that overlap a
+//! live allocation.
+use std::collections::{BTreeMap, HashMap};
+use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType, Reallocation};
+use crate::damselfly::memory::trace_anomaly::{AnomalyKind, TraceAnomaly};
+
+#[derive(Default)]
+pub struct TraceValidator {
+    live_allocations: BTreeMap<usize, MemoryUpdateType>,
+    largest_live_size: usize,
+    freed_addresses: HashMap<usize, MemoryUpdateType>,
+    anomalies: Vec<TraceAnomaly>,
+}
+
+impl TraceValidator {
+    /// Validates a trace in a single pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_updates`: Updates in the order they happened, without padding.
+    ///
+    /// returns: Every anomaly found, in the order they happened.
+    pub fn validate(memory_updates: &[MemoryUpdateType]) -> Vec<TraceAnomaly> {
+        let mut validator = TraceValidator::default();
+        for update in memory_updates {
+            validator.push_update(update);
+        }
+        validator.anomalies
+    }
+
+    /// Push an update into the validator, recording any anomalies it causes.
+    ///
+    /// # Arguments
+    ///
+    /// * `update`: The next update in the trace.
+    ///
+    /// returns: ()
+    pub fn push_update(&mut self, update: &MemoryUpdateType) {
+        match update {
+            MemoryUpdateType::Allocation(allocation) => {
+                self.allocate(update, allocation.get_absolute_address(), allocation.get_absolute_size());
+            }
+            MemoryUpdateType::Free(free) => {
+                // Freeing a null pointer is allowed and does nothing
+                if free.get_absolute_address() != 0 {
+                    self.free(update, free.get_absolute_address());
+                }
+            }
+            MemoryUpdateType::Reallocation(reallocation) => {
+                // Reallocating a null pointer is the same as allocating
+                if !Self::reallocates_null(reallocation) {
+                    self.free(update, reallocation.get_old_address());
+                }
+                self.allocate(update, reallocation.get_absolute_address(), reallocation.get_absolute_size());
+            }
+        }
+    }
+
+    pub fn get_anomalies(&self) -> &Vec<TraceAnomaly> {
+        &self.anomalies
+    }
+
+    /// Marks a span as live, checking that it does not overlap another live allocation.
+    ///
+    /// # Arguments
+    ///
+    /// * `update`: The update that allocated the span.
+    /// * `address`: Start of the span.
+    /// * `size`: Size of the span.
+    ///
+    /// returns: ()
+    fn allocate(&mut self, update: &MemoryUpdateType, address: usize, size: usize) {
+        if size > 0 {
+            // Any live allocation that overlaps must start before this one ends, and cannot start
+            // further back than the largest live allocation
+            let overlap = self.live_allocations
+                .range(..address + size)
+                .rev()
+                .take_while(|(start, _)| *start + self.largest_live_size > address)
+                .find(|(start, live_update)| *start + live_update.get_absolute_size() > address)
+                .map(|(_, live_update)| live_update.clone());
+            if let Some(overlap) = overlap {
+                self.anomalies.push(TraceAnomaly::new(AnomalyKind::OverlappingAllocation, update.clone(), Some(overlap)));
+            }
+        }
+        self.largest_live_size = self.largest_live_size.max(size);
+        self.live_allocations.insert(address, update.clone());
+        self.freed_addresses.remove(&address);
+    }
+
+    /// Marks the allocation at an address as freed, checking that it was live.
+    ///
+    /// # Arguments
+    ///
+    /// * `update`: The update that freed the address.
+    /// * `address`: Address of the allocation being freed.
+    ///
+    /// returns: ()
+    fn free(&mut self, update: &MemoryUpdateType, address: usize) {
+        if self.live_allocations.remove(&address).is_some() {
+            self.freed_addresses.insert(address, update.clone());
+        } else if let Some(first_free) = self.freed_addresses.get(&address) {
+            self.anomalies.push(TraceAnomaly::new(AnomalyKind::DoubleFree, update.clone(), Some(first_free.clone())));
+        } else {
+            self.anomalies.push(TraceAnomaly::new(AnomalyKind::UnknownFree, update.clone(), None));
+        }
+    }
+
+    fn reallocates_null(reallocation: &Reallocation) -> bool {
+        reallocation.get_old_address() == 0 && reallocation.get_old_size() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType, Reallocation};
+    use crate::damselfly::memory::trace_anomaly::AnomalyKind;
+    use crate::damselfly::memory::trace_validator::TraceValidator;
+
+    fn alloc(address: usize, size: usize, timestamp: usize) -> MemoryUpdateType {
+        Allocation::new(address, size, Arc::new(String::new()), timestamp, String::new()).wrap_in_enum()
+    }
+
+    fn free(address: usize, timestamp: usize) -> MemoryUpdateType {
+        Free::new(address, 0, Arc::new(String::new()), timestamp, String::new()).wrap_in_enum()
+    }
+
+    #[test]
+    fn valid_trace_test() {
+        let updates = vec![
+            alloc(0x1000, 8, 0),
+            alloc(0x1008, 8, 1),
+            free(0x1000, 2),
+            alloc(0x1000, 4, 3),
+            Reallocation::new(0x1020, 16, 0x1008, 8, Arc::new(String::new()), 4, String::new()).wrap_in_enum(),
+            Reallocation::new(0x1040, 8, 0, 0, Arc::new(String::new()), 5, String::new()).wrap_in_enum(),
+            free(0x1000, 6),
+            free(0, 7),
+        ];
+        assert!(TraceValidator::validate(&updates).is_empty());
+    }
+
+    #[test]
+    fn double_free_test() {
+        let updates = vec![alloc(0x1000, 8, 0), free(0x1000, 1), free(0x1000, 2)];
+        let anomalies = TraceValidator::validate(&updates);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].get_kind(), AnomalyKind::DoubleFree);
+        assert_eq!(anomalies[0].get_update().get_timestamp(), 2);
+        assert_eq!(anomalies[0].get_related_update().unwrap().get_timestamp(), 1);
+    }
+
+    #[test]
+    fn unknown_free_test() {
+        let updates = vec![alloc(0x1000, 8, 0), free(0x1010, 1)];
+        let anomalies = TraceValidator::validate(&updates);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].get_kind(), AnomalyKind::UnknownFree);
+        assert!(anomalies[0].get_related_update().is_none());
+    }
+
+    #[test]
+    fn overlapping_allocation_test() {
+        let updates = vec![alloc(0x1000, 64, 0), alloc(0x1080, 8, 1), alloc(0x1020, 8, 2), alloc(0x1040, 8, 3)];
+        let anomalies = TraceValidator::validate(&updates);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].get_kind(), AnomalyKind::OverlappingAllocation);
+        assert_eq!(anomalies[0].get_update().get_timestamp(), 2);
+        assert_eq!(anomalies[0].get_related_update().unwrap().get_timestamp(), 0);
+    }
+
+    #[test]
+    fn realloc_of_freed_address_test() {
+        let updates = vec![
+            alloc(0x1000, 8, 0),
+            free(0x1000, 1),
+            Reallocation::new(0x1010, 16, 0x1000, 8, Arc::new(String::new()), 2, String::new()).wrap_in_enum(),
+        ];
+        let anomalies = TraceValidator::validate(&updates);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].get_kind(), AnomalyKind::DoubleFree);
+    }
+}
@@ -2,10 +2,22 @@
 use std::str::FromStr;
 use num_traits::cast::FromPrimitive;
 
+use crate::damselfly::damselfly_error::DamselflyError;
+
 pub struct Utility {}
 
 impl Utility {
-    pub fn convert_to_microseconds(time_string: &String) -> u64 {
+    /// Converts a realtime timestamp such as "0003.677 s" to microseconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_string`: The timestamp, in us, ms or s.
+    ///
+    /// returns: The timestamp in microseconds, or BadTimestamp if it cannot be parsed, has an
+    /// unknown unit, or is negative or too large. The error's line is 0, so callers parsing a log
+    /// should replace it with the line being parsed.
+    pub fn convert_to_microseconds(time_string: &str) -> Result<u64, DamselflyError> {
+        let bad_timestamp = || DamselflyError::BadTimestamp { line: 0, text: time_string.to_string() };
         let mut time = String::new();
         let mut units = String::new();
         for char in time_string.chars() {
@@ -17,43 +29,48 @@ impl Utility {
         }
         let time = time.trim();
         let units = units.trim();
-        let time_float = f64::from_str(time)
-            .expect("[Utility::convert_to_microseconds]: Failed to parse time_float from time String");
-        match units {
-            "us" => u64::from_f64(time_float)
-                .expect("[Utility::convert_to_microseconds]: Failed to convert time_float to u64"),
-            "ms" => u64::from_f64(time_float * 1000.0)
-                .expect("[Utility::convert_to_microseconds]: Failed to convert time_float to u64"),
-            "s" => u64::from_f64(time_float * 1000000.0)
-                .expect("[Utility::convert_to_microseconds]: Failed to convert time_float to u64"),
-            _ => panic!("[Utility::convert_to_microseconds]: Invalid unit {units}"),
-        }
+        let time_float = f64::from_str(time).map_err(|_| bad_timestamp())?;
+        let microseconds_per_unit = match units {
+            "us" => 1.0,
+            "ms" => 1000.0,
+            "s" => 1000000.0,
+            _ => return Err(bad_timestamp()),
+        };
+        u64::from_f64(time_float * microseconds_per_unit).ok_or_else(bad_timestamp)
     }
-    
-    pub fn round_to_nearest_multiple_of(value: u64, multiple_of: u64) -> u64 {
-        ((value as f64 / multiple_of as f64).round() as u64) * multiple_of
+
+    pub fn round_to_nearest_multiple_of(value: f64, multiple_of: u64) -> u64 {
+        ((value / multiple_of as f64).round() as u64) * multiple_of
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::damselfly::damselfly_error::DamselflyError;
     use crate::damselfly::memory::utility::Utility;
 
     #[test]
     fn convert_seconds_to_microseconds_test() {
         let time = " 0008.157 s ".to_string();
-        assert_eq!(Utility::convert_to_microseconds(&time), 8157000);
+        assert_eq!(Utility::convert_to_microseconds(&time).unwrap(), 8157000);
     }
 
     #[test]
     fn convert_milliseconds_to_microseconds_test() {
         let time = "0083.339 ms   ".to_string();
-        assert_eq!(Utility::convert_to_microseconds(&time), 83339);
+        assert_eq!(Utility::convert_to_microseconds(&time).unwrap(), 83339);
     }
 
     #[test]
     fn convert_microseconds_to_microseconds_test() {
         let time = " 230 us".to_string();
-        assert_eq!(Utility::convert_to_microseconds(&time), 230);
+        assert_eq!(Utility::convert_to_microseconds(&time).unwrap(), 230);
+    }
+
+    #[test]
+    fn convert_invalid_timestamp_test() {
+        for time in ["-1 us", "99999999999999999999999 s", "NaN us", "12 min", "twelve us", ""] {
+            assert!(matches!(Utility::convert_to_microseconds(time), Err(DamselflyError::BadTimestamp { line: 0, .. })), "{time}");
+        }
     }
-}
\ No newline at end of file
+}
@@ -89,54 +89,21 @@ impl DistinctBlockCounter {
     /// 
     /// returns: () 
     pub fn push_update(&mut self, update: &MemoryUpdateType) {
-        let start = update.get_start().saturating_sub(self.left_padding);
-        let end = update.get_end().saturating_add(self.right_padding);
-        let mut left_attached = false;
-        let mut right_attached = false;
-        let mut block_delta: i64 = 0;
-        
-        if self.ends_set.contains(&start) {
-            left_attached = true;
-        }
-        if self.starts_set.contains(&end) {
-            right_attached = true;
-        }
-
-        
-        match update {
-            MemoryUpdateType::Allocation(_) => {
-                // glues together two blocks, reducing fragmentation
-                if left_attached && right_attached {
-                    block_delta = -1;
+        let block_delta = match update {
+            MemoryUpdateType::Allocation(_) => self.push_allocation(update.get_start(), update.get_end()),
+            MemoryUpdateType::Free(_) => self.push_free(update.get_start(), update.get_end()),
+            MemoryUpdateType::Reallocation(reallocation) => {
+                // A reallocation frees the old block, then allocates the new one
+                let mut block_delta = 0;
+                if reallocation.get_old_size() != 0 {
+                    let old_start = reallocation.get_old_address();
+                    block_delta += self.push_free(old_start, old_start + reallocation.get_old_size());
                 }
-
-                // island block with no blocks surrounding it, increasing fragmentation
-                if !left_attached && !right_attached {
-                    block_delta = 1;
+                if reallocation.get_absolute_size() != 0 {
+                    let new_start = reallocation.get_absolute_address();
+                    block_delta += self.push_allocation(new_start, new_start + reallocation.get_absolute_size());
                 }
-
-                // otherwise, glues onto an existing block, leaving fragmentation unchanged
-                self.starts_set.insert(start);
-                self.ends_set.insert(end);
-                self.starts_tree.insert(start);
-                self.ends_tree.insert(end);
-            }
-            MemoryUpdateType::Free(_) => {
-                // breaks a block into two blocks, increasing fragmentation
-                if left_attached && right_attached {
-                    block_delta = 1;
-                }
-                
-                // frees an island block, reducing fragmentation
-                if !left_attached && !right_attached {
-                    block_delta = -1;
-                }
-                
-                // otherwise, frees a block glued onto another, leaving fragmentation unchanged
-                self.starts_set.remove(&start);
-                self.ends_set.remove(&end);
-                self.starts_tree.remove(&start);
-                self.ends_tree.remove(&end);
+                block_delta
             }
         };
         
@@ -148,6 +115,75 @@ impl DistinctBlockCounter {
         self.distinct_blocks = self.distinct_blocks.saturating_add_signed(block_delta as i128);
     }
 
+    /// Adds an allocated span to the tracked blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `start`: Start of the allocated span, before padding.
+    /// * `end`: End of the allocated span, before padding.
+    ///
+    /// returns: The change in the number of distinct blocks.
+    fn push_allocation(&mut self, start: usize, end: usize) -> i64 {
+        let (start, end, left_attached, right_attached) = self.pad_and_attach(start, end);
+        let mut block_delta = 0;
+
+        // glues together two blocks, reducing fragmentation
+        if left_attached && right_attached {
+            block_delta = -1;
+        }
+
+        // island block with no blocks surrounding it, increasing fragmentation
+        if !left_attached && !right_attached {
+            block_delta = 1;
+        }
+
+        // otherwise, glues onto an existing block, leaving fragmentation unchanged
+        self.starts_set.insert(start);
+        self.ends_set.insert(end);
+        self.starts_tree.insert(start);
+        self.ends_tree.insert(end);
+        block_delta
+    }
+
+    /// Removes a freed span from the tracked blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `start`: Start of the freed span, before padding.
+    /// * `end`: End of the freed span, before padding.
+    ///
+    /// returns: The change in the number of distinct blocks.
+    fn push_free(&mut self, start: usize, end: usize) -> i64 {
+        let (start, end, left_attached, right_attached) = self.pad_and_attach(start, end);
+        let mut block_delta = 0;
+
+        // breaks a block into two blocks, increasing fragmentation
+        if left_attached && right_attached {
+            block_delta = 1;
+        }
+
+        // frees an island block, reducing fragmentation
+        if !left_attached && !right_attached {
+            block_delta = -1;
+        }
+
+        // otherwise, frees a block glued onto another, leaving fragmentation unchanged
+        self.starts_set.remove(&start);
+        self.ends_set.remove(&end);
+        self.starts_tree.remove(&start);
+        self.ends_tree.remove(&end);
+        block_delta
+    }
+
+    /// Applies padding to a span and checks whether it touches any existing blocks.
+    ///
+    /// returns: (padded start, padded end, left attached, right attached)
+    fn pad_and_attach(&self, start: usize, end: usize) -> (usize, usize, bool, bool) {
+        let start = start.saturating_sub(self.left_padding);
+        let end = end.saturating_add(self.right_padding);
+        (start, end, self.ends_set.contains(&start), self.starts_set.contains(&end))
+    }
+
     /// Calculates free blocks and stores them within the struct.
     pub fn calculate_free_blocks(&mut self) {
         let mut starts_iter = self.starts_tree.iter();
@@ -222,18 +258,8 @@ impl DistinctBlockCounter {
     /// 
     /// returns: () 
     fn calculate_new_memory_bounds(&mut self, update: &MemoryUpdateType) {
-        let new_start;
-        let new_stop;
-        match update {
-            MemoryUpdateType::Allocation(allocation) => {
-                new_start = allocation.get_absolute_address();
-                new_stop = new_start + allocation.get_absolute_size()
-            }
-            MemoryUpdateType::Free(free) => {
-                new_start = free.get_absolute_address();
-                new_stop = new_start + free.get_absolute_size();
-            }
-        }
+        let new_start = update.get_start();
+        let new_stop = update.get_end();
         self.start = min(self.start, new_start);
         self.stop = max(self.stop, new_stop);
     }
@@ -252,15 +278,17 @@ impl DistinctBlockCounter {
 
 }
 
+#[cfg(test)]
 mod tests {
     use crate::damselfly::consts::{TEST_BINARY_PATH, TEST_LOG};
     use crate::damselfly::memory::memory_parsers::{MemoryParser, MemorySysTraceParser};
-    use crate::damselfly::memory::memory_update::MemoryUpdateType;
+    use std::sync::Arc;
+    use crate::damselfly::memory::memory_update::{Allocation, MemoryUpdateType, Reallocation};
     use crate::damselfly::update_interval::distinct_block_counter::DistinctBlockCounter;
 
     fn _initialise_test_log() -> (Vec<MemoryUpdateType>, DistinctBlockCounter) {
         let mst_parser = MemorySysTraceParser::new();
-        let updates = mst_parser.parse_log_directly(TEST_LOG, TEST_BINARY_PATH).memory_updates;
+        let updates = mst_parser.parse_log_directly(TEST_LOG, TEST_BINARY_PATH).unwrap().memory_updates;
         (updates, DistinctBlockCounter::default())
     }
 
@@ -287,4 +315,17 @@ mod tests {
         assert_eq!(distinct_blocks, 4);
         assert_eq!(free_blocks.len(), 3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn realloc_in_place_growth_test() {
+        let mut distinct_block_counter = DistinctBlockCounter::new(vec![], 0, 0, Some((0, 64)));
+        distinct_block_counter.push_update(&MemoryUpdateType::Allocation(Allocation::new(8, 8, Arc::new(String::new()), 0, String::new())));
+        distinct_block_counter.push_update(&MemoryUpdateType::Allocation(Allocation::new(24, 8, Arc::new(String::new()), 1, String::new())));
+        assert_eq!(distinct_block_counter.get_distinct_blocks(), 2);
+
+        // Growing the first allocation into the gap glues it onto the second
+        distinct_block_counter.push_update(&MemoryUpdateType::Reallocation(Reallocation::new(8, 16, 8, 8, Arc::new(String::new()), 2, String::new())));
+        assert_eq!(distinct_block_counter.get_distinct_blocks(), 1);
+        assert_eq!(distinct_block_counter.get_free_blocks(), vec![(0, 8), (32, 64)]);
+    }
+}
@@ -42,7 +42,7 @@ mod tests {
 
     fn initialise_test_log() -> OverlapFinder {
         let mst_parser = MemorySysTraceParser::new();
-        let updates = mst_parser.parse_log_directly(OVERLAP_FINDER_TEST_LOG, TEST_BINARY_PATH).memory_updates;
+        let updates = mst_parser.parse_log_directly(OVERLAP_FINDER_TEST_LOG, TEST_BINARY_PATH).unwrap().memory_updates;
         let intervals = UpdateIntervalFactory::new(updates).construct_enum_vector();
         OverlapFinder::new(intervals)
     }
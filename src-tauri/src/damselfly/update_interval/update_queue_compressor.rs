@@ -1,6 +1,7 @@
-//! Utility struct that compresses updates. It does this by deleting allocs that have a corresponding free.
+//! Utility struct that compresses updates. It does this by deleting allocs that have a corresponding free,
+//! and replacing allocs with any reallocs of them.
 //! Use this when you only care about the result of a collection of updates.
-use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType};
+use crate::damselfly::memory::memory_update::{MemoryUpdate, MemoryUpdateType, Reallocation};
 use crate::damselfly::update_interval::UpdateInterval;
 
 pub struct UpdateQueueCompressor { }
@@ -18,14 +19,17 @@ impl UpdateQueueCompressor {
         for update in updates {
             match update {
                 MemoryUpdateType::Allocation(allocation) => compressed_updates.push(allocation.clone().wrap_in_enum()),
+                MemoryUpdateType::Reallocation(reallocation) => {
+                    Self::remove_reallocated(&mut compressed_updates, reallocation);
+                    compressed_updates.push(reallocation.clone().wrap_in_enum());
+                }
                 MemoryUpdateType::Free(free) => {
                     let alloc_to_remove = compressed_updates
                         .iter()
                         .position(|update| {
                             match update {
-                                MemoryUpdateType::Allocation(allocation) =>
-                                    allocation.get_absolute_address() == free.get_absolute_address(),
                                 MemoryUpdateType::Free(_) => panic!("[UpdateQueueCompressor::compress_to_allocs_only]: Free found in compressed_updates"),
+                                _ => update.get_absolute_address() == free.get_absolute_address(),
                             }
                         })
                         .or(None);
@@ -44,15 +48,18 @@ impl UpdateQueueCompressor {
         for update in updates {
             match update {
                 MemoryUpdateType::Allocation(allocation) => compressed_updates.push(allocation.clone().wrap_in_enum()),
+                MemoryUpdateType::Reallocation(reallocation) => {
+                    Self::remove_reallocated(&mut compressed_updates, reallocation);
+                    compressed_updates.push(reallocation.clone().wrap_in_enum());
+                }
                 MemoryUpdateType::Free(free) => {
                     compressed_updates.remove(
                         compressed_updates
                             .iter()
                             .position(|update| {
                                 match update {
-                                    MemoryUpdateType::Allocation(allocation) =>
-                                        allocation.get_absolute_address() == free.get_absolute_address(),
                                     MemoryUpdateType::Free(_) => panic!("[UpdateQueueCompressor::compress_to_allocs_only]: Free found in compressed_updates"),
+                                    _ => update.get_absolute_address() == free.get_absolute_address(),
                                 }
                             })
                             .expect("[UpdateQueueCompressor::strip_frees_and_corresponding_allocs]: Cannot find alloc corresponding to free"));
@@ -74,15 +81,18 @@ impl UpdateQueueCompressor {
         for update in updates {
             match &update.val {
                 MemoryUpdateType::Allocation(allocation) => compressed_updates.push(allocation.clone().wrap_in_enum()),
+                MemoryUpdateType::Reallocation(reallocation) => {
+                    Self::remove_reallocated(&mut compressed_updates, reallocation);
+                    compressed_updates.push(reallocation.clone().wrap_in_enum());
+                }
                 MemoryUpdateType::Free(free) => {
                     compressed_updates.remove(
                         compressed_updates
                             .iter()
                             .position(|update| {
                                 match update {
-                                    MemoryUpdateType::Allocation(allocation) => 
-                                        allocation.get_absolute_address() == free.get_absolute_address(),
                                     MemoryUpdateType::Free(_) => panic!("[UpdateQueueCompressor::compress_intervals]: Free found in compressed_updates"),
+                                    _ => update.get_absolute_address() == free.get_absolute_address(),
                                 }
                             })
                             .expect("[UpdateQueueCompressor::compress_intervals]: Cannot find alloc corresponding to free"));
@@ -91,6 +101,24 @@ impl UpdateQueueCompressor {
         }
         compressed_updates
     }
+
+    /// Removes the update that a reallocation replaces, if it is present. A reallocation of
+    /// nothing (e.g. realloc with a null pointer) has nothing to replace.
+    ///
+    /// # Arguments
+    ///
+    /// * `compressed_updates`: Updates compressed so far.
+    /// * `reallocation`: The reallocation replacing an earlier update.
+    ///
+    /// returns: ()
+    fn remove_reallocated(compressed_updates: &mut Vec<MemoryUpdateType>, reallocation: &Reallocation) {
+        let update_to_remove = compressed_updates
+            .iter()
+            .position(|update| update.get_absolute_address() == reallocation.get_old_address());
+        if let Some(update_to_remove) = update_to_remove {
+            compressed_updates.remove(update_to_remove);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -105,7 +133,7 @@ mod tests {
 
     fn initialise_test_log() -> OverlapFinder {
         let mst_parser = MemorySysTraceParser::new();
-        let updates = mst_parser.parse_log_directly(OVERLAP_FINDER_TEST_LOG, TEST_BINARY_PATH).memory_updates;
+        let updates = mst_parser.parse_log_directly(OVERLAP_FINDER_TEST_LOG, TEST_BINARY_PATH).unwrap().memory_updates;
         let update_intervals = UpdateIntervalFactory::new(updates).construct_enum_vector();
         OverlapFinder::new(update_intervals)
     }
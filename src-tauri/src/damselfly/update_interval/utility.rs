@@ -22,6 +22,7 @@ impl Utility {
                 let free_address = free.get_absolute_address();
                 (free_address, free_address + free.get_absolute_size())
             }
+            MemoryUpdateType::Reallocation(reallocation) => reallocation.get_span(),
         };
 
         (start, stop)
@@ -1,11 +1,18 @@
 //! A single instance of Damselfly, which contains a graph and a map for a single pool.
 //! To have multiple pools, instantiate a DamselflyInstance for each pool and store them in
 //! DamselflyViewer.
-use crate::damselfly::memory::memory_usage_stats::MemoryUsageStats;
+use std::collections::BTreeSet;
 use rust_lapper::Lapper;
 use crate::damselfly::consts::{DEFAULT_OPERATION_LOG_SIZE, DEFAULT_SAMPLE_INTERVAL};
+use crate::damselfly::damselfly_error::DamselflyError;
+use crate::damselfly::memory::allocation_site_factory::{AllocationSite, AllocationSiteFactory};
+use crate::damselfly::memory::allocation_statistics::AllocationStatistics;
+use crate::damselfly::memory::allocation_statistics_factory::AllocationStatisticsFactory;
+use crate::damselfly::memory::leak_report_factory::{LeakGroup, LeakReportFactory};
 use crate::damselfly::memory::memory_status::MemoryStatus;
 use crate::damselfly::memory::memory_update::MemoryUpdateType;
+use crate::damselfly::memory::memory_usage_factory::MemoryUsageFactory;
+use crate::damselfly::memory::pool_lifetime::PoolLifetime;
 use crate::damselfly::memory::sampled_memory_usages::SampledMemoryUsages;
 use crate::damselfly::update_interval::update_interval_factory::UpdateIntervalFactory;
 use crate::damselfly::viewer::graph_viewer::GraphViewer;
@@ -13,6 +20,17 @@ use crate::damselfly::viewer::map_viewer::MapViewer;
 
 pub struct DamselflyInstance {
     name: String,
+    lowest_address: usize,
+    highest_address: usize,
+    padding: (usize, usize),
+    cache_size: usize,
+    max_timestamp: u64,
+    memory_updates: Vec<MemoryUpdateType>,
+    thread_filter: Option<Vec<usize>>,
+    operation_count: usize,
+    // Lifetime of the pool, and the same lifetime renumbered for the operations being shown
+    pool_lifetime: Option<PoolLifetime>,
+    shown_pool_lifetime: Option<PoolLifetime>,
     graph_viewer: GraphViewer,
     map_viewer: MapViewer,
     full_lapper: Lapper<usize, MemoryUpdateType>,
@@ -25,16 +43,69 @@ impl DamselflyInstance {
     ///
     /// * `name`: Name.
     /// * `memory_updates`: Updates to store in this DamselflyInstance. Create these using a MemoryParser.
-    /// * `memory_usage_stats`: Stats to plot on the graph.
+    /// * `padding`: (left, right) padding that has been applied to the updates, used when computing
+    ///   the stats to plot on the graph.
     /// * `lowest_address`: Lowest address - from pool bounds computed during parsing.
     /// * `highest_address`: Highest address - from pool bounds computed during parsing.
     /// * `cache_size`: Interval at which maps should be cached.
     /// * `max_timestamp`: Max absolute operation timestamp to show on the graph - computed during parsing.
     ///
-    /// returns: DamselflyInstance
-    pub fn new(name: String, memory_updates: Vec<MemoryUpdateType>, memory_usage_stats: MemoryUsageStats,
+    /// returns: DamselflyInstance, or BadTimestamp if an update's realtime timestamp is invalid
+    pub fn new(name: String, memory_updates: Vec<MemoryUpdateType>, padding: (usize, usize),
                lowest_address: usize, highest_address: usize, cache_size: usize, max_timestamp: u64,
-    ) -> Self {
+    ) -> Result<Self, DamselflyError> {
+        let (graph_viewer, map_viewer, full_lapper) = Self::build_viewers(
+            &name, memory_updates.clone(), padding, lowest_address, highest_address, cache_size, max_timestamp
+        )?;
+
+        Ok(Self {
+            name,
+            lowest_address,
+            highest_address,
+            padding,
+            cache_size,
+            max_timestamp,
+            operation_count: memory_updates.len(),
+            memory_updates,
+            thread_filter: None,
+            pool_lifetime: None,
+            shown_pool_lifetime: None,
+            graph_viewer,
+            map_viewer,
+            full_lapper,
+        })
+    }
+
+    /// Sets when the pool is created, resized and deleted. While the pool does not exist, or
+    /// outside its bounds at the time, the map shows memory as unused.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_lifetime`: Lifetime of the pool, with positions counting the operations in this
+    ///   instance. None if the pool exists for the whole trace.
+    ///
+    /// returns: DamselflyInstance
+    pub fn with_pool_lifetime(mut self, pool_lifetime: Option<PoolLifetime>) -> Self {
+        self.pool_lifetime = pool_lifetime;
+        self.shown_pool_lifetime = self.pool_lifetime.clone();
+        self
+    }
+
+    /// Builds the graph, map and lookup structures for a set of updates.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_updates`: Updates to show, with operation timestamps counting up from 0.
+    /// * the rest: see DamselflyInstance::new.
+    ///
+    /// returns: (GraphViewer, MapViewer, full lapper), or BadTimestamp if an update's realtime
+    /// timestamp is invalid
+    fn build_viewers(name: &str, memory_updates: Vec<MemoryUpdateType>, padding: (usize, usize),
+                     lowest_address: usize, highest_address: usize, cache_size: usize, max_timestamp: u64,
+    ) -> Result<(GraphViewer, MapViewer, Lapper<usize, MemoryUpdateType>), DamselflyError> {
+        let memory_usage_stats = MemoryUsageFactory::new(memory_updates.clone(), padding.0, padding.1,
+                                                         lowest_address, highest_address)
+            .calculate_usage_stats()?;
         let memory_usages = memory_usage_stats.get_memory_usages();
         let max_usage = memory_usage_stats.get_max_usage();
         let max_distinct_blocks = memory_usage_stats.get_max_distinct_blocks();
@@ -57,21 +128,114 @@ impl DamselflyInstance {
         );
 
         let update_intervals = UpdateIntervalFactory::new(memory_updates).construct_enum_vector();
-        let map_viewer = MapViewer::new(name.clone(), update_intervals.clone(), lowest_address, highest_address, cache_size as u64);
+        let map_viewer = MapViewer::new(name.to_string(), update_intervals.clone(), lowest_address, highest_address, cache_size as u64);
         let full_lapper = Lapper::new(update_intervals);
+        Ok((graph_viewer, map_viewer, full_lapper))
+    }
 
-        Self {
-            name,
-            graph_viewer,
-            map_viewer,
-            full_lapper,
+    /// Restricts the graphs and map to the memory operations made by some threads (ThreadX tasks).
+    /// Operation timestamps are renumbered to count only the operations that are shown.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_ids`: IDs of the threads to show, or None to show every operation.
+    ///
+    /// returns: (), or an error if none of the operations in this pool were made by the threads
+    pub fn set_thread_filter(&mut self, thread_ids: Option<Vec<usize>>) -> Result<(), DamselflyError> {
+        if thread_ids == self.thread_filter {
+            return Ok(());
+        }
+        let shown_positions: Vec<usize> = match &thread_ids {
+            None => (0..self.memory_updates.len()).collect(),
+            Some(thread_ids) => self.memory_updates
+                .iter()
+                .enumerate()
+                .filter(|(_, update)| update.get_thread_id().is_some_and(|thread_id| thread_ids.contains(&thread_id)))
+                .map(|(position, _)| position)
+                .collect(),
+        };
+        let mut filtered_updates: Vec<MemoryUpdateType> = shown_positions
+            .iter()
+            .map(|&position| self.memory_updates[position].clone())
+            .collect();
+        if filtered_updates.is_empty() {
+            return Err(DamselflyError::NoThreadOperations { pool: self.name.clone() });
         }
+        for (index, update) in filtered_updates.iter_mut().enumerate() {
+            update.set_timestamp(index);
+        }
+
+        // The graphs end at the last operation shown, and maps are cached at least once
+        let (cache_size, max_timestamp) = match thread_ids {
+            None => (self.cache_size, self.max_timestamp),
+            Some(_) => (self.cache_size.min(filtered_updates.len()), filtered_updates.len() as u64 - 1),
+        };
+        let block_size = self.map_viewer.get_block_size();
+        self.operation_count = filtered_updates.len();
+        (self.graph_viewer, self.map_viewer, self.full_lapper) = Self::build_viewers(
+            &self.name, filtered_updates, self.padding, self.lowest_address, self.highest_address,
+            cache_size, max_timestamp
+        )?;
+        if block_size != self.map_viewer.get_block_size() {
+            self.map_viewer.set_block_size(block_size);
+        }
+        self.shown_pool_lifetime = self.pool_lifetime.as_ref()
+            .map(|pool_lifetime| pool_lifetime.renumber(&shown_positions));
+        self.thread_filter = thread_ids;
+        Ok(())
+    }
+
+    /// Gets the IDs of the threads being shown, or None if every operation is being shown.
+    pub fn get_thread_filter(&self) -> Option<&Vec<usize>> {
+        self.thread_filter.as_ref()
+    }
+
+    /// Gets the IDs of every thread that made a memory operation in this pool, in ascending order.
+    pub fn get_thread_ids(&self) -> Vec<usize> {
+        self.memory_updates
+            .iter()
+            .filter_map(|update| update.get_thread_id())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
     }
 
     pub fn get_name(&self) -> &str {
         &self.name
     }
 
+    /// Gets the bounds of the pool managed by this instance.
+    ///
+    /// returns: (lowest_address, highest_address)
+    pub fn get_pool_bounds(&self) -> (usize, usize) {
+        (self.lowest_address, self.highest_address)
+    }
+
+    /// Gets the number of memory operations in this pool.
+    pub fn get_operation_count(&self) -> usize {
+        self.operation_count
+    }
+
+    /// Gets the peak number of bytes allocated at any point in the trace.
+    pub fn get_max_usage(&self) -> i128 {
+        self.graph_viewer.get_max_usage()
+    }
+
+    /// Gets the peak number of distinct blocks at any point in the trace.
+    pub fn get_max_distinct_blocks(&self) -> usize {
+        self.graph_viewer.get_max_distinct_blocks()
+    }
+
+    /// Gets the worst free segment fragmentation seen at any point in the trace.
+    pub fn get_max_free_segment_fragmentation(&self) -> u128 {
+        self.graph_viewer.get_max_free_segment_fragmentation()
+    }
+
+    /// Gets the size of the largest free block seen at any point in the trace.
+    pub fn get_max_largest_free_block(&self) -> u128 {
+        self.graph_viewer.get_max_largest_free_block()
+    }
+
     /// Renders the memory map in full at a specified timestamp, truncating regions that are too large
     /// for legibility.
     ///
@@ -94,7 +258,17 @@ impl DamselflyInstance {
         truncate_after: u64,
     ) -> (u64, Vec<(i64, u64, usize)>) {
         self.map_viewer.set_timestamp(timestamp as usize);
-        let full_map = self.map_viewer.paint_map_full_from_cache();
+        let mut full_map = self.map_viewer.paint_map_full_from_cache();
+        if let Some(pool_lifetime) = &self.shown_pool_lifetime {
+            // The map includes the operation at the timestamp
+            let pool_bounds = pool_lifetime.get_bounds_after(timestamp as usize + 1);
+            for block in full_map.iter_mut() {
+                let address = block.get_address();
+                if !pool_bounds.is_some_and(|(start, end)| (start..end).contains(&address)) {
+                    *block = MemoryStatus::Unused(address);
+                }
+            }
+        }
 
         // parent address, address, status
         let mut result: Vec<(i64, u64, usize)> = Vec::new();
@@ -349,7 +523,194 @@ impl DamselflyInstance {
             .collect()
     }
 
+    /// Finds the allocations that are still live at an operation timestamp, grouped by the
+    /// callstack that made them. Only the operations shown by the thread filter are considered.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp`: Operation timestamp to check at, or None to check at the end of the trace.
+    ///
+    /// returns: Vec<LeakGroup>, largest total size first
+    pub fn get_leak_report(&self, timestamp: Option<usize>) -> Vec<LeakGroup> {
+        let memory_updates = self.full_lapper
+            .iter()
+            .map(|interval| interval.val.clone())
+            .collect();
+        LeakReportFactory::new(memory_updates, self.padding.0, self.padding.1)
+            .calculate_leaks(timestamp)
+    }
+
+    /// Finds the allocations that are still live at a realtime timestamp, grouped by the
+    /// callstack that made them.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp`: Realtime timestamp.
+    ///
+    /// returns: Vec<LeakGroup>, largest total size first
+    pub fn get_leak_report_realtime(&self, timestamp: u64) -> Vec<LeakGroup> {
+        let timestamp = self.graph_viewer.get_operation_timestamp_of_realtime_timestamp(timestamp) as usize;
+        self.get_leak_report(Some(timestamp))
+    }
+
+    /// Aggregates the operations in this pool by the callstack that made them, to find the code
+    /// paths that allocate the most. Only the operations shown by the thread filter are considered.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames`: Number of frames (from the top of the callstack) to group by, or None to group
+    ///   by the full callstack.
+    ///
+    /// returns: Vec<AllocationSite>, most bytes allocated first, or an error if a realtime
+    /// timestamp is invalid
+    pub fn get_allocation_sites(&self, frames: Option<usize>) -> Result<Vec<AllocationSite>, DamselflyError> {
+        let memory_updates = self.full_lapper
+            .iter()
+            .map(|interval| interval.val.clone())
+            .collect();
+        AllocationSiteFactory::new(memory_updates, self.padding.1)
+            .calculate_allocation_sites(frames)
+    }
+
+    /// Computes histograms of the sizes and lifetimes of the allocations made in a range of
+    /// operation timestamps. Only the operations shown by the thread filter are considered.
+    ///
+    /// # Arguments
+    ///
+    /// * `range`: (start, end) operation timestamps, inclusive, or None for the whole trace.
+    ///
+    /// returns: AllocationStatistics, or an error if a realtime timestamp is invalid
+    pub fn get_allocation_statistics(&self, range: Option<(usize, usize)>) -> Result<AllocationStatistics, DamselflyError> {
+        let memory_updates = self.full_lapper
+            .iter()
+            .map(|interval| interval.val.clone())
+            .collect();
+        AllocationStatisticsFactory::new(memory_updates, self.padding.1)
+            .calculate_statistics(range)
+    }
+
+    /// Computes histograms of the sizes and lifetimes of the allocations made in a range of
+    /// realtime timestamps.
+    ///
+    /// # Arguments
+    ///
+    /// * `start`: Realtime timestamp to start from, inclusive.
+    /// * `end`: Realtime timestamp to end at, inclusive.
+    ///
+    /// returns: AllocationStatistics, or an error if a realtime timestamp is invalid
+    pub fn get_allocation_statistics_realtime(&self, start: u64, end: u64) -> Result<AllocationStatistics, DamselflyError> {
+        let start = self.graph_viewer.get_operation_timestamp_of_realtime_timestamp(start) as usize;
+        let end = self.graph_viewer.get_operation_timestamp_of_realtime_timestamp(end) as usize;
+        self.get_allocation_statistics(Some((start, end)))
+    }
+
     pub fn set_map_block_size(&mut self, new_size: usize) {
         self.map_viewer.set_block_size(new_size);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdate, MemoryUpdateType};
+    use crate::damselfly::memory::pool_lifetime::{PoolEvent, PoolEventKind, PoolLifetime};
+    use crate::damselfly::viewer::damselfly_instance::DamselflyInstance;
+
+    fn update_in_thread(mut update: impl MemoryUpdate, thread_id: usize) -> MemoryUpdateType {
+        update.set_thread_id(Some(thread_id));
+        update.wrap_in_enum()
+    }
+
+    fn initialise_test_instance() -> DamselflyInstance {
+        let updates = vec![
+            update_in_thread(Allocation::new(0, 8, Arc::new(String::new()), 0, String::from("0001.676 s")), 1),
+            update_in_thread(Allocation::new(16, 8, Arc::new(String::new()), 1, String::from("0001.677 s")), 2),
+            update_in_thread(Allocation::new(32, 8, Arc::new(String::new()), 2, String::from("0001.678 s")), 1),
+            update_in_thread(Free::new(0, 8, Arc::new(String::new()), 3, String::from("0001.679 s")), 2),
+        ];
+        DamselflyInstance::new(String::from("test_pool"), updates, (0, 0), 0, 64, 2, 3).unwrap()
+    }
+
+    #[test]
+    fn get_thread_ids_test() {
+        let instance = initialise_test_instance();
+        assert_eq!(instance.get_thread_ids(), vec![1, 2]);
+    }
+
+    #[test]
+    fn set_thread_filter_test() {
+        let mut instance = initialise_test_instance();
+        instance.set_thread_filter(Some(vec![1])).unwrap();
+        assert_eq!(instance.get_operation_count(), 2);
+        assert_eq!(instance.get_max_usage(), 16);
+        assert_eq!(instance.query_block_naive(32).len(), 1);
+        assert_eq!(instance.query_block_naive(32)[0].get_timestamp(), 1);
+
+        instance.set_thread_filter(None).unwrap();
+        assert_eq!(instance.get_operation_count(), 4);
+        assert_eq!(instance.get_max_usage(), 24);
+    }
+
+    #[test]
+    fn set_thread_filter_graph_bounds_test() {
+        let mut instance = initialise_test_instance();
+        assert_eq!(instance.graph_viewer.get_max_timestamp(), 3);
+        assert_eq!(instance.graph_viewer.get_usage_plot_points().len(), 4);
+
+        instance.set_thread_filter(Some(vec![2])).unwrap();
+        assert_eq!(instance.graph_viewer.get_max_timestamp(), 1);
+        let usage_plot_points = instance.graph_viewer.get_usage_plot_points();
+        assert_eq!(usage_plot_points.len(), 2);
+        assert_eq!(usage_plot_points[1], [1.0, 0.0]);
+
+        instance.set_thread_filter(None).unwrap();
+        assert_eq!(instance.graph_viewer.get_max_timestamp(), 3);
+        assert_eq!(instance.graph_viewer.get_usage_plot_points().len(), 4);
+    }
+
+    #[test]
+    fn get_leak_report_test() {
+        let mut instance = initialise_test_instance();
+        let leaks = instance.get_leak_report(None);
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].get_count(), 2);
+        assert_eq!(leaks[0].get_addresses(), &vec![16, 32]);
+
+        instance.set_thread_filter(Some(vec![1])).unwrap();
+        let leaks = instance.get_leak_report(Some(0));
+        assert_eq!(leaks[0].get_addresses(), &vec![0]);
+    }
+
+    #[test]
+    fn pool_lifetime_test() {
+        let mut lifetime = PoolLifetime::new(Some((0, 64)));
+        lifetime.push_event(PoolEvent { kind: PoolEventKind::Resize { size: 24 }, position: 2, real_timestamp: String::new() });
+        lifetime.push_event(PoolEvent { kind: PoolEventKind::Delete, position: 4, real_timestamp: String::new() });
+        let mut instance = initialise_test_instance().with_pool_lifetime(Some(lifetime));
+        instance.map_viewer.set_block_size(8);
+        let statuses = |instance: &mut DamselflyInstance, timestamp: u64| -> Vec<u64> {
+            instance.get_map_full_at_nosync_colours_truncate(timestamp, u64::MAX).1
+                .iter()
+                .map(|(_, status, _)| *status)
+                .collect()
+        };
+        assert_eq!(statuses(&mut instance, 0), vec![3, 0, 0, 0, 0]);
+        assert_eq!(statuses(&mut instance, 1), vec![3, 0, 3, 0, 0]);
+        // 32 is allocated, but outside the resized pool
+        assert_eq!(statuses(&mut instance, 2), vec![3, 0, 3, 0, 0]);
+        assert_eq!(statuses(&mut instance, 3), vec![0, 0, 0, 0, 0]);
+
+        // Thread 1 made operations 0 and 2, so the resize comes after the first operation shown
+        instance.set_thread_filter(Some(vec![1])).unwrap();
+        assert_eq!(statuses(&mut instance, 0), vec![3, 0, 0, 0, 0]);
+        assert_eq!(statuses(&mut instance, 1), vec![0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn set_thread_filter_no_operations_test() {
+        let mut instance = initialise_test_instance();
+        assert!(instance.set_thread_filter(Some(vec![3])).is_err());
+        assert_eq!(instance.get_operation_count(), 4);
+        assert!(instance.get_thread_filter().is_none());
+    }
+}
@@ -4,15 +4,20 @@
 //! DamselflyViewer also exposes methods for querying each DamselflyInstance to generate memory maps,
 //! get graphs etc.
 use std::cmp::min;
+use crate::damselfly::damselfly_error::DamselflyError;
 use crate::damselfly::memory::memory_parsers::{MemoryParser};
 use crate::damselfly::memory::memory_pool::MemoryPool;
 use crate::damselfly::memory::memory_update::MemoryUpdateType;
-use crate::damselfly::memory::memory_usage_factory::MemoryUsageFactory;
-use crate::damselfly::memory::memory_usage_stats::MemoryUsageStats;
+use crate::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+use crate::damselfly::memory::pool_lifetime::PoolLifetime;
+use crate::damselfly::memory::trace_anomaly::TraceAnomaly;
+use crate::damselfly::memory::trace_validator::TraceValidator;
 use crate::damselfly::viewer::damselfly_instance::DamselflyInstance;
 
 pub struct DamselflyViewer {
     pub damselflies: Vec<DamselflyInstance>,
+    pub diagnostics: Vec<ParseDiagnostic>,
+    pub anomalies: Vec<TraceAnomaly>,
 }
 
 impl DamselflyViewer {
@@ -27,7 +32,7 @@ impl DamselflyViewer {
     /// * `distinct_block_right_padding`: Padding to the right of each memory update (increases the size.
     /// * `parser`: The parser used to parse the log file. You can implement your own if you like.
     ///
-    /// returns: DamselflyViewer
+    /// returns: DamselflyViewer, or the error that stopped the log from being parsed
     pub fn new(
         log_path: &str,
         binary_path: &str,
@@ -35,14 +40,22 @@ impl DamselflyViewer {
         distinct_block_left_padding: usize,
         distinct_block_right_padding: usize,
         parser: impl MemoryParser
-    ) -> Self {
+    ) -> Result<Self, DamselflyError> {
         let mut damselfly_viewer = DamselflyViewer {
             damselflies: Vec::new(),
+            diagnostics: Vec::new(),
+            anomalies: Vec::new(),
         };
-        let pool_restricted_parse_results = parser.parse_log_contents_split_by_pools(log_path, binary_path, distinct_block_left_padding, distinct_block_right_padding);
+        let mut parse_results = parser.parse_log(log_path, binary_path)?;
+        damselfly_viewer.diagnostics = std::mem::take(&mut parse_results.diagnostics);
+        damselfly_viewer.anomalies = TraceValidator::validate(&parse_results.memory_updates);
+        let pool_restricted_parse_results = parse_results.split_by_pools(distinct_block_left_padding, distinct_block_right_padding)?;
         for parse_results in &pool_restricted_parse_results {
+            // A pool that is created but never used has nothing to show
+            if parse_results.memory_updates.is_empty() {
+                continue;
+            }
             let (memory_updates, max_timestamp) = (parse_results.memory_updates.clone(), parse_results.max_timestamp);
-            let (pool_start, pool_stop) = (parse_results.pool.get_start(), parse_results.pool.get_start() + parse_results.pool.get_size());
             let mut resampled_memory_updates = Vec::new();
             // This should really be iter_mut, but I don't want to break anything
             for (index, memory_update) in memory_updates.iter().enumerate() {
@@ -58,16 +71,13 @@ impl DamselflyViewer {
             }
             
             let cache_size = min(cache_size, resampled_memory_updates.len() as u64);
-            let memory_usage_stats = MemoryUsageFactory::new(resampled_memory_updates.clone(), 
-                                                             distinct_block_left_padding,
-                                                             distinct_block_right_padding,
-                                                             pool_start,
-                                                             pool_stop,
-                                                            ).calculate_usage_stats();
-            damselfly_viewer.spawn_damselfly(resampled_memory_updates, memory_usage_stats, parse_results.pool.clone(), max_timestamp, cache_size);
+            damselfly_viewer.spawn_damselfly(resampled_memory_updates,
+                                             (distinct_block_left_padding, distinct_block_right_padding),
+                                             parse_results.pool.clone(), parse_results.pool_lifetime.clone(),
+                                             max_timestamp, cache_size)?;
         }
 
-        damselfly_viewer
+        Ok(damselfly_viewer)
     }
 
     /// Spawns a DamselflyInstance. Each DamselflyInstance manages a single memory pool, encapsulating
@@ -76,23 +86,52 @@ impl DamselflyViewer {
     /// # Arguments
     ///
     /// * `memory_updates`: Vec of memory updates.
-    /// * `memory_usage_stats`: Memory usage stats.
+    /// * `padding`: (left, right) padding applied to the memory updates.
     /// * `pool`: Pool to associate with this instance.
+    /// * `pool_lifetime`: When the pool is created, resized and deleted, or None if it exists for
+    ///   the whole trace.
     /// * `max_timestamp`: Max timestamp in this instance.
     /// * `cache_size`: Cache size for this instance.
     ///
-    /// returns: ()
-    fn spawn_damselfly(&mut self, memory_updates: Vec<MemoryUpdateType>, memory_usage_stats: MemoryUsageStats, pool: MemoryPool, max_timestamp: u64, cache_size: u64) {
+    /// returns: (), or BadTimestamp if an update's realtime timestamp is invalid
+    fn spawn_damselfly(&mut self, memory_updates: Vec<MemoryUpdateType>, padding: (usize, usize), pool: MemoryPool,
+                       pool_lifetime: Option<PoolLifetime>, max_timestamp: u64, cache_size: u64) -> Result<(), DamselflyError> {
         self.damselflies.push(
             DamselflyInstance::new(
                 pool.get_name().to_string(),
                 memory_updates,
-                memory_usage_stats,
+                padding,
                 pool.get_start(),
                 pool.get_start() + pool.get_size(),
                 cache_size as usize,
                 max_timestamp,
-            )
+            )?.with_pool_lifetime(pool_lifetime)
         );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+    use crate::damselfly::memory::memory_parsers::MemorySysTraceParser;
+    use crate::damselfly::viewer::damselfly_viewer::DamselflyViewer;
+
+    #[test]
+    fn unused_pool_test() {
+        let log = "\
+00000150: 03c30500 |V|A|005|        0 us   0003.936 s    < DT:  unknown > POOLCREATE empty_pool f1000000 64
+00000151: 03c30500 |V|A|005|        0 us   0003.936 s    < DT:  unknown > POOLCREATE task_pool e1000000 64
+00000152: 03c30560 |V|A|005|        0 us   0003.937 s    < DT:  unknown > + e1000000 10
+";
+        let log_path = env::temp_dir().join(format!("damselfly-unused-pool-test-{}.log", std::process::id()));
+        fs::write(&log_path, log).unwrap();
+        // The log has no stack traces, so no binary is needed
+        let viewer = DamselflyViewer::new(&log_path.to_string_lossy(), "", 1000, 0, 0, MemorySysTraceParser::new());
+        fs::remove_file(&log_path).unwrap();
+
+        let viewer = viewer.unwrap();
+        let pool_names: Vec<&str> = viewer.damselflies.iter().map(|damselfly| damselfly.get_name()).collect();
+        assert_eq!(pool_names, vec!["task_pool"]);
     }
 }
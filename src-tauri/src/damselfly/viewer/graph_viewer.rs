@@ -210,15 +210,27 @@ impl GraphViewer {
         self.sampled_memory_usage_snapshots.get_operation_timestamps_in_realtime_timestamp(realtime_timestamp).1
     }
 
-    fn get_max_usage(&self) -> i128 {
+    pub fn get_max_usage(&self) -> i128 {
         self.max_usage
     }
 
-    fn get_max_distinct_blocks(&self) -> usize {
+    pub fn get_max_distinct_blocks(&self) -> usize {
         self.max_distinct_blocks
     }
     
-    fn get_max_free_blocks(&self) -> u128 {
+    pub fn get_max_free_blocks(&self) -> u128 {
         self.max_free_blocks
     }
+
+    pub fn get_max_free_segment_fragmentation(&self) -> u128 {
+        self.max_free_segment_fragmentation
+    }
+
+    pub fn get_max_largest_free_block(&self) -> u128 {
+        self.max_largest_free_block
+    }
+
+    pub fn get_max_timestamp(&self) -> u64 {
+        self.max_timestamp
+    }
 }
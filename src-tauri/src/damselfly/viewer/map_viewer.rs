@@ -34,9 +34,9 @@ impl MapViewer {
             prev.val.get_absolute_address().cmp(&next.val.get_absolute_address())
         }).expect("[MapViewer::new]: Cannot find highest address").val.get_absolute_address();
 
-        println!("Reported pool bounds from log: {lowest_address} -> {highest_address}");
-        println!("Analysed pool bounds from instructions: {analysed_lowest_address} -> {analysed_highest_address}");
-        println!("The reported pool bounds should be larger than or equal to the analysed bounds.");
+        eprintln!("Reported pool bounds from log: {lowest_address} -> {highest_address}");
+        eprintln!("Analysed pool bounds from instructions: {analysed_lowest_address} -> {analysed_highest_address}");
+        eprintln!("The reported pool bounds should be larger than or equal to the analysed bounds.");
 
         MapViewer {
             map_name,
@@ -52,7 +52,7 @@ impl MapViewer {
     }
 
     pub fn get_update_history(&self, history_size: usize) -> Vec<MemoryUpdateType> {
-        println!("[get_update_history]: current timestamp: {}", self.current_timestamp);
+        eprintln!("[get_update_history]: current timestamp: {}", self.current_timestamp);
         let mut update_history = Vec::new();
         for update in &self.update_intervals {
             if update.val.get_timestamp() > self.current_timestamp {
@@ -54,6 +54,19 @@ impl Block {
                     .clamp(usize::MIN, self.block_bounds.1 - self.block_bounds.0);
                 self.update_block_status(free.get_absolute_address(), free.get_absolute_size(), free.get_callstack());
             }
+            MemoryUpdateType::Reallocation(reallocation) => {
+                // The span of a reallocation covers both the old and new allocations, so this
+                // block may sit in the gap between them and be untouched
+                let bytes_freed = self.get_overlap(reallocation.get_old_address(), reallocation.get_old_size());
+                let bytes_allocated = self.get_overlap(reallocation.get_absolute_address(), reallocation.get_absolute_size());
+                if bytes_freed == 0 && bytes_allocated == 0 {
+                    return;
+                }
+                self.remaining_bytes = self.remaining_bytes.saturating_add(bytes_freed)
+                    .clamp(usize::MIN, self.block_bounds.1 - self.block_bounds.0)
+                    .saturating_sub(bytes_allocated);
+                self.update_block_status(reallocation.get_absolute_address(), reallocation.get_absolute_size(), reallocation.get_callstack());
+            }
         }
     }
 
@@ -69,6 +82,18 @@ impl Block {
         self.block_bounds.1
     }
 
+    /// Gets the number of bytes in this block that overlap with the given span.
+    ///
+    /// # Arguments
+    ///
+    /// * `address`: Start of the span.
+    /// * `size`: Size of the span.
+    ///
+    /// returns: The number of overlapping bytes.
+    fn get_overlap(&self, address: usize, size: usize) -> usize {
+        min(self.block_bounds.1, address + size).saturating_sub(max(self.block_bounds.0, address))
+    }
+
     /// Updates the block's status depending on how many bytes it has left unallocated.
     /// 
     /// # Arguments 
@@ -95,7 +120,7 @@ impl Block {
 mod tests {
     use std::sync::Arc;
     use crate::damselfly::memory::memory_status::MemoryStatus;
-    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdateType};
+    use crate::damselfly::memory::memory_update::{Allocation, Free, MemoryUpdateType, Reallocation};
     use crate::damselfly::viewer::memory_block::Block;
 
     #[test]
@@ -148,4 +173,33 @@ mod tests {
         assert!(matches!(block.get_block_status(), MemoryStatus::Free(..)));
         assert_eq!(block.remaining_bytes, 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn paint_block_realloc_in_place_growth() {
+        let mut block = Block::new(4, 4);
+        let ops = vec![
+            MemoryUpdateType::Allocation(Allocation::new(0, 6, Arc::new("alloc".to_string()), 0, "0".to_string())),
+            MemoryUpdateType::Reallocation(Reallocation::new(0, 8, 0, 6, Arc::new("realloc".to_string()), 1, "1".to_string())),
+        ];
+        for op in &ops {
+            block.paint_block(op);
+        }
+        assert!(matches!(block.get_block_status(), MemoryStatus::Allocated(..)));
+        assert_eq!(block.remaining_bytes, 0);
+    }
+
+    #[test]
+    fn paint_block_realloc_moved() {
+        let mut old_block = Block::new(0, 4);
+        let mut gap_block = Block::new(4, 4);
+        let mut new_block = Block::new(8, 4);
+        old_block.paint_block(&MemoryUpdateType::Allocation(Allocation::new(0, 4, Arc::new("alloc".to_string()), 0, "0".to_string())));
+        let realloc = MemoryUpdateType::Reallocation(Reallocation::new(8, 4, 0, 4, Arc::new("realloc".to_string()), 1, "1".to_string()));
+        old_block.paint_block(&realloc);
+        gap_block.paint_block(&realloc);
+        new_block.paint_block(&realloc);
+        assert!(matches!(old_block.get_block_status(), MemoryStatus::Free(..)));
+        assert!(matches!(gap_block.get_block_status(), MemoryStatus::Unused(..)));
+        assert!(matches!(new_block.get_block_status(), MemoryStatus::Allocated(..)));
+    }
+}
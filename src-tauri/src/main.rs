@@ -5,7 +5,16 @@
 use damselfly3::damselfly::memory::memory_update::MemoryUpdateType;
 use damselfly3::damselfly::viewer::damselfly_viewer::DamselflyViewer;
 use std::sync::{Arc, Mutex};
-use damselfly3::damselfly::memory::memory_parsers::MemorySysTraceParser;
+use damselfly3::damselfly::memory::log_format::{FormatParser, LogFormat};
+use damselfly3::damselfly::memory::parse_diagnostic::ParseDiagnostic;
+use damselfly3::damselfly::memory::systrace_layout::SysTraceLayout;
+use damselfly3::damselfly::memory::symbol_options::SymbolOptions;
+use damselfly3::damselfly::memory::binary_module::BinaryModule;
+use damselfly3::damselfly::memory::symbol_cache::SymbolCache;
+use damselfly3::damselfly::memory::trace_anomaly::TraceAnomaly;
+use damselfly3::damselfly::memory::leak_report_factory::LeakGroup;
+use damselfly3::damselfly::memory::allocation_site_factory::AllocationSite;
+use damselfly3::damselfly::memory::allocation_statistics::AllocationStatistics;
 
 struct AppState {
     viewer: Arc<Mutex<Option<DamselflyViewer>>>,
@@ -44,15 +53,48 @@ fn main() {
             query_block,
             query_block_realtime,
             get_pool_list,
+            get_parse_diagnostics,
+            get_trace_anomalies,
+            get_thread_ids,
+            set_thread_filter,
+            get_leak_report,
+            get_allocation_sites,
+            get_allocation_statistics,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
 #[tauri::command(rename_all = "snake_case")]
-fn initialise_viewer(state: tauri::State<AppState>, log_path: String, binary_path: String, cache_size: u64, distinct_block_left_padding: usize, distinct_block_right_padding: usize) {
-    let viewer = DamselflyViewer::new(&log_path, &binary_path, cache_size, distinct_block_left_padding, distinct_block_right_padding, MemorySysTraceParser::new());
+fn initialise_viewer(state: tauri::State<AppState>, log_path: String, binary_path: String, cache_size: u64, distinct_block_left_padding: usize, distinct_block_right_padding: usize, lenient: bool, log_format: Option<String>, layout_path: Option<String>, debug_dirs: Vec<String>, path_remaps: Vec<String>, modules: Vec<String>) -> Result<(), String> {
+    let format = match log_format {
+        Some(log_format) => log_format.parse::<LogFormat>()?,
+        None => LogFormat::detect(&log_path).map_err(|error| error.to_string())?,
+    };
+    let mut parser = FormatParser::new(format, lenient);
+    if let Some(layout_path) = layout_path {
+        let layout = SysTraceLayout::load(&layout_path).map_err(|error| error.to_string())?;
+        parser = parser.with_systrace_layout(layout);
+    }
+    let mut symbol_options = SymbolOptions::new();
+    for debug_dir in debug_dirs {
+        symbol_options = symbol_options.with_debug_dir(debug_dir);
+    }
+    for path_remap in path_remaps {
+        let (from, to) = SymbolOptions::parse_path_remap(&path_remap)?;
+        symbol_options = symbol_options.with_path_remap(from, to);
+    }
+    for module in modules {
+        symbol_options = symbol_options.with_module(BinaryModule::parse(&module)?);
+    }
+    if let Some(cache_dir) = SymbolCache::default_dir() {
+        symbol_options = symbol_options.with_cache_dir(cache_dir);
+    }
+    parser = parser.with_symbol_options(symbol_options);
+    let viewer = DamselflyViewer::new(&log_path, &binary_path, cache_size, distinct_block_left_padding, distinct_block_right_padding, parser)
+        .map_err(|error| error.to_string())?;
     state.viewer.lock().unwrap().replace(viewer);
+    Ok(())
 }
 
 #[tauri::command]
@@ -456,3 +498,104 @@ fn get_pool_list(state: tauri::State<AppState>) -> Result<Vec<String>, String> {
         Err("Viewer is not initialised".to_string())
     }
 }
+
+#[tauri::command]
+fn get_parse_diagnostics(state: tauri::State<AppState>) -> Result<Vec<ParseDiagnostic>, String> {
+    let viewer_lock = state.viewer.lock().unwrap();
+    if let Some(viewer) = &*viewer_lock {
+        Ok(viewer.diagnostics.clone())
+    } else {
+        Err("Viewer is not initialised".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_trace_anomalies(state: tauri::State<AppState>) -> Result<Vec<TraceAnomaly>, String> {
+    let viewer_lock = state.viewer.lock().unwrap();
+    if let Some(viewer) = &*viewer_lock {
+        Ok(viewer.anomalies.clone())
+    } else {
+        Err("Viewer is not initialised".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_thread_ids(state: tauri::State<AppState>, damselfly_instance: u64) -> Result<Vec<usize>, String> {
+    let viewer_lock = state.viewer.lock().unwrap();
+    if let Some(viewer) = &*viewer_lock {
+        Ok(viewer
+            .damselflies
+            .get(damselfly_instance as usize)
+            .expect("[tauri::command::get_thread_ids]: damselfly_instance not found")
+            .get_thread_ids())
+    } else {
+        Err("Viewer is not initialised".to_string())
+    }
+}
+
+#[tauri::command]
+fn set_thread_filter(state: tauri::State<AppState>, damselfly_instance: u64, thread_ids: Option<Vec<usize>>) -> Result<(), String> {
+    let mut viewer_lock = state.viewer.lock().unwrap();
+    if let Some(viewer) = &mut *viewer_lock {
+        viewer
+            .damselflies
+            .get_mut(damselfly_instance as usize)
+            .expect("[tauri::command::set_thread_filter]: damselfly_instance not found")
+            .set_thread_filter(thread_ids)
+            .map_err(|error| error.to_string())
+    } else {
+        Err("Viewer is not initialised".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_leak_report(state: tauri::State<AppState>, damselfly_instance: u64, timestamp: Option<u64>, realtime: bool) -> Result<Vec<LeakGroup>, String> {
+    let viewer_lock = state.viewer.lock().unwrap();
+    if let Some(viewer) = &*viewer_lock {
+        let instance = viewer
+            .damselflies
+            .get(damselfly_instance as usize)
+            .expect("[tauri::command::get_leak_report]: damselfly_instance not found");
+        Ok(match timestamp {
+            Some(timestamp) if realtime => instance.get_leak_report_realtime(timestamp),
+            timestamp => instance.get_leak_report(timestamp.map(|timestamp| timestamp as usize)),
+        })
+    } else {
+        Err("Viewer is not initialised".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_allocation_sites(state: tauri::State<AppState>, damselfly_instance: u64, frames: Option<usize>, limit: usize) -> Result<Vec<AllocationSite>, String> {
+    let viewer_lock = state.viewer.lock().unwrap();
+    if let Some(viewer) = &*viewer_lock {
+        let mut sites = viewer
+            .damselflies
+            .get(damselfly_instance as usize)
+            .expect("[tauri::command::get_allocation_sites]: damselfly_instance not found")
+            .get_allocation_sites(frames)
+            .map_err(|error| error.to_string())?;
+        sites.truncate(limit);
+        Ok(sites)
+    } else {
+        Err("Viewer is not initialised".to_string())
+    }
+}
+
+#[tauri::command]
+fn get_allocation_statistics(state: tauri::State<AppState>, damselfly_instance: u64, range: Option<(u64, u64)>, realtime: bool) -> Result<AllocationStatistics, String> {
+    let viewer_lock = state.viewer.lock().unwrap();
+    if let Some(viewer) = &*viewer_lock {
+        let instance = viewer
+            .damselflies
+            .get(damselfly_instance as usize)
+            .expect("[tauri::command::get_allocation_statistics]: damselfly_instance not found");
+        let statistics = match range {
+            Some((start, end)) if realtime => instance.get_allocation_statistics_realtime(start, end),
+            range => instance.get_allocation_statistics(range.map(|(start, end)| (start as usize, end as usize))),
+        };
+        statistics.map_err(|error| error.to_string())
+    } else {
+        Err("Viewer is not initialised".to_string())
+    }
+}
@@ -0,0 +1,82 @@
+import {useEffect, useState} from "react";
+import {invoke} from "@tauri-apps/api/tauri";
+import Data from "./Data.tsx";
+
+interface AllocationSitesProps {
+    activeInstance: number;
+    memoryData: Data;
+}
+
+interface AllocationSite {
+    callstack: string;
+    allocation_count: number;
+    total_bytes: number;
+    peak_live_bytes: number;
+    mean_lifetime: number | null;
+    mean_lifetime_microseconds: number | null;
+}
+
+const SITE_LIMIT = 50;
+
+function AllocationSites({ activeInstance, memoryData }: AllocationSitesProps) {
+    const [sites, setSites] = useState<AllocationSite[]>([]);
+    // Blank groups by the full callstack
+    const [frames, setFrames] = useState<string>("");
+
+    useEffect(() => {
+        const fetchSites = async () => {
+            const framesInt = parseInt(frames);
+            try {
+                const fetchedSites = await invoke<AllocationSite[]>("get_allocation_sites", {
+                    damselflyInstance: activeInstance,
+                    frames: isNaN(framesInt) || framesInt <= 0 ? null : framesInt,
+                    limit: SITE_LIMIT
+                });
+                setSites(fetchedSites);
+            } catch (error) {
+                console.error("Failed to fetch allocation sites", error);
+            }
+        }
+        fetchSites().then();
+    }, [activeInstance, memoryData, frames]);
+
+    const formatLifetime = (site: AllocationSite) => {
+        if (site.mean_lifetime === null || site.mean_lifetime_microseconds === null) {
+            return "never freed";
+        }
+        return `${site.mean_lifetime.toFixed(1)} ops (${site.mean_lifetime_microseconds.toFixed(0)} us)`;
+    }
+
+    return (
+        <div className="log-container">
+            <label>
+                Frames to group by (blank for full callstack):
+                <input type="number" min="1" value={frames} onChange={(event) => setFrames(event.target.value)} />
+            </label>
+            <table className="leak-table">
+                <thead>
+                    <tr>
+                        <th>Bytes</th>
+                        <th>Count</th>
+                        <th>Peak live bytes</th>
+                        <th>Mean lifetime</th>
+                        <th>Callstack</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {sites.map((site, index) => (
+                        <tr key={index}>
+                            <td>{site.total_bytes}</td>
+                            <td>{site.allocation_count}</td>
+                            <td>{site.peak_live_bytes}</td>
+                            <td>{formatLifetime(site)}</td>
+                            <td><pre>{site.callstack}</pre></td>
+                        </tr>
+                    ))}
+                </tbody>
+            </table>
+        </div>
+    )
+}
+
+export default AllocationSites;
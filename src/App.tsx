@@ -7,6 +7,9 @@ import MapGrid from "./MapGridComponent";
 import OperationLog from "./OperationLogComponent.tsx";
 import GraphSlider from "./GraphSliderComponent.tsx";
 import Callstack from "./CallstackComponent.tsx";
+import LeakReport from "./LeakReportComponent.tsx";
+import AllocationSites from "./AllocationSitesComponent.tsx";
+import Statistics from "./StatisticsComponent.tsx";
 import BlockStatus from "./BlockStatusComponent.tsx";
 import Data from "./Data.tsx";
 import '@fontsource/roboto/300.css';
@@ -14,6 +17,7 @@ import '@fontsource/roboto/400.css';
 import '@fontsource/roboto/500.css';
 import '@fontsource/roboto/700.css';
 import PoolSelector from "./PoolSelector.tsx";
+import ThreadSelector from "./ThreadSelector.tsx";
 
 function App() {
   const [dataLoaded, setDataLoaded] = useState<boolean>(false);
@@ -32,6 +36,9 @@ function App() {
   const [selectedPool, setSelectedPool] = useState<number>(0);
   const [leftPadding, setLeftPadding] = useState<number>(0);
   const [rightPadding, setRightPadding] = useState<number>(0);
+  const [threadList, setThreadList] = useState<number[]>([]);
+  const [selectedThreads, setSelectedThreads] = useState<number[]>([]);
+  const [threadFilterVersion, setThreadFilterVersion] = useState<number>(0);
 
   useEffect(() => {
     const fetchData = async () => {
@@ -67,7 +74,24 @@ function App() {
       }
     };
     fetchData();
-  }, [xClick, dataLoaded, blockSize, selectedPool, realtimeGraph]);
+  }, [xClick, dataLoaded, blockSize, selectedPool, realtimeGraph, threadFilterVersion]);
+
+  useEffect(() => {
+    const fetchThreads = async () => {
+      if (dataLoaded) {
+        try {
+          const threads: number[] = await invoke("get_thread_ids", { damselflyInstance: selectedPool });
+          setThreadList(threads);
+          setSelectedThreads([]);
+          await invoke("set_thread_filter", { damselflyInstance: selectedPool, threadIds: null });
+          setThreadFilterVersion((version) => version + 1);
+        } catch (error) {
+          console.error("Error fetching thread list: ", error);
+        }
+      }
+    };
+    fetchThreads();
+  }, [dataLoaded, selectedPool]);
 
   const selectFilesAndInitialiseViewer = async () => {
     try {
@@ -95,13 +119,47 @@ function App() {
       }
       setLeftPadding(parseInt(left_padding));
       setRightPadding(parseInt(right_padding));
+      const lenient = confirm("Skip malformed records instead of failing?\n");
+      // Blank detects the format from the log
+      const logFormat = prompt("Enter the log format (systrace, mtrace, massif, dhat, heaptrack, kmem, damselfly or binary), or leave it blank to detect it.\n");
+      // Blank uses the default SysTrace line layout
+      const layoutPath = !logFormat || logFormat.toLowerCase() === "systrace"
+          ? prompt("Enter the path to a SysTrace line layout file (JSON or TOML), or leave it blank for the default layout.\n")
+          : null;
+      // Blank uses only the debuginfo in the binary and trims the common source directories
+      const symbolFormats = ["systrace", "mtrace"];
+      const symbolised = !logFormat || symbolFormats.includes(logFormat.toLowerCase());
+      const debugDirs = symbolised
+          ? prompt("Enter directories to search for separate debuginfo files, separated by commas, or leave it blank.\n")
+          : null;
+      const pathRemaps = symbolised
+          ? prompt("Enter source path remappings as FROM=TO, separated by commas, or leave it blank.\n")
+          : null;
+      const modules = symbolised
+          ? prompt("Enter other binaries loaded by the program as PATH@START-END (hex addresses), separated by commas, or leave it blank.\n")
+          : null;
+      const splitList = (list: string | null) => list ? list.split(",").map((item) => item.trim()).filter((item) => item.length > 0) : [];
 
       if (logFilePath && binaryFilePath) {
-        await invoke("initialise_viewer", { log_path: logFilePath, binary_path: binaryFilePath, cache_size: cacheSizeInt, distinct_block_left_padding: parseInt(left_padding), distinct_block_right_padding: parseInt(right_padding) });
+        await invoke("initialise_viewer", { log_path: logFilePath, binary_path: binaryFilePath, cache_size: cacheSizeInt, distinct_block_left_padding: parseInt(left_padding), distinct_block_right_padding: parseInt(right_padding), lenient: lenient, log_format: logFormat ? logFormat : null, layout_path: layoutPath ? layoutPath : null, debug_dirs: splitList(debugDirs), path_remaps: splitList(pathRemaps), modules: splitList(modules) });
         setDataLoaded(true);
+        const diagnostics: { line: number, text: string, reason: string }[] = await invoke("get_parse_diagnostics");
+        if (diagnostics.length > 0) {
+          console.warn("Skipped records: ", diagnostics);
+          alert(`Skipped ${diagnostics.length} malformed records. First skipped record:\nLine ${diagnostics[0].line}: ${diagnostics[0].reason}`);
+        }
+        const anomalies: { kind: string }[] = await invoke("get_trace_anomalies");
+        if (anomalies.length > 0) {
+          console.warn("Trace anomalies: ", anomalies);
+          const kinds = ["DoubleFree", "UnknownFree", "OverlappingAllocation"]
+              .map(kind => `${kind}: ${anomalies.filter(anomaly => anomaly.kind === kind).length}`)
+              .join("\n");
+          alert(`Found ${anomalies.length} anomalies in the trace.\n${kinds}`);
+        }
       }
     } catch (error) {
       console.error("Error initialising viewer: ", error);
+      alert(`Failed to load trace:\n${error}`);
     }
   }
 
@@ -141,23 +199,41 @@ function App() {
     setSelectedPool(index);
   };
 
+  // Selecting no threads shows every thread
+  const handleSelectThreads = async (threads: number[]) => {
+    try {
+      await invoke("set_thread_filter", { damselflyInstance: selectedPool, threadIds: threads.length > 0 ? threads : null });
+      setSelectedThreads(threads);
+      setXClick(0);
+      setThreadFilterVersion(threadFilterVersion + 1);
+    } catch (error) {
+      alert(`Failed to filter threads:\n${error}`);
+    }
+  };
+
   return (
       <div className="container">
         <div className="mainContent">
           <div className="left">
             <div className="top">
-              <Graph activeInstance={selectedPool} dataLoaded={dataLoaded} realtimeGraph={realtimeGraph} setXClick={setXClick} xClick={xClick} setXLimit={setXLimit} setRealtimeGraphOffset={setRealtimeGraphOffset} />
+              <Graph activeInstance={selectedPool} threadFilterVersion={threadFilterVersion} dataLoaded={dataLoaded} realtimeGraph={realtimeGraph} setXClick={setXClick} xClick={xClick} setXLimit={setXLimit} setRealtimeGraphOffset={setRealtimeGraphOffset} />
               <GraphSlider xClick={xClick} setXClick={setXClick} xLimit={xLimit}/>
             </div>
             <div className="tabs">
               <button onClick={() => setActiveTab('operationLog')} className={activeTab === 'operationLog' ? 'active' : ''}>Operation Log</button>
               <button onClick={() => setActiveTab('callstack')} className={activeTab === 'callstack' ? 'active' : ''}>Callstack</button>
               <button onClick={() => setActiveTab('block')} className={activeTab === 'block' ? 'active' : ''}>Block</button>
+              <button onClick={() => setActiveTab('leaks')} className={activeTab === 'leaks' ? 'active' : ''}>Leaks</button>
+              <button onClick={() => setActiveTab('sites')} className={activeTab === 'sites' ? 'active' : ''}>Sites</button>
+              <button onClick={() => setActiveTab('statistics')} className={activeTab === 'statistics' ? 'active' : ''}>Statistics</button>
             </div>
             <div className="tabContent">
               {activeTab === 'operationLog' && <OperationLog activeInstance={selectedPool} memoryData={memoryData} dataLoaded={dataLoaded} xClick={xClick} setSelectedBlock={setSelectedBlock} setLookupTile={setLookupTile} setSelectedTile={setSelectedTile} setRealtimeGraph={setRealtimeGraph} setXClick={setXClick} leftPadding={leftPadding} rightPadding={rightPadding}/>}
               {activeTab === 'callstack' && <Callstack activeInstance={selectedPool} xClick={xClick} />}
               {activeTab === 'block' && <BlockStatus activeInstance={selectedPool} lookupTile={lookupTile} timestamp={realtimeGraph ? xClick + realtimeGraphOffset : xClick} realtimeGraph={realtimeGraph} leftPadding={leftPadding} rightPadding={rightPadding}/>}
+              {activeTab === 'leaks' && <LeakReport activeInstance={selectedPool} memoryData={memoryData} timestamp={realtimeGraph ? xClick + realtimeGraphOffset : xClick} realtimeGraph={realtimeGraph}/>}
+              {activeTab === 'sites' && <AllocationSites activeInstance={selectedPool} memoryData={memoryData}/>}
+              {activeTab === 'statistics' && <Statistics activeInstance={selectedPool} memoryData={memoryData} realtimeGraph={realtimeGraph}/>}
             </div>
             <div className="bottom">
               {/* GraphSlider or other components if needed */}
@@ -181,6 +257,9 @@ function App() {
           <div className="PoolSelector">
             <PoolSelector poolList={poolList} selectedPool={selectedPool} onSelectPool={handleSelectPool} />
           </div>
+          <div className="ThreadSelector">
+            <ThreadSelector threadList={threadList} selectedThreads={selectedThreads} onSelectThreads={handleSelectThreads} />
+          </div>
           <div className="memoryStateLegend">
             <div className="legend-item">
               <div className="legend-square" style={{ backgroundColor: 'red' }}></div>
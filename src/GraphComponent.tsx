@@ -5,6 +5,7 @@ import { LineChart, Line, XAxis, YAxis, CartesianGrid, Tooltip, Legend, Referenc
 
 interface GraphProps {
     activeInstance: number,
+    threadFilterVersion: number,
     dataLoaded: boolean,
     realtimeGraph: boolean,
     setXClick: (x: number) => void;
@@ -22,7 +23,7 @@ type GraphData = {
     largest_free_block: number,
 }
 
-function Graph({ activeInstance, dataLoaded, realtimeGraph, setXClick , xClick, setXLimit, setRealtimeGraphOffset }: GraphProps) {
+function Graph({ activeInstance, threadFilterVersion, dataLoaded, realtimeGraph, setXClick , xClick, setXLimit, setRealtimeGraphOffset }: GraphProps) {
     const [data, setData] = useState<GraphData[]>([]);
     const [chartWidth, setChartWidth] = useState(window.innerWidth / 2);
     const [chartHeight, _setChartHeight] = useState(300); // Maintain a fixed height or adjust as needed
@@ -129,7 +130,7 @@ function Graph({ activeInstance, dataLoaded, realtimeGraph, setXClick , xClick,
 
     useEffect(() => {
         fetchData().then();
-    }, [dataLoaded, realtimeGraph, activeInstance]);
+    }, [dataLoaded, realtimeGraph, activeInstance, threadFilterVersion]);
 
     useEffect(() => {
         window.addEventListener('resize', updateDimensions);
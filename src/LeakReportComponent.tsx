@@ -0,0 +1,71 @@
+import {useEffect, useState} from "react";
+import {invoke} from "@tauri-apps/api/tauri";
+import Data from "./Data.tsx";
+
+interface LeakReportProps {
+    activeInstance: number;
+    memoryData: Data;
+    timestamp: number;
+    realtimeGraph: boolean;
+}
+
+interface LeakGroup {
+    callstack: string;
+    count: number;
+    total_bytes: number;
+    addresses: number[];
+}
+
+function LeakReport({ activeInstance, memoryData, timestamp, realtimeGraph }: LeakReportProps) {
+    const [leaks, setLeaks] = useState<LeakGroup[]>([]);
+    const [atSelectedTime, setAtSelectedTime] = useState<boolean>(false);
+
+    useEffect(() => {
+        const fetchLeaks = async () => {
+            try {
+                const fetchedLeaks = await invoke<LeakGroup[]>("get_leak_report", {
+                    damselflyInstance: activeInstance,
+                    timestamp: atSelectedTime ? timestamp : null,
+                    realtime: realtimeGraph
+                });
+                setLeaks(fetchedLeaks);
+            } catch (error) {
+                console.error("Failed to fetch leak report", error);
+            }
+        }
+        fetchLeaks().then();
+    }, [activeInstance, memoryData, timestamp, realtimeGraph, atSelectedTime]);
+
+    const totalBytes = leaks.reduce((total, leak) => total + leak.total_bytes, 0);
+    const totalCount = leaks.reduce((total, leak) => total + leak.count, 0);
+
+    return (
+        <div className="log-container">
+            <label>
+                <input type="checkbox" checked={atSelectedTime} onChange={(event) => setAtSelectedTime(event.target.checked)} />
+                Live at selected time (otherwise at end of trace)
+            </label>
+            <div>{totalCount} live allocations, {totalBytes} bytes</div>
+            <table className="leak-table">
+                <thead>
+                    <tr>
+                        <th>Bytes</th>
+                        <th>Count</th>
+                        <th>Callstack</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {leaks.map((leak, index) => (
+                        <tr key={index} title={leak.addresses.map(address => `0x${address.toString(16)}`).join(" ")}>
+                            <td>{leak.total_bytes}</td>
+                            <td>{leak.count}</td>
+                            <td><pre>{leak.callstack}</pre></td>
+                        </tr>
+                    ))}
+                </tbody>
+            </table>
+        </div>
+    )
+}
+
+export default LeakReport;
@@ -0,0 +1,92 @@
+import {useEffect, useState} from "react";
+import {invoke} from "@tauri-apps/api/tauri";
+import {BarChart, Bar, XAxis, YAxis, CartesianGrid, Tooltip} from "recharts";
+import Data from "./Data.tsx";
+
+interface StatisticsProps {
+    activeInstance: number;
+    memoryData: Data;
+    realtimeGraph: boolean;
+}
+
+interface HistogramBucket {
+    lower: number;
+    upper: number;
+    count: number;
+}
+
+interface AllocationStatistics {
+    size_histogram: HistogramBucket[];
+    lifetime_histogram: HistogramBucket[];
+    lifetime_microseconds_histogram: HistogramBucket[];
+    allocation_count: number;
+    live_count: number;
+}
+
+function Histogram({ title, buckets }: { title: string, buckets: HistogramBucket[] }) {
+    const data = buckets.map(bucket => ({ name: `${bucket.lower}-${bucket.upper}`, count: bucket.count }));
+    return (
+        <div>
+            <div>{title}</div>
+            <BarChart width={500} height={200} data={data}>
+                <CartesianGrid strokeDasharray="3 3" />
+                <XAxis dataKey="name" />
+                <YAxis />
+                <Tooltip />
+                <Bar dataKey="count" fill="#8884d8" isAnimationActive={false} />
+            </BarChart>
+        </div>
+    )
+}
+
+function Statistics({ activeInstance, memoryData, realtimeGraph }: StatisticsProps) {
+    const [statistics, setStatistics] = useState<AllocationStatistics | null>(null);
+    // Blank start and end cover the whole trace
+    const [start, setStart] = useState<string>("");
+    const [end, setEnd] = useState<string>("");
+
+    useEffect(() => {
+        const fetchStatistics = async () => {
+            const startInt = parseInt(start);
+            const endInt = parseInt(end);
+            const range = isNaN(startInt) && isNaN(endInt) ? null
+                : [isNaN(startInt) ? 0 : startInt, isNaN(endInt) ? Number.MAX_SAFE_INTEGER : endInt];
+            try {
+                const fetchedStatistics = await invoke<AllocationStatistics>("get_allocation_statistics", {
+                    damselflyInstance: activeInstance,
+                    range: range,
+                    realtime: realtimeGraph
+                });
+                setStatistics(fetchedStatistics);
+            } catch (error) {
+                console.error("Failed to fetch allocation statistics", error);
+            }
+        }
+        fetchStatistics().then();
+    }, [activeInstance, memoryData, realtimeGraph, start, end]);
+
+    return (
+        <div className="log-container">
+            <div>
+                <label>
+                    Start ({realtimeGraph ? "realtime" : "operation"}):
+                    <input type="number" min="0" value={start} onChange={(event) => setStart(event.target.value)} />
+                </label>
+                <label>
+                    End:
+                    <input type="number" min="0" value={end} onChange={(event) => setEnd(event.target.value)} />
+                </label>
+            </div>
+            {statistics && (
+                <>
+                    <div>{statistics.allocation_count} allocations, {statistics.live_count} never freed</div>
+                    <Histogram title="Size (bytes)" buckets={statistics.size_histogram} />
+                    <Histogram title="Lifetime (operations)" buckets={statistics.lifetime_histogram} />
+                    <Histogram title="Lifetime (us)" buckets={statistics.lifetime_microseconds_histogram} />
+                </>
+            )}
+        </div>
+    )
+}
+
+export default Statistics;
@@ -0,0 +1,25 @@
+import React from 'react';
+
+interface ThreadSelectorProps {
+    threadList: number[];
+    selectedThreads: number[];
+    onSelectThreads: (threads: number[]) => void;
+}
+
+const ThreadSelector: React.FC<ThreadSelectorProps> = ({ threadList, selectedThreads, onSelectThreads }) => {
+    return (
+        <div>
+            <label htmlFor="threadSelector">Filter Threads:</label>
+            <select id="threadSelector" multiple value={selectedThreads.map(String)}
+                    onChange={(e) => onSelectThreads(Array.from(e.target.selectedOptions, (option) => parseInt(option.value)))}>
+                {threadList.map((thread, index) => (
+                    <option key={index} value={thread}>
+                        DT:0x{thread.toString(16).toUpperCase()}
+                    </option>
+                ))}
+            </select>
+        </div>
+    );
+};
+
+export default ThreadSelector;